@@ -10,6 +10,17 @@ async fn main() {
     if command.args.len() <= 1 {
         command.args.push("help".to_owned());
     }
+    let theme = match config_file::load_config_file() {
+        Ok(config) => config.theme,
+        Err(e) => {
+            println!("{}", e);
+            Default::default()
+        }
+    };
+    if let Err(e) = cli::init_color(&command, theme) {
+        println!("{}", e);
+        return;
+    }
 
     match command.args[1].as_str() {
         "help" => {
@@ -21,7 +32,27 @@ async fn main() {
             return;
         }
         command_ident => {
-            let (user, client) = match ensure_authorized_user().await {
+            let can_be_offline = OFFLINE_COMMANDS.contains(&command_ident);
+            if commands::is_offline(&command) && !can_be_offline {
+                println!(
+                    "`{}` needs to reach Monzo and can't be used with `--offline`",
+                    command_ident
+                );
+                return;
+            }
+            let uc = if commands::is_offline(&command) {
+                load_offline_user()
+            } else {
+                match ensure_authorized_user().await {
+                    Err(error::UserFileError::Expired) if can_be_offline => {
+                        eprintln!("using cached data until this application is authorized again");
+                        command.args_set.insert("--offline".to_owned());
+                        load_offline_user()
+                    }
+                    res => res,
+                }
+            };
+            let (mut user, client) = match uc {
                 Ok(uc) => uc,
                 Err(e) => {
                     println!("{}", e);
@@ -30,10 +61,24 @@ async fn main() {
             };
 
             let res = match command_ident {
-                "balance" => commands::balance(&user, &client, &command),
+                "balance" => commands::balance(&mut user, &client, &command),
                 "account" => commands::account(&user, &command),
+                "accounts" => commands::accounts(&mut user, &client, &command),
                 "token" => commands::token(&user, &command),
-				"transactions" => commands::transactions(&user, &client, &command),
+				"transactions" => commands::transactions(&mut user, &client, &command),
+                "recurring" => commands::recurring(&mut user, &client, &command),
+                "history" => commands::history(&mut user, &client, &command),
+                "tui" => commands::tui(&mut user, &client, &command),
+                "attachments" => commands::attachments(&mut user, &client, &command),
+                "expenses" => commands::expenses(&mut user, &client, &command),
+                "split" => commands::split(&mut user, &client, &command),
+                "categorise" => commands::categorise(&mut user, &client, &command),
+                "merchants" => commands::merchants(&mut user, &client, &command),
+                "fx" => commands::fx(&mut user, &client, &command),
+                "import" => commands::import(&mut user, &command),
+                "reconcile" => commands::reconcile(&mut user, &client, &command),
+                "daemon" => commands::daemon(&mut user, &client, &command).await,
+                "serve" => commands::serve(&mut user, &client, &command).await,
                 _ => Err(error::BadArgumentError(format!(
                     "invalid command `{}`, use `help` for a list of commands",
                     command_ident
@@ -47,16 +92,55 @@ async fn main() {
     }
 }
 
+/// The commands that can read from the cache instead of fetching from Monzo
+const OFFLINE_COMMANDS: &[&str] = &[
+    "balance",
+    "account",
+    "accounts",
+    "token",
+    "transactions",
+    "recurring",
+    "history",
+    "tui",
+    "expenses",
+    "split",
+    "merchants",
+    "fx",
+    "import",
+    "reconcile",
+];
+
+/// Loads the user file without checking the access token, for reading from the cache
+fn load_offline_user() -> Result<(user::User, reqwest::Client), error::UserFileError> {
+    match user_file::load_user_file() {
+        Ok(user) => {
+            let client = user.create_authorized_client();
+            Ok((user, client))
+        }
+        Err(_) => Err(error::UserFileError::InvalidOrAbsent),
+    }
+}
+
 /// Ensures that there is a valid user file present on this system (does not necessarily
 /// mean it has not expired)
 async fn ensure_authorized_user() -> Result<(user::User, reqwest::Client), error::UserFileError> {
-    let user = match user_file::load_user_file() {
+    let mut user = match user_file::load_user_file() {
         Ok(user) => user,
         Err(_) => return Err(error::UserFileError::InvalidOrAbsent),
     };
 
     let now = time::Time::now();
     if now >= user.access_token.expires {
+        if let Some(refresh_token) = user.access_token.refresh_token.clone() {
+            if let Ok(access_token_response) =
+                commands::refresh_access_token(&reqwest::Client::new(), refresh_token).await
+            {
+                user.access_token = user::AccessToken::new(access_token_response);
+                let _ = user_file::update_user_file(&user);
+                let client = user.create_authorized_client();
+                return Ok((user, client));
+            }
+        }
         println!(
             "found an access token, but it expired -- please authorize this \
             application again"
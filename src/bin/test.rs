@@ -2,4 +2,32 @@ use monzo::*;
 
 fn main() {
     monzo::tests::time_parsing(); 
+    monzo::tests::recurring_detection();
+    monzo::tests::filter_parsing();
+    monzo::tests::table_rendering();
+    monzo::tests::tui_cells();
+    monzo::tests::colour_parsing();
+    monzo::tests::template_rendering();
+    monzo::tests::template_money();
+    monzo::tests::statement_export();
+    monzo::tests::ledger_export();
+    monzo::tests::balance_history_reconstruction();
+    monzo::tests::account_merging();
+    monzo::tests::account_selection();
+    monzo::tests::daemon_events();
+    monzo::tests::metrics_rendering();
+    monzo::tests::balance_all_rendering();
+    monzo::tests::serve_api();
+    monzo::tests::attachment_dirs();
+    monzo::tests::expense_report();
+    monzo::tests::split_ledger();
+    monzo::tests::reauthorization();
+    monzo::tests::synced_user_merging();
+    monzo::tests::rule_categorisation();
+    monzo::tests::merchant_directory_grouping();
+    monzo::tests::balance_caching();
+    monzo::tests::statement_import();
+    monzo::tests::statement_reconciliation();
+    monzo::tests::pending_transactions();
+    monzo::tests::fx_report();
 }
\ No newline at end of file
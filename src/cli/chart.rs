@@ -0,0 +1,93 @@
+/// Bars from lowest to highest, a value is drawn with one of these
+const SPARK_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const ASCII_SPARK_BARS: [char; 8] = ['_', '.', '-', '~', '=', '+', '*', '#'];
+
+/// Eighths of a cell, from empty to full
+const EIGHTHS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Scales `values` to `0..=steps`, a flat series is drawn in the middle
+fn scale(values: &[i64], steps: usize) -> Vec<usize> {
+    let min = values.iter().copied().min().unwrap_or(0);
+    let max = values.iter().copied().max().unwrap_or(0);
+    values
+        .iter()
+        .map(|value| {
+            if max == min {
+                steps / 2
+            } else {
+                ((value - min) as f64 / (max - min) as f64 * steps as f64).round() as usize
+            }
+        })
+        .collect()
+}
+
+/// Shrinks `values` to at most `width` values, each one being the last value of a bucket so
+/// that e.g. a day's closing balance is kept
+fn resample(values: &[i64], width: usize) -> Vec<i64> {
+    if values.len() <= width || width == 0 {
+        return values.to_vec();
+    }
+    (1..=width)
+        .map(|bucket| values[bucket * values.len() / width - 1])
+        .collect()
+}
+
+/// Draws `values` on one line, one character per value
+pub fn sparkline(values: &[i64], ascii: bool) -> String {
+    let bars = if ascii { ASCII_SPARK_BARS } else { SPARK_BARS };
+    scale(values, bars.len() - 1)
+        .into_iter()
+        .map(|step| bars[step])
+        .collect()
+}
+
+/// Draws `values` as an area chart `height` lines tall with the lowest and highest values
+/// labelled on the left, values are resampled to fit within `width`
+pub fn area_chart(
+    values: &[i64],
+    labels: (&str, &str),
+    width: usize,
+    height: usize,
+    ascii: bool,
+) -> Vec<String> {
+    let (low_label, high_label) = labels;
+    let label_width = std::cmp::max(low_label.chars().count(), high_label.chars().count());
+    let values = resample(values, width.saturating_sub(label_width + 3));
+    // never draw an empty column, so the lowest value is still visible
+    let eighths: Vec<usize> = scale(&values, height * 8 - 1)
+        .into_iter()
+        .map(|step| step + 1)
+        .collect();
+
+    let axis = if ascii { '|' } else { '┤' };
+    let mut lines = vec![];
+    for row in (0..height).rev() {
+        let label = if row + 1 == height {
+            high_label
+        } else if row == 0 {
+            low_label
+        } else {
+            ""
+        };
+        let cells: String = eighths
+            .iter()
+            .map(|eighths| {
+                let filled = eighths.saturating_sub(row * 8).min(8);
+                match (ascii, filled) {
+                    (false, filled) => EIGHTHS[filled],
+                    (true, 0) => ' ',
+                    (true, 1..=4) => '.',
+                    (true, _) => '#',
+                }
+            })
+            .collect();
+        lines.push(format!(
+            "{:>width$} {} {}",
+            label,
+            axis,
+            cells,
+            width = label_width
+        ));
+    }
+    lines
+}
@@ -1,9 +1,17 @@
 use std::collections::{HashMap, HashSet};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
 
 use lazy_static::lazy_static;
 
+use crate::types::config::{Style, Theme};
 use crate::types::error::BadArgumentError;
 
+pub mod chart;
+pub mod table;
+pub mod template;
+
 /// Represents a parsed command
 pub struct Command {
     pub args: Vec<String>,
@@ -39,6 +47,23 @@ impl Command {
         }
     }
 
+    /// The args after the command that aren't flags or kwargs, e.g. `refresh` in
+    /// `monzo accounts refresh --detailed`
+    pub fn positional_args(&self) -> Vec<&str> {
+        self.args
+            .iter()
+            .skip(2)
+            .filter(|arg| {
+                !arg.starts_with('-')
+                    && !self
+                        .kwargs
+                        .keys()
+                        .any(|key| arg.starts_with(&format!("{}=", key)))
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
     /// tries to parse the speciifed kwarg into an int
     /// returns None if the key is not present
     /// returns Some(Ok(T)) if the key is present and the value for it can be
@@ -61,6 +86,138 @@ impl Command {
     }
 }
 
+/// How many colours the terminal supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ColorSupport {
+    /// No escape codes at all
+    None = 0,
+    Ansi16 = 1,
+    Ansi256 = 2,
+    TrueColor = 3,
+}
+
+static COLOR_SUPPORT: AtomicU8 = AtomicU8::new(ColorSupport::TrueColor as u8);
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Decides whether to use colour from `--color=always|never|auto`, `NO_COLOR` and whether stdout
+/// is a terminal, and sets the theme. This should be called once, before anything is printed
+pub fn init_color(command: &Command, theme: Theme) -> Result<(), BadArgumentError> {
+    let _ = THEME.set(theme);
+    let color_support = match command.kwargs.get("--color").map(String::as_str) {
+        Some("always") => std::cmp::max(terminal_color_support(), ColorSupport::Ansi16),
+        Some("never") => ColorSupport::None,
+        Some("auto") | None => {
+            let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+            if no_color || !std::io::stdout().is_terminal() {
+                ColorSupport::None
+            } else {
+                terminal_color_support()
+            }
+        }
+        Some(s) => {
+            return Err(BadArgumentError(format!(
+                "`--color` must be one of `always`, `never` or `auto`, not `{}`",
+                s
+            )))
+        }
+    };
+    COLOR_SUPPORT.store(color_support as u8, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Guesses how many colours the terminal supports from `COLORTERM` and `TERM`
+fn terminal_color_support() -> ColorSupport {
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+    let term = std::env::var("TERM").unwrap_or_default();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        ColorSupport::TrueColor
+    } else if term == "dumb" {
+        ColorSupport::None
+    } else if term.contains("256") {
+        ColorSupport::Ansi256
+    } else {
+        ColorSupport::Ansi16
+    }
+}
+
+pub fn color_support() -> ColorSupport {
+    match COLOR_SUPPORT.load(Ordering::Relaxed) {
+        0 => ColorSupport::None,
+        1 => ColorSupport::Ansi16,
+        2 => ColorSupport::Ansi256,
+        _ => ColorSupport::TrueColor,
+    }
+}
+
+/// The theme from the config, or the default theme if `init_color` hasn't been called
+pub fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+/// The escape codes for a foreground colour, using the closest colour the terminal supports
+pub(crate) fn foreground_color_codes(
+    (r, g, b): (u8, u8, u8),
+    color_support: ColorSupport,
+) -> Vec<String> {
+    match color_support {
+        ColorSupport::TrueColor => vec![
+            "38".to_string(),
+            "2".to_string(),
+            r.to_string(),
+            g.to_string(),
+            b.to_string(),
+        ],
+        ColorSupport::Ansi256 => {
+            let index = if r == g && g == b {
+                // use the greyscale ramp
+                match r {
+                    0..=7 => 16,
+                    249..=255 => 231,
+                    _ => 232 + (r as u16 - 8) * 24 / 241,
+                }
+            } else {
+                let level = |c: u8| -> u16 {
+                    match c {
+                        0..=47 => 0,
+                        48..=114 => 1,
+                        _ => (c as u16 - 35) / 40,
+                    }
+                };
+                16 + 36 * level(r) + 6 * level(g) + level(b)
+            };
+            vec!["38".to_string(), "5".to_string(), index.to_string()]
+        }
+        ColorSupport::Ansi16 => {
+            /// The usual colours of the 16 ansi colours, and their codes
+            const PALETTE: [((i32, i32, i32), u8); 16] = [
+                ((0, 0, 0), 30),
+                ((205, 0, 0), 31),
+                ((0, 205, 0), 32),
+                ((205, 205, 0), 33),
+                ((0, 0, 238), 34),
+                ((205, 0, 205), 35),
+                ((0, 205, 205), 36),
+                ((229, 229, 229), 37),
+                ((127, 127, 127), 90),
+                ((255, 0, 0), 91),
+                ((0, 255, 0), 92),
+                ((255, 255, 0), 93),
+                ((92, 92, 255), 94),
+                ((255, 0, 255), 95),
+                ((0, 255, 255), 96),
+                ((255, 255, 255), 97),
+            ];
+            let (r, g, b) = (r as i32, g as i32, b as i32);
+            let (_, code) = PALETTE
+                .iter()
+                .min_by_key(|((pr, pg, pb), _)| (pr - r).pow(2) + (pg - g).pow(2) + (pb - b).pow(2))
+                .unwrap();
+            vec![code.to_string()]
+        }
+        ColorSupport::None => vec![],
+    }
+}
+
 /// Represents a builder for creating ansi strings
 pub struct AnsiStringBuilder {
     bold: bool,
@@ -98,19 +255,30 @@ impl AnsiStringBuilder {
         self
     }
 
+    /// Sets the colour, boldness and strikethrough from a theme style
+    pub fn set_style(mut self, style: &Style) -> Self {
+        if let Some(colour) = style.colour {
+            self.foreground_color = Some((colour.0, colour.1, colour.2));
+        }
+        self.bold |= style.bold;
+        self.strikethrough |= style.strikethrough;
+        self
+    }
+
+    /// Pushes `string` with the current styles, if colour is turned off then `string` is
+    /// pushed as is
     pub fn push_str(mut self, string: &str) -> Self {
+        let color_support = color_support();
+        if color_support == ColorSupport::None {
+            self.s.push_str(string);
+            return self;
+        }
         let mut escape_codes = Vec::<String>::new();
         if self.bold {
             escape_codes.push("1".to_string());
         }
         if let Some(color) = self.foreground_color {
-            escape_codes.extend([
-                "38".to_string(),
-                "2".to_string(),
-                color.0.to_string(),
-                color.1.to_string(),
-                color.2.to_string(),
-            ]);
+            escape_codes.extend(foreground_color_codes(color, color_support));
         }
         if self.strikethrough {
             escape_codes.push("9".to_string());
@@ -124,3 +292,58 @@ impl AnsiStringBuilder {
         self.s
     }
 }
+
+/// Splits a string into ansi escape codes (`Err`) and visible characters (`Ok`)
+fn ansi_chars(s: &str) -> Vec<Result<char, String>> {
+    let mut chars = s.chars();
+    let mut parts = vec![];
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            let mut escape = String::from(c);
+            for c in chars.by_ref() {
+                escape.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+            parts.push(Err(escape));
+        } else {
+            parts.push(Ok(c));
+        }
+    }
+    parts
+}
+
+/// Removes any ansi escape codes from `s`
+pub fn strip_ansi(s: &str) -> String {
+    ansi_chars(s).into_iter().filter_map(Result::ok).collect()
+}
+
+/// The number of characters in `s` that will be displayed
+pub fn visible_width(s: &str) -> usize {
+    ansi_chars(s).iter().filter(|c| c.is_ok()).count()
+}
+
+/// Cuts `s` down to `width` visible characters, keeping any ansi escape codes
+pub fn truncate_ansi(s: &str, width: usize) -> String {
+    let mut truncated = String::new();
+    let mut visible = 0;
+    let mut escaped = false;
+    for c in ansi_chars(s) {
+        match c {
+            Ok(c) if visible < width => {
+                truncated.push(c);
+                visible += 1;
+            }
+            Ok(_) => {}
+            Err(escape) => {
+                truncated.push_str(&escape);
+                escaped = true;
+            }
+        }
+    }
+    if escaped {
+        truncated.push_str("\x1b[0m");
+    }
+    truncated
+}
@@ -0,0 +1,169 @@
+use std::io::IsTerminal;
+
+use crate::cli;
+
+/// The narrowest a column will be shrunk to when fitting a table to the terminal
+const MIN_COLUMN_WIDTH: usize = 6;
+
+/// The space between columns
+const COLUMN_SEPARATOR: &str = "  ";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Align {
+    Left,
+    /// Right aligned columns are for numbers and money, and are never truncated
+    Right,
+}
+
+struct Column {
+    header: String,
+    align: Align,
+}
+
+/// Renders rows of text as a table that fits the terminal. When stdout isn't a terminal, the
+/// table isn't truncated
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Default for Table {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Table {
+            columns: vec![],
+            rows: vec![],
+        }
+    }
+
+    /// Adds a column, if no column has a header then the header row isn't shown
+    pub fn column(mut self, header: &str, align: Align) -> Self {
+        self.columns.push(Column {
+            header: header.to_owned(),
+            align,
+        });
+        self
+    }
+
+    /// Adds a row, cells may contain ansi escape codes (e.g. from `AnsiStringBuilder`)
+    pub fn push_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Renders the table, with no trailing newline
+    pub fn render(&self) -> String {
+        let ansi = cli::color_support() != cli::ColorSupport::None;
+        if std::io::stdout().is_terminal() {
+            let terminal_width = crossterm::terminal::size()
+                .map(|(width, _)| width as usize)
+                .unwrap_or(80);
+            self.render_with(Some(terminal_width), ansi)
+        } else {
+            self.render_with(None, ansi)
+        }
+    }
+
+    /// Renders the table, truncating columns to fit within `max_width`
+    pub fn render_with(&self, max_width: Option<usize>, ansi: bool) -> String {
+        let show_headers = self.columns.iter().any(|column| !column.header.is_empty());
+        let cell = |row: &Vec<String>, i: usize| -> String {
+            let cell = row.get(i).map_or("", String::as_str);
+            if ansi {
+                cell.to_owned()
+            } else {
+                cli::strip_ansi(cell)
+            }
+        };
+
+        let mut widths: Vec<usize> = self
+            .columns
+            .iter()
+            .map(|column| {
+                if show_headers {
+                    column.header.chars().count()
+                } else {
+                    0
+                }
+            })
+            .collect();
+        for row in &self.rows {
+            for (i, width) in widths.iter_mut().enumerate() {
+                *width = std::cmp::max(*width, cli::visible_width(&cell(row, i)));
+            }
+        }
+
+        // shrink the widest left aligned column until the table fits
+        if let Some(max_width) = max_width {
+            let separators = COLUMN_SEPARATOR.len() * widths.len().saturating_sub(1);
+            while widths.iter().sum::<usize>() + separators > max_width {
+                let widest = widths
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, width)| {
+                        self.columns[*i].align == Align::Left && **width > MIN_COLUMN_WIDTH
+                    })
+                    .max_by_key(|(_, width)| **width)
+                    .map(|(i, _)| i);
+                match widest {
+                    Some(i) => widths[i] -= 1,
+                    None => break,
+                }
+            }
+        }
+
+        let render_row = |cells: Vec<String>| -> String {
+            let mut line = vec![];
+            for (i, cell) in cells.iter().enumerate() {
+                let width = widths[i];
+                let cell = if cli::visible_width(cell) > width {
+                    cli::truncate_ansi(cell, width.saturating_sub(1)) + "…"
+                } else {
+                    cell.clone()
+                };
+                let padding = " ".repeat(width - cli::visible_width(&cell));
+                // don't pad the end of the line
+                line.push(match self.columns[i].align {
+                    Align::Left if i + 1 == cells.len() => cell,
+                    Align::Left => cell + &padding,
+                    Align::Right => padding + &cell,
+                });
+            }
+            // empty cells at the end of a row would leave trailing spaces
+            line.join(COLUMN_SEPARATOR).trim_end().to_owned()
+        };
+
+        let mut lines = vec![];
+        if show_headers {
+            lines.push(render_row(
+                self.columns
+                    .iter()
+                    .map(|column| {
+                        if ansi {
+                            cli::AnsiStringBuilder::new()
+                                .set_bold(true)
+                                .push_str(&column.header)
+                                .build()
+                        } else {
+                            column.header.clone()
+                        }
+                    })
+                    .collect(),
+            ));
+        }
+        for row in &self.rows {
+            lines.push(render_row(
+                (0..self.columns.len()).map(|i| cell(row, i)).collect(),
+            ));
+        }
+        lines.join("\n")
+    }
+}
@@ -0,0 +1,256 @@
+use crate::types::error::BadArgumentError;
+use crate::types::*;
+use crate::*;
+
+/// Formats minor currency units with a sign, e.g. `+£12.05` or `-12.34 EUR`
+fn fmt_money(amount: i32, currency: &str) -> String {
+    let sign = match amount.signum() {
+        -1 => "-",
+        1 => "+",
+        _ => "",
+    };
+    let decimal = util::fmt_decimal(amount, currency);
+    let decimal = decimal.trim_start_matches('-');
+    match currency {
+        "GBP" => format!("{}£{}", sign, decimal),
+        _ => format!("{}{} {}", sign, decimal, currency),
+    }
+}
+
+/// Something that changes how a field is displayed
+#[derive(Debug, Clone, PartialEq)]
+enum Formatter {
+    /// Formats minor currency units in the transaction's currency
+    Money,
+    /// Formats a time with a `chrono` format string
+    Time(String),
+    /// Green for money in, red for money out
+    Colour,
+    Bold,
+    /// Pads or truncates to a width, aligning to the left or the right
+    Width(usize, bool),
+}
+
+impl Formatter {
+    fn from_str(s: &str) -> Result<Self, BadArgumentError> {
+        Ok(match s {
+            "money" => Self::Money,
+            "time" => Self::Time("%Y-%m-%d %H:%M".to_owned()),
+            "colour" | "color" => Self::Colour,
+            "bold" => Self::Bold,
+            s => {
+                let (width, right_align) = match s.strip_prefix('>') {
+                    Some(width) => (width, true),
+                    None => (s, false),
+                };
+                match width.parse::<usize>() {
+                    Ok(width) => Self::Width(width, right_align),
+                    Err(_) => {
+                        return Err(BadArgumentError(format!(
+                            "`{}` is not a valid template formatter",
+                            s
+                        )))
+                    }
+                }
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    /// The first of `paths` that has a value is displayed
+    Field {
+        paths: Vec<Vec<String>>,
+        formatters: Vec<Formatter>,
+    },
+}
+
+/// A template for displaying a transaction on one line, e.g.
+/// `{created:%d %b} {amount:money:colour} {merchant.name|counterparty.name|description}`
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parses a template, `{` and `}` can be escaped as `{{` and `}}`
+    pub fn parse(s: &str) -> Result<Self, BadArgumentError> {
+        let err = |reason: &str| {
+            BadArgumentError(format!("`{}` is not a valid template -- {}", s, reason))
+        };
+
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(err("unmatched `}`")),
+                '{' => {
+                    let mut field = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => field.push(c),
+                            None => return Err(err("unclosed `{`")),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Self::parse_field(&field).map_err(|e| err(&e.0))?);
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Self { parts })
+    }
+
+    /// Parses the inside of `{...}`, a time format takes up the rest of the field since it
+    /// may contain `:`
+    fn parse_field(field: &str) -> Result<Part, BadArgumentError> {
+        let mut sections = field.splitn(2, ':');
+        let paths: Vec<Vec<String>> = sections
+            .next()
+            .unwrap_or("")
+            .split('|')
+            .map(|path| path.trim().split('.').map(str::to_owned).collect())
+            .collect();
+        if paths.iter().any(|path| path.iter().any(String::is_empty)) {
+            return Err(BadArgumentError(format!("`{}` has an empty field", field)));
+        }
+
+        let mut formatters = vec![];
+        let mut rest = sections.next();
+        while let Some(s) = rest {
+            if s.starts_with('%') {
+                // chrono panics when displaying a bad format, so check it here
+                if chrono::format::StrftimeItems::new(s)
+                    .any(|item| item == chrono::format::Item::Error)
+                {
+                    return Err(BadArgumentError(format!(
+                        "`{}` is not a valid time format",
+                        s
+                    )));
+                }
+                formatters.push(Formatter::Time(s.to_owned()));
+                break;
+            }
+            let (formatter, next) = match s.split_once(':') {
+                Some((formatter, next)) => (formatter, Some(next)),
+                None => (s, None),
+            };
+            formatters.push(Formatter::from_str(formatter)?);
+            rest = next;
+        }
+        Ok(Part::Field { paths, formatters })
+    }
+
+    /// Displays `transaction` with this template
+    pub fn render(&self, transaction: &Transaction) -> String {
+        let value = serde_json::to_value(transaction).unwrap_or(serde_json::Value::Null);
+        let mut s = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => s.push_str(literal),
+                Part::Field { paths, formatters } => {
+                    let field = paths
+                        .iter()
+                        .filter_map(|path| {
+                            path.iter()
+                                .try_fold(&value, |value, key| value.get(key))
+                                .filter(|value| match value {
+                                    serde_json::Value::Null => false,
+                                    serde_json::Value::String(s) => !s.is_empty(),
+                                    _ => true,
+                                })
+                                .map(|value| (path, value))
+                        })
+                        .next();
+                    if let Some((path, field)) = field {
+                        // the local amount is in the currency it was spent in. Transactions
+                        // cached before it was kept have none, so it's the same as `amount`
+                        let amount = serde_json::Value::from(transaction.amount);
+                        let (field, currency) = match path.last().map(String::as_str) {
+                            Some("local_amount") if transaction.local_currency.is_empty() => {
+                                (&amount, &transaction.currency)
+                            }
+                            Some("local_amount") => (field, &transaction.local_currency),
+                            _ => (field, &transaction.currency),
+                        };
+                        s.push_str(&Self::render_field(
+                            field,
+                            formatters,
+                            transaction,
+                            currency,
+                        ));
+                    }
+                }
+            }
+        }
+        s
+    }
+
+    fn render_field(
+        field: &serde_json::Value,
+        formatters: &[Formatter],
+        transaction: &Transaction,
+        currency: &str,
+    ) -> String {
+        let mut s = match field {
+            serde_json::Value::String(s) => s.clone(),
+            value => value.to_string(),
+        };
+        for formatter in formatters {
+            s = match formatter {
+                Formatter::Money => match field.as_i64() {
+                    Some(amount) => fmt_money(amount as i32, currency),
+                    None => s,
+                },
+                Formatter::Time(fmt) => match time::Time::try_parse_str(&s) {
+                    Ok(time) => time.date_time().format(fmt).to_string(),
+                    Err(_) => s,
+                },
+                Formatter::Colour => {
+                    let amount = field.as_i64().unwrap_or(transaction.amount as i64);
+                    let style = if amount.is_negative() {
+                        &cli::theme().debit
+                    } else {
+                        &cli::theme().credit
+                    };
+                    cli::AnsiStringBuilder::new()
+                        .set_style(style)
+                        .push_str(&s)
+                        .build()
+                }
+                Formatter::Bold => cli::AnsiStringBuilder::new()
+                    .set_bold(true)
+                    .push_str(&s)
+                    .build(),
+                Formatter::Width(width, right_align) => {
+                    let mut s: String = s.chars().take(*width).collect();
+                    let padding = " ".repeat(width - s.chars().count());
+                    if *right_align {
+                        s.insert_str(0, &padding);
+                    } else {
+                        s.push_str(&padding);
+                    }
+                    s
+                }
+            }
+        }
+        s
+    }
+}
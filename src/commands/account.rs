@@ -1,49 +1,101 @@
+use crate::cli::table::{Align, Table};
 use crate::*;
 
+/// The accounts that a command should show with their indices, closed accounts are only
+/// included with `--closed`, and `account_type=<type>` only includes accounts of that type, see
+/// `util::account_type_matches`
+pub(crate) fn shown_accounts<'a>(
+    user: &'a types::user::User,
+    command: &cli::Command,
+) -> Vec<(usize, &'a types::Account)> {
+    let show_closed = command.args_set.contains("--closed");
+    let account_type = command.kwargs.get("account_type");
+    user.accounts
+        .iter()
+        .enumerate()
+        .filter(|(_, account)| show_closed || !account.closed)
+        .filter(|(_, account)| {
+            account_type.is_none_or(|t| util::account_type_matches(&account.account_type, t))
+        })
+        .collect()
+}
+
 /// The `account` command
-pub fn account(user: &types::user::User, command: &cli::Command) -> Result<(), Box<dyn std::error::Error>> {
+pub fn account(
+    user: &types::user::User,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
     let detailed = command.args_set.contains("--detailed") || command.args_set.contains("-d");
-    for (account_index, account) in user.accounts.iter().enumerate() {
-        let details = if detailed {
-            let mut details = format!(
-                "\n\
-                ACCOUNT INDEX: [{}]\n\
-                CREATED: {}\n\
-                CURRENCY: {}\n\
-                ID: {}\n\n\
-                ACCOUNT NUMBER: {}\n\
-                SORT CODE: {}\n\n\
-                OWNERS: ",
-                account_index,
-                account.created,
-                account.currency,
-                account.id,
-                account.account_number,
-                account.sort_code,
-            );
-            for owner in &account.owners {
-                details.push_str(&format!(
-                    "{} [{}]\n        ",
-                    owner.preferred_name, owner.user_id
-                ))
-            }
-            details
+    let mut table = if detailed {
+        Table::new()
+            .column("INDEX", Align::Right)
+            .column("ID", Align::Left)
+            .column("CREATED", Align::Left)
+            .column("TYPE", Align::Left)
+            .column("CURRENCY", Align::Left)
+            .column("ACCOUNT NUMBER", Align::Left)
+            .column("SORT CODE", Align::Left)
+            .column("OWNERS", Align::Left)
+    } else {
+        Table::new()
+            .column("INDEX", Align::Right)
+            .column("ACCOUNT NUMBER", Align::Left)
+            .column("SORT CODE", Align::Left)
+            .column("OWNERS", Align::Left)
+    };
+    for (account_index, account) in shown_accounts(user, command) {
+        let owners = account
+            .owners
+            .iter()
+            .map(|owner| {
+                if detailed {
+                    format!("{} [{}]", owner.preferred_name, owner.user_id)
+                } else {
+                    owner.preferred_name.clone()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        // accounts at other banks have no owners, say where they came from instead
+        let owners = match account.import_source() {
+            Some(name) => format!("imported as `{}`", name),
+            None => owners,
+        };
+        let row = if detailed {
+            vec![
+                account_index.to_string(),
+                account.id.clone(),
+                account.created.to_string(),
+                account.account_type.clone(),
+                account.currency.clone(),
+                account.account_number.clone(),
+                account.sort_code.clone(),
+                owners,
+            ]
         } else {
-            let mut details = format!(
-                "\n\
-                ACCOUNT INDEX: [{}]\n\
-                ACCOUNT NUMBER: {}\n\
-                SORT CODE: {}\n\
-                OWNERS: ",
-                account_index, account.account_number, account.sort_code,
-            );
-            for owner in &account.owners {
-                details.push_str(&format!("{}\n        ", owner.preferred_name))
-            }
-            details
+            vec![
+                account_index.to_string(),
+                account.account_number.clone(),
+                account.sort_code.clone(),
+                owners,
+            ]
         };
-        println!("{}", details);
+        // closed accounts are only shown with `--closed`, and are styled like declined
+        // transactions
+        table.push_row(if account.closed {
+            row.iter()
+                .map(|cell| {
+                    cli::AnsiStringBuilder::new()
+                        .set_style(&cli::theme().declined)
+                        .push_str(cell)
+                        .build()
+                })
+                .collect()
+        } else {
+            row
+        });
     }
+    println!("{}", table.render());
 
     Ok(())
 }
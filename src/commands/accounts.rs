@@ -0,0 +1,132 @@
+use crate::types::*;
+use crate::*;
+
+/// A difference between the stored accounts and the accounts that Monzo has
+#[derive(Debug, Clone)]
+pub enum AccountChange {
+    Opened(Account),
+    Closed(Account),
+    OwnersChanged {
+        account: Account,
+        before: Vec<String>,
+        after: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for AccountChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let describe = |account: &Account| {
+            format!(
+                "{} {} {} [{}]",
+                account.account_type, account.account_number, account.sort_code, account.id
+            )
+        };
+        match self {
+            Self::Opened(account) => write!(f, "opened  {}", describe(account)),
+            Self::Closed(account) => write!(f, "closed  {}", describe(account)),
+            Self::OwnersChanged {
+                account,
+                before,
+                after,
+            } => write!(
+                f,
+                "owners  {} changed from {} to {}",
+                describe(account),
+                before.join(", "),
+                after.join(", ")
+            ),
+        }
+    }
+}
+
+fn owner_names(account: &Account) -> Vec<String> {
+    let mut owners: Vec<String> = account
+        .owners
+        .iter()
+        .map(|owner| owner.preferred_name.clone())
+        .collect();
+    owners.sort();
+    owners
+}
+
+/// Updates `accounts` with the accounts fetched from Monzo and returns what changed. Stored
+/// accounts keep their position so that `account=<index>` still refers to the same account,
+/// new accounts are added to the end and accounts that Monzo no longer lists are marked as
+/// closed
+pub fn merge_accounts(accounts: &mut Vec<Account>, fetched: Vec<Account>) -> Vec<AccountChange> {
+    let mut changes = vec![];
+    for account in accounts.iter_mut() {
+        // accounts at other banks are never fetched from Monzo
+        if account.import_source().is_some() {
+            continue;
+        }
+        if !account.closed && !fetched.iter().any(|fetched| fetched.id == account.id) {
+            account.closed = true;
+            changes.push(AccountChange::Closed(account.clone()));
+        }
+    }
+    for fetched in fetched {
+        match accounts.iter_mut().find(|account| account.id == fetched.id) {
+            Some(account) => {
+                if fetched.closed && !account.closed {
+                    changes.push(AccountChange::Closed(fetched.clone()));
+                } else if !fetched.closed && account.closed {
+                    changes.push(AccountChange::Opened(fetched.clone()));
+                }
+                let (before, after) = (owner_names(account), owner_names(&fetched));
+                if before != after {
+                    changes.push(AccountChange::OwnersChanged {
+                        account: fetched.clone(),
+                        before,
+                        after,
+                    });
+                }
+                *account = fetched;
+            }
+            None => {
+                if !fetched.closed {
+                    changes.push(AccountChange::Opened(fetched.clone()));
+                }
+                accounts.push(fetched);
+            }
+        }
+    }
+    changes
+}
+
+/// The `accounts` command, `accounts refresh` fetches the accounts again
+pub fn accounts(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command.positional_args().first() {
+        Some(&"refresh") if commands::is_offline(command) => {
+            return Err(
+                error::BadArgumentError("accounts can't be refreshed offline".to_owned()).into(),
+            )
+        }
+        Some(&"refresh") => {}
+        None => return commands::account(user, command),
+        Some(subcommand) => {
+            return Err(error::BadArgumentError(format!(
+                "invalid subcommand `{}`, the only subcommand of `accounts` is `refresh`",
+                subcommand
+            ))
+            .into())
+        }
+    }
+
+    let fetched = pollster::block_on(commands::get_accounts(client))?;
+    let changes = merge_accounts(&mut user.accounts, fetched);
+    user_file::update_user_file(user)?;
+
+    if changes.is_empty() {
+        println!("no accounts have changed");
+    }
+    for change in &changes {
+        println!("{}", change);
+    }
+    println!();
+    commands::account(user, command)
+}
@@ -0,0 +1,291 @@
+use std::path::{Path, PathBuf};
+
+use crate::cli::table::{Align, Table};
+use crate::types::*;
+use crate::*;
+
+/// The MIME type of a file, from its extension
+fn file_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "heic" => "image/heic",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// The extension for a MIME type, the reverse of `file_type`
+fn file_extension(file_type: &str) -> &'static str {
+    match file_type {
+        "image/jpeg" => "jpg",
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/heic" => "heic",
+        "image/webp" => "webp",
+        "application/pdf" => "pdf",
+        _ => "bin",
+    }
+}
+
+/// Makes `s` safe to use as a directory name
+fn sanitize_file_name(s: &str) -> String {
+    s.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == ' ' || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+        .trim_matches(|c| c == ' ' || c == '.')
+        .to_owned()
+}
+
+/// The directory that the attachments of a transaction are downloaded into, e.g.
+/// `receipts/2022-01-01 Pret A Manger`
+pub fn attachment_dir(dir: &Path, transaction: &Transaction) -> PathBuf {
+    dir.join(format!(
+        "{} {}",
+        transaction.created.date_time().format("%Y-%m-%d"),
+        sanitize_file_name(&transaction.payee_name())
+    ))
+}
+
+/// Gets a single transaction
+pub async fn get_transaction(
+    client: &reqwest::Client,
+    transaction_id: &str,
+) -> Result<Transaction, Box<dyn std::error::Error>> {
+    #[derive(Deserialize)]
+    struct TransactionResponse {
+        transaction: Transaction,
+    }
+
+    let response = client
+        .get(format!(
+            "{}/transactions/{}?expand[]=merchant",
+            consts::MONZO_API,
+            transaction_id
+        ))
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    Ok(serde_json::from_slice::<TransactionResponse>(&response)?.transaction)
+}
+
+/// Uploads a file and attaches it to a transaction
+pub async fn upload_attachment(
+    client: &reqwest::Client,
+    transaction_id: &str,
+    path: &Path,
+) -> Result<Attachment, Box<dyn std::error::Error>> {
+    #[derive(Serialize)]
+    struct UploadRequest<'a> {
+        file_name: &'a str,
+        file_type: &'a str,
+        content_length: usize,
+    }
+    #[derive(Deserialize)]
+    struct UploadResponse {
+        file_url: String,
+        upload_url: String,
+    }
+    #[derive(Serialize)]
+    struct RegisterRequest<'a> {
+        external_id: &'a str,
+        file_url: &'a str,
+        file_type: &'a str,
+    }
+    #[derive(Deserialize)]
+    struct RegisterResponse {
+        attachment: Attachment,
+    }
+
+    let contents = std::fs::read(path)?;
+    let file_type = file_type(path);
+    let file_name = path
+        .file_name()
+        .and_then(|file_name| file_name.to_str())
+        .unwrap_or("attachment");
+
+    // ask for somewhere to upload the file to, upload it, then attach it to the transaction
+    let upload = client
+        .post(concatcp!(consts::MONZO_API, "/attachment/upload"))
+        .form(&UploadRequest {
+            file_name,
+            file_type,
+            content_length: contents.len(),
+        })
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let upload: UploadResponse = serde_json::from_slice(&upload)?;
+
+    // the upload url is pre-signed, so it mustn't have our authorization header
+    reqwest::Client::new()
+        .put(&upload.upload_url)
+        .header(reqwest::header::CONTENT_TYPE, file_type)
+        .body(contents)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let register = client
+        .post(concatcp!(consts::MONZO_API, "/attachment/register"))
+        .form(&RegisterRequest {
+            external_id: transaction_id,
+            file_url: &upload.file_url,
+            file_type,
+        })
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    Ok(serde_json::from_slice::<RegisterResponse>(&register)?.attachment)
+}
+
+/// Downloads an attachment into `dir`, returning the path of the file
+pub async fn download_attachment(
+    attachment: &Attachment,
+    dir: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = dir.join(format!(
+        "{}.{}",
+        attachment.id,
+        file_extension(&attachment.file_type)
+    ));
+    let contents = reqwest::Client::new()
+        .get(&attachment.file_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(&path, &contents)?;
+    Ok(path)
+}
+
+/// The transactions that `attachments list` and `attachments fetch` look at, either the
+/// transaction given by id or every transaction in the time range that has attachments
+fn transactions_with_attachments(
+    user: &user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+    transaction_id: Option<&str>,
+) -> Result<Vec<Transaction>, Box<dyn std::error::Error>> {
+    if let Some(transaction_id) = transaction_id {
+        return Ok(vec![pollster::block_on(get_transaction(
+            client,
+            transaction_id,
+        ))?]);
+    }
+    let (since, before) = util::get_time_range(user, command)?;
+    let account_index = util::get_account_index(user, command)?;
+    let mut transactions = pollster::block_on(commands::get_transactions(
+        user,
+        client,
+        since,
+        before,
+        account_index,
+    ))?;
+    transactions.retain(|transaction| !transaction.attachments.is_empty());
+    Ok(transactions)
+}
+
+/// The `attachments` command
+pub fn attachments(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let args = command.positional_args();
+    match args.as_slice() {
+        ["add", transaction_id, path] => {
+            let attachment =
+                pollster::block_on(upload_attachment(client, transaction_id, Path::new(path)))?;
+            println!("attached {} to {}", attachment.id, transaction_id);
+            if let Some(transaction) = user
+                .transactions
+                .iter_mut()
+                .find(|transaction| &transaction.id == transaction_id)
+            {
+                transaction.attachments.push(attachment);
+                user_file::update_user_file(user)?;
+            }
+        }
+        ["list"] | ["list", _] => {
+            let transactions =
+                transactions_with_attachments(user, client, command, args.get(1).copied())?;
+            let mut table = Table::new()
+                .column("TIME", Align::Left)
+                .column("PAYEE", Align::Left)
+                .column("TRANSACTION", Align::Left)
+                .column("ATTACHMENT", Align::Left)
+                .column("TYPE", Align::Left);
+            for transaction in &transactions {
+                for attachment in &transaction.attachments {
+                    table.push_row(vec![
+                        transaction.created.to_string(),
+                        transaction.payee_name(),
+                        transaction.id.clone(),
+                        attachment.id.clone(),
+                        attachment.file_type.clone(),
+                    ]);
+                }
+            }
+            if table.is_empty() {
+                println!("no attachments found");
+            } else {
+                println!("{}", table.render());
+            }
+        }
+        ["fetch"] | ["fetch", _] => {
+            let dir = PathBuf::from(
+                command
+                    .kwargs
+                    .get("dir")
+                    .map_or("attachments", String::as_str),
+            );
+            let transactions =
+                transactions_with_attachments(user, client, command, args.get(1).copied())?;
+            let mut downloaded = 0;
+            for transaction in &transactions {
+                let transaction_dir = attachment_dir(&dir, transaction);
+                for attachment in &transaction.attachments {
+                    match pollster::block_on(download_attachment(attachment, &transaction_dir)) {
+                        Ok(path) => {
+                            println!("{}", path.display());
+                            downloaded += 1;
+                        }
+                        Err(e) => println!("could not download {} -- {}", attachment.id, e),
+                    }
+                }
+            }
+            println!("downloaded {} attachments", downloaded);
+        }
+        _ => {
+            return Err(error::BadArgumentError(
+                "use `attachments add <transaction_id> <file>`, `attachments list \
+                [transaction_id]` or `attachments fetch [transaction_id]`"
+                    .to_owned(),
+            )
+            .into())
+        }
+    }
+    Ok(())
+}
@@ -15,7 +15,16 @@ pub async fn auth() -> Result<(types::user::User, reqwest::Client), types::error
     let _ = std::io::stdin().read_line(&mut String::new());
     println!();
 
-    let user = types::user::User::new_from_access_token(&client, access_token_response).await?;
+    // the user file also holds the cache, splits and imported accounts, which authorizing
+    // again mustn't lose
+    let user = match user_file::load_user_file() {
+        Ok(mut user) => {
+            let accounts = commands::get_accounts(&client).await?;
+            user.reauthorize(access_token_response, accounts);
+            user
+        }
+        Err(_) => types::user::User::new_from_access_token(&client, access_token_response).await?,
+    };
     let _ = user_file::update_user_file(&user);
 
     Ok((user, client))
@@ -105,3 +114,23 @@ async fn acquire_access_token(
 
     Ok(access_token_response)
 }
+
+/// Gets a new access token with a refresh token, so that the user doesn't need to authorize
+/// again
+pub async fn refresh_access_token(
+    client: &reqwest::Client,
+    refresh_token: String,
+) -> Result<types::AccessTokenResponse, types::error::AuthorizationError> {
+    let refresh_token_request = types::RefreshTokenRequest::new(refresh_token);
+    let response = client
+        .post(concatcp!(consts::MONZO_API, "/oauth2/token"))
+        .form(&refresh_token_request)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let access_token_response =
+        serde_json::from_slice::<types::AccessTokenResponse>(&response.bytes().await?)?;
+
+    Ok(access_token_response)
+}
@@ -1,4 +1,8 @@
+use std::collections::BTreeMap;
+
+use crate::cli::table::{Align, Table};
 use crate::types::*;
+use crate::util::FmtCurrencyOptions;
 use crate::*;
 
 /// Returns the balance of the account
@@ -7,11 +11,18 @@ pub async fn get_balance(
     client: &reqwest::Client,
     account_index: usize,
 ) -> Result<types::Balance, Box<dyn std::error::Error>> {
+    let account = &user.accounts[account_index];
+    if account.import_source().is_some() {
+        return Ok(import::statement_balance(
+            &user.cached_transactions(&account.id),
+            &account.currency,
+        ));
+    }
     let balance = client
         .get(&format!(
             "{}/balance?account_id={}",
             consts::MONZO_API,
-            user.accounts[account_index].id
+            account.id
         ))
         .send()
         .await?
@@ -21,23 +32,170 @@ pub async fn get_balance(
     Ok(serde_json::from_slice(&balance)?)
 }
 
+/// Returns the pots of the account, including deleted pots
+pub async fn get_pots(
+    client: &reqwest::Client,
+    account_id: &str,
+) -> Result<Vec<Pot>, Box<dyn std::error::Error>> {
+    #[derive(Deserialize)]
+    struct Pots {
+        pots: Vec<Pot>,
+    }
+
+    // other banks don't have pots
+    if account_id.starts_with(IMPORT_ACCOUNT_PREFIX) {
+        return Ok(vec![]);
+    }
+    let pots = client
+        .get(&format!(
+            "{}/pots?current_account_id={}",
+            consts::MONZO_API,
+            account_id
+        ))
+        .send()
+        .await?
+        .bytes()
+        .await?;
+
+    Ok(serde_json::from_slice::<Pots>(&pots)?.pots)
+}
+
+/// Returns the balance and pots of each of the accounts at `account_indices`, the accounts
+/// are fetched concurrently
+pub async fn get_all_balances(
+    user: &types::user::User,
+    client: &reqwest::Client,
+    account_indices: &[usize],
+) -> Result<Vec<(Balance, Vec<Pot>)>, Box<dyn std::error::Error>> {
+    let mut handles = vec![];
+    for &account_index in account_indices {
+        let user = user.clone();
+        let client = client.clone();
+        handles.push(tokio::spawn(async move {
+            let account_id = &user.accounts[account_index].id;
+            // errors aren't `Send`, so they can't be kept across an await or leave the task
+            let (balance, pots) = tokio::join!(
+                async {
+                    get_balance(&user, &client, account_index)
+                        .await
+                        .map_err(|e| e.to_string())
+                },
+                async {
+                    get_pots(&client, account_id)
+                        .await
+                        .map_err(|e| e.to_string())
+                }
+            );
+            Ok::<_, String>((balance?, pots?))
+        }));
+    }
+
+    let mut balances = vec![];
+    for handle in handles {
+        balances.push(handle.await??);
+    }
+    Ok(balances)
+}
+
+/// Prints the balance of every open account and its pots, followed by the total for each
+/// currency
+fn balance_all(
+    user: &mut types::user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let accounts: Vec<(usize, Account)> = commands::shown_accounts(user, command)
+        .into_iter()
+        .map(|(account_index, account)| (account_index, account.clone()))
+        .collect();
+    let account_indices: Vec<usize> = accounts.iter().map(|(i, _)| *i).collect();
+    let balances = commands::load_all_balances(user, client, command, &account_indices)?;
+    println!("{}", balance_all_table(&accounts, &balances).render());
+    Ok(())
+}
+
+/// The table for `balance --all`, a row for every account in `accounts` and each of its pots
+/// that isn't deleted, then the total for each currency. `balances` has the balance and pots of
+/// each account
+pub(crate) fn balance_all_table(
+    accounts: &[(usize, Account)],
+    balances: &[(Balance, Vec<Pot>)],
+) -> Table {
+    let fmt = |amount: i32, currency: &str| {
+        util::fmt_currency(amount, currency, &FmtCurrencyOptions::default())
+    };
+    let mut table = Table::new()
+        .column("ACCOUNT", Align::Left)
+        .column("BALANCE", Align::Right)
+        .column("WITH SAVINGS", Align::Right)
+        .column("SPENT TODAY", Align::Right);
+    let mut totals = BTreeMap::<String, i32>::new();
+    for ((account_index, account), (balance, pots)) in accounts.iter().zip(balances) {
+        table.push_row(vec![
+            format!(
+                "[{}] {} {}",
+                account_index, account.account_number, account.sort_code
+            ),
+            balance.balance_string(),
+            balance.balance_including_flexible_savings_string(),
+            balance.spend_today_string(),
+        ]);
+        *totals.entry(balance.currency.clone()).or_default() += balance.balance;
+        for pot in pots.iter().filter(|pot| !pot.deleted) {
+            table.push_row(vec![
+                format!("  {}", pot.name),
+                fmt(pot.balance, &pot.currency),
+                String::new(),
+                String::new(),
+            ]);
+            *totals.entry(pot.currency.clone()).or_default() += pot.balance;
+        }
+    }
+    for (currency, total) in &totals {
+        table.push_row(vec![
+            cli::AnsiStringBuilder::new()
+                .set_bold(true)
+                .push_str(&format!("TOTAL {}", currency))
+                .build(),
+            cli::AnsiStringBuilder::new()
+                .set_bold(true)
+                .push_str(&fmt(*total, currency))
+                .build(),
+            String::new(),
+            String::new(),
+        ]);
+    }
+    table
+}
+
 /// The `balance` command
 pub fn balance(
-    user: &types::user::User,
+    user: &mut types::user::User,
     client: &reqwest::Client,
     command: &cli::Command,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    if command.args_set.contains("--all") || command.args_set.contains("-a") {
+        return balance_all(user, client, command);
+    }
     let account_index = util::get_account_index(user, command)?;
-    
-    let balance = pollster::block_on(get_balance(user, client, account_index))?;
+
+    let balance = commands::load_balance(user, client, command, account_index)?;
 
     if command.args_set.contains("--detailed") || command.args_set.contains("-d") {
-        println!(
-            "BALANCE: {}\n\
-            TOTAL BALANCE: {}",
-            balance.balance_string(),
-            balance.total_balance_string()
-        );
+        let mut table = Table::new()
+            .column("", Align::Left)
+            .column("", Align::Right);
+        table.push_row(vec!["BALANCE".to_owned(), balance.balance_string()]);
+        table.push_row(vec![
+            "TOTAL BALANCE".to_owned(),
+            balance.total_balance_string(),
+        ]);
+        table.push_row(vec![
+            "WITH FLEXIBLE SAVINGS".to_owned(),
+            balance.balance_including_flexible_savings_string(),
+        ]);
+        table.push_row(vec!["SPENT TODAY".to_owned(), balance.spend_today_string()]);
+        println!("{}", table.render());
     } else {
         println!("{}", balance.balance_string());
     };
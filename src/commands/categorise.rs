@@ -0,0 +1,90 @@
+use crate::cli::table::{Align, Table};
+use crate::types::*;
+use crate::*;
+
+/// The `categorise` command, shows the categories and tags that the rules in the config give
+/// transactions and applies them to the cached transactions. `--dry-run` only shows them
+pub fn categorise(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (since, before) = util::get_time_range(user, command)?;
+    let account_index = util::get_account_index(user, command)?;
+    let filter = util::get_filter(command)?;
+    let dry_run = command.args_set.contains("--dry-run");
+    let config = config_file::load_config_file()?;
+    let rules = rules::Rules::new(&config.rules)?;
+    if config.rules.is_empty() {
+        println!("there are no rules in the config, see `rules` in the README");
+        return Ok(());
+    }
+
+    let mut transactions = pollster::block_on(commands::get_transactions(
+        user,
+        client,
+        since,
+        before,
+        account_index,
+    ))?;
+    // the filter can match the categories and tags that the rules give
+    for transaction in &mut transactions {
+        rules.apply(transaction);
+    }
+    transactions.retain(|transaction| filter.matches(transaction));
+
+    let mut table = Table::new()
+        .column("TIME", Align::Left)
+        .column("PAYEE", Align::Left)
+        .column("AMOUNT", Align::Right)
+        .column("MONZO CATEGORY", Align::Left)
+        .column("CATEGORY", Align::Left)
+        .column("TAGS", Align::Left);
+    let mut matched = 0;
+    for transaction in &mut transactions {
+        if !rules.apply(transaction) {
+            continue;
+        }
+        matched += 1;
+        table.push_row(vec![
+            transaction.created.to_string(),
+            transaction.payee_name(),
+            util::fmt_decimal(transaction.amount, &transaction.currency),
+            transaction
+                .merchant
+                .as_ref()
+                .map_or("", |merchant| merchant.category.as_str())
+                .to_owned(),
+            util::unwrap_to_string(&transaction.custom_category, ""),
+            transaction.tags.join(", "),
+        ]);
+    }
+    if !table.is_empty() {
+        println!("{}", table.render());
+    }
+    println!(
+        "the rules match {} of {} transactions",
+        matched,
+        transactions.len()
+    );
+
+    if dry_run {
+        println!("dry run, the cached transactions haven't been changed");
+        return Ok(());
+    }
+    // recategorise every cached transaction, not just the ones fetched now, so that cached
+    // transactions from before the rules changed are brought up to date
+    let mut changed = 0;
+    for transaction in &mut user.transactions {
+        let before = transaction.clone();
+        rules.apply(transaction);
+        if before.custom_category != transaction.custom_category || before.tags != transaction.tags
+        {
+            changed += 1;
+        }
+    }
+    user.cache_transactions(&transactions);
+    user_file::update_user_file(user)?;
+    println!("updated {} cached transactions", changed);
+    Ok(())
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+
+use crate::types::config::{DaemonConfig, Hook};
+use crate::types::*;
+use crate::util::FmtCurrencyOptions;
+use crate::*;
+
+/// Access tokens are refreshed when they have less than this many seconds left
+const REFRESH_BEFORE_EXPIRY_SECONDS: i64 = 15 * 60;
+
+/// Something that the daemon noticed
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum DaemonEvent {
+    NewTransaction {
+        transaction: Transaction,
+    },
+    LargeDebit {
+        transaction: Transaction,
+    },
+    Declined {
+        transaction: Transaction,
+    },
+    /// A pending transaction has settled or its amount has changed, `pending_amount` is what
+    /// it was while pending
+    Settled {
+        transaction: Transaction,
+        pending_amount: i32,
+    },
+    LowBalance {
+        account_id: String,
+        balance: i32,
+        currency: String,
+    },
+}
+
+impl DaemonEvent {
+    /// The name of this event, as used in `Hook::events`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::NewTransaction { .. } => "new_transaction",
+            Self::LargeDebit { .. } => "large_debit",
+            Self::Declined { .. } => "declined",
+            Self::Settled { .. } => "settled",
+            Self::LowBalance { .. } => "low_balance",
+        }
+    }
+
+    /// A short description of this event for notifications
+    pub fn message(&self) -> String {
+        let fmt = |amount: i32, currency: &str| {
+            util::fmt_currency(
+                amount,
+                currency,
+                &FmtCurrencyOptions {
+                    include_positive_sign: true,
+                    colored: false,
+                },
+            )
+        };
+        match self {
+            Self::NewTransaction { transaction } => format!(
+                "{} {}",
+                fmt(transaction.amount, &transaction.currency),
+                transaction.payee_name()
+            ),
+            Self::LargeDebit { transaction } => format!(
+                "large payment of {} to {}",
+                fmt(transaction.amount, &transaction.currency),
+                transaction.payee_name()
+            ),
+            Self::Declined { transaction } => format!(
+                "{} at {} was declined ({})",
+                fmt(transaction.amount, &transaction.currency),
+                transaction.payee_name(),
+                util::unwrap_to_string(&transaction.decline_reason, "")
+            ),
+            Self::Settled {
+                transaction,
+                pending_amount,
+            } if *pending_amount != transaction.amount => format!(
+                "{} at {} settled as {}",
+                fmt(*pending_amount, &transaction.currency),
+                transaction.payee_name(),
+                fmt(transaction.amount, &transaction.currency)
+            ),
+            Self::Settled { transaction, .. } => format!(
+                "{} at {} settled",
+                fmt(transaction.amount, &transaction.currency),
+                transaction.payee_name()
+            ),
+            Self::LowBalance {
+                balance, currency, ..
+            } => format!("your balance is low, {}", fmt(*balance, currency)),
+        }
+    }
+}
+
+/// The events for `fetched` transactions that aren't in `cached`, and for those that were
+/// pending in `cached` and have since settled or changed amount
+pub fn transaction_events(
+    cached: &[&Transaction],
+    fetched: &[Transaction],
+    config: &DaemonConfig,
+) -> Vec<DaemonEvent> {
+    let cached: HashMap<&str, &Transaction> = cached
+        .iter()
+        .map(|transaction| (transaction.id.as_str(), *transaction))
+        .collect();
+    let mut events = vec![];
+    for transaction in fetched {
+        if let Some(previous) = cached.get(transaction.id.as_str()) {
+            if previous.is_pending()
+                && (!transaction.is_pending() || transaction.amount != previous.amount)
+            {
+                events.push(DaemonEvent::Settled {
+                    transaction: transaction.clone(),
+                    pending_amount: previous.amount,
+                });
+            }
+            continue;
+        }
+        let transaction = transaction.clone();
+        if transaction.decline_reason.is_some() {
+            events.push(DaemonEvent::Declined { transaction });
+        } else if config
+            .large_debit
+            .is_some_and(|large_debit| -transaction.amount >= large_debit)
+        {
+            events.push(DaemonEvent::LargeDebit { transaction });
+        } else {
+            events.push(DaemonEvent::NewTransaction { transaction });
+        }
+    }
+    events
+}
+
+/// The event for a balance that has become low, `previous` is the balance at the last sync
+pub fn low_balance_event(
+    account_id: &str,
+    previous: Option<i32>,
+    balance: &Balance,
+    config: &DaemonConfig,
+) -> Option<DaemonEvent> {
+    let low_balance = config.low_balance?;
+    let was_low = previous.is_some_and(|previous| previous < low_balance);
+    if balance.balance < low_balance && !was_low {
+        Some(DaemonEvent::LowBalance {
+            account_id: account_id.to_owned(),
+            balance: balance.balance,
+            currency: balance.currency.clone(),
+        })
+    } else {
+        None
+    }
+}
+
+fn log(message: &str) {
+    println!("[{}] {}", time::Time::now(), message);
+}
+
+/// Does everything that `hook` asks for, failures are logged rather than returned so that one
+/// hook can't stop the others
+async fn run_hook(hook: &Hook, event: &DaemonEvent) {
+    if !hook.events.is_empty() && !hook.events.iter().any(|kind| kind == event.kind()) {
+        return;
+    }
+    let json = serde_json::to_string(event).unwrap_or_default();
+    if let Some(command) = &hook.command {
+        let status = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("MONZO_EVENT", event.kind())
+            .env("MONZO_MESSAGE", event.message())
+            .env("MONZO_JSON", &json)
+            .status()
+            .await;
+        match status {
+            Ok(status) if !status.success() => {
+                log(&format!("hook `{}` failed with {}", command, status))
+            }
+            Err(e) => log(&format!("could not run hook `{}` -- {}", command, e)),
+            Ok(_) => {}
+        }
+    }
+    if hook.notify {
+        let status = tokio::process::Command::new("notify-send")
+            .arg("Monzo")
+            .arg(event.message())
+            .status()
+            .await;
+        if let Err(e) = status {
+            log(&format!("could not run notify-send -- {}", e));
+        }
+    }
+    if let Some(url) = &hook.url {
+        let response = reqwest::Client::new()
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(json)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status);
+        if let Err(e) = response {
+            log(&format!("could not post to `{}` -- {}", url, e));
+        }
+    }
+}
+
+/// Refreshes the access token if it is about to expire
+async fn ensure_fresh_token(
+    user: &mut user::User,
+    client: &mut reqwest::Client,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let refresh_at = user
+        .access_token
+        .expires
+        .add(&chrono::Duration::seconds(-REFRESH_BEFORE_EXPIRY_SECONDS));
+    if time::Time::now() < refresh_at {
+        return Ok(());
+    }
+    let refresh_token =
+        match &user.access_token.refresh_token {
+            Some(refresh_token) => refresh_token.clone(),
+            None if time::Time::now() < user.access_token.expires => return Ok(()),
+            None => return Err(error::AuthorizationError::Custom(
+                "the access token has expired and can't be refreshed, run `auth` and start the \
+                daemon again"
+                    .to_owned(),
+            )
+            .into()),
+        };
+    let access_token_response =
+        commands::refresh_access_token(&reqwest::Client::new(), refresh_token).await?;
+    user.access_token = user::AccessToken::new(access_token_response);
+    *client = user.create_authorized_client();
+    user_file::merge_into_user_file(user, &[])?;
+    log("refreshed the access token");
+    Ok(())
+}
+
+/// Fetches new transactions and balances for every open account, returning the events. An
+/// account that can't be fetched is logged and skipped
+async fn sync(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    config: &DaemonConfig,
+    rules: &rules::Rules<'_>,
+    balances: &mut HashMap<String, i32>,
+) -> Result<Vec<DaemonEvent>, Box<dyn std::error::Error>> {
+    let mut events = vec![];
+    let mut synced = vec![];
+    for account_index in 0..user.accounts.len() {
+        let account = user.accounts[account_index].clone();
+        if account.closed {
+            continue;
+        }
+
+        let cached = user.cached_transactions(&account.id);
+        let since = user.resync_since(&account.id);
+        // one account failing shouldn't lose what was fetched for the others
+        let mut fetched = match commands::get_transactions(
+            user,
+            client,
+            Some(since),
+            None,
+            account_index,
+        )
+        .await
+        {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                log(&format!(
+                    "could not fetch the transactions of {} -- {}",
+                    account.id, e
+                ));
+                continue;
+            }
+        };
+        for transaction in &mut fetched {
+            rules.apply(transaction);
+        }
+        // the first sync of an account fills the cache, rather than reporting every
+        // transaction as new
+        if cached.is_empty() {
+            log(&format!(
+                "cached {} transactions for {}",
+                fetched.len(),
+                account.id
+            ));
+        } else {
+            events.extend(transaction_events(&cached, &fetched, config));
+        }
+        user.cache_transactions(&fetched);
+        synced.extend(fetched);
+
+        let balance = match commands::get_balance(user, client, account_index).await {
+            Ok(balance) => balance,
+            Err(e) => {
+                log(&format!(
+                    "could not fetch the balance of {} -- {}",
+                    account.id, e
+                ));
+                continue;
+            }
+        };
+        events.extend(low_balance_event(
+            &account.id,
+            balances.get(&account.id).copied(),
+            &balance,
+            config,
+        ));
+        balances.insert(account.id.clone(), balance.balance);
+    }
+    user_file::merge_into_user_file(user, &synced)?;
+    Ok(events)
+}
+
+/// The `daemon` command, syncs transactions and balances on a schedule and runs the hooks in
+/// the config for anything new
+pub async fn daemon(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let full_config = config_file::load_config_file()?;
+    let rules = rules::Rules::new(&full_config.rules)?;
+    let config = &full_config.daemon;
+    let interval = match command.uint_kwarg::<u64>("interval") {
+        Some(interval) => interval?,
+        None => config.interval,
+    };
+    let once = command.args_set.contains("--once");
+
+    let mut client = client.clone();
+    let mut balances = HashMap::new();
+    log(&format!("syncing every {}s", interval));
+    loop {
+        match ensure_fresh_token(user, &mut client).await {
+            Ok(()) => match sync(user, &client, config, &rules, &mut balances).await {
+                Ok(events) => {
+                    for event in &events {
+                        log(&format!("{}: {}", event.kind(), event.message()));
+                        for hook in &config.hooks {
+                            run_hook(hook, event).await;
+                        }
+                    }
+                }
+                Err(e) => log(&format!("sync failed -- {}", e)),
+            },
+            // without a refresh token, trying again won't help
+            Err(e) if user.access_token.refresh_token.is_none() => return Err(e),
+            // the token is refreshed before it expires, so there is time to try again
+            Err(e) => log(&format!("could not refresh the access token -- {}", e)),
+        }
+        if once {
+            return Ok(());
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(std::time::Duration::from_secs(interval)) => {}
+            _ = tokio::signal::ctrl_c() => {
+                log("stopping");
+                return Ok(());
+            }
+        }
+    }
+}
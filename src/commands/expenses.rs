@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use crate::cli::table::{Align, Table};
+use crate::types::*;
+use crate::*;
+
+/// Whether a transaction has `tag` from the rules, or any of its metadata, which includes its
+/// notes, contains `#tag`. Tags are matched case insensitively and `#work` doesn't match
+/// `#workshop`
+pub fn is_tagged(transaction: &Transaction, tag: &str) -> bool {
+    let tag = tag.trim_start_matches('#').to_lowercase();
+    if transaction
+        .tags
+        .iter()
+        .any(|rule_tag| rule_tag.trim_start_matches('#').to_lowercase() == tag)
+    {
+        return true;
+    }
+    let tag = format!("#{}", tag);
+    transaction.metadata.values().any(|value| {
+        let value = value.to_lowercase();
+        value.match_indices(&tag).any(|(i, _)| {
+            !value[i + tag.len()..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-')
+        })
+    })
+}
+
+/// Escapes text for HTML
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escapes text for a Markdown table cell
+fn escape_markdown(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+/// The address of the merchant of a transaction on one line
+fn merchant_address(transaction: &Transaction) -> String {
+    match &transaction.merchant {
+        Some(merchant) => merchant.address.one_line(),
+        None => String::new(),
+    }
+}
+
+/// The transactions tagged for reimbursement in a period, with what is owed in each currency
+pub struct ExpenseReport<'a> {
+    pub tag: String,
+    pub since: Option<time::Time>,
+    pub before: Option<time::Time>,
+    pub expenses: Vec<&'a Transaction>,
+    /// currency -> amount claimed, in minor units. Refunds reduce the amount
+    pub totals: BTreeMap<String, i32>,
+    /// attachment id -> where the receipt can be found, a path relative to the report or a url
+    pub receipts: HashMap<String, String>,
+}
+
+impl<'a> ExpenseReport<'a> {
+    /// Collects the transactions tagged with `tag`, oldest first. Declined transactions are
+    /// left out
+    pub fn new(
+        transactions: &'a [Transaction],
+        tag: &str,
+        since: Option<time::Time>,
+        before: Option<time::Time>,
+    ) -> Self {
+        let mut expenses: Vec<&Transaction> = transactions
+            .iter()
+            .filter(|transaction| {
+                transaction.decline_reason.is_none() && is_tagged(transaction, tag)
+            })
+            .collect();
+        expenses.sort_by(|a, b| a.created.partial_cmp(&b.created).unwrap());
+        let mut totals = BTreeMap::new();
+        for transaction in &expenses {
+            *totals.entry(transaction.currency.clone()).or_default() -= transaction.amount;
+        }
+        Self {
+            tag: tag.trim_start_matches('#').to_owned(),
+            since,
+            before,
+            expenses,
+            totals,
+            receipts: HashMap::new(),
+        }
+    }
+
+    fn title(&self) -> String {
+        let date = |time: &Option<time::Time>| match time {
+            Some(time) => time.date_time().format("%Y-%m-%d").to_string(),
+            None => "now".to_owned(),
+        };
+        format!(
+            "Expenses #{}, {} to {}",
+            self.tag,
+            date(&self.since),
+            date(&self.before)
+        )
+    }
+
+    /// The links to the receipts of a transaction
+    fn receipts_of<'b>(&'b self, transaction: &'b Transaction) -> Vec<&'b str> {
+        transaction
+            .attachments
+            .iter()
+            .map(|attachment| {
+                self.receipts
+                    .get(&attachment.id)
+                    .map_or(attachment.file_url.as_str(), String::as_str)
+            })
+            .collect()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut s = format!("# {}\n\n", self.title());
+        s.push_str("| Date | Merchant | Category | Address | Notes | Amount | Receipts |\n");
+        s.push_str("|------|----------|----------|---------|-------|-------:|----------|\n");
+        for transaction in &self.expenses {
+            let receipts: Vec<String> = self
+                .receipts_of(transaction)
+                .iter()
+                .enumerate()
+                .map(|(i, link)| format!("[{}](<{}>)", i + 1, link))
+                .collect();
+            s.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} {} | {} |\n",
+                transaction.created,
+                escape_markdown(&transaction.payee_name()),
+                escape_markdown(transaction.category().unwrap_or("")),
+                escape_markdown(&merchant_address(transaction)),
+                escape_markdown(transaction.metadata.get("notes").map_or("", String::as_str)),
+                util::fmt_decimal(-transaction.amount, &transaction.currency),
+                transaction.currency,
+                receipts.join(" "),
+            ));
+        }
+        s.push_str("\n## Totals\n\n| Currency | Amount |\n|----------|-------:|\n");
+        for (currency, total) in &self.totals {
+            s.push_str(&format!(
+                "| {} | {} |\n",
+                currency,
+                util::fmt_decimal(*total, currency)
+            ));
+        }
+        s
+    }
+
+    pub fn to_html(&self) -> String {
+        let title = escape_html(&self.title());
+        let mut s = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+            <style>\nbody {{ font-family: sans-serif; }}\n\
+            table {{ border-collapse: collapse; margin-bottom: 2em; }}\n\
+            th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }}\n\
+            .amount {{ text-align: right; }}\n</style>\n</head>\n<body>\n<h1>{0}</h1>\n\
+            <table>\n<tr><th>Date</th><th>Merchant</th><th>Category</th><th>Address</th>\
+            <th>Notes</th><th class=\"amount\">Amount</th><th>Receipts</th></tr>\n",
+            title
+        );
+        for transaction in &self.expenses {
+            let receipts: Vec<String> = self
+                .receipts_of(transaction)
+                .iter()
+                .enumerate()
+                .map(|(i, link)| format!("<a href=\"{}\">{}</a>", escape_html(link), i + 1))
+                .collect();
+            s.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+                <td class=\"amount\">{} {}</td><td>{}</td></tr>\n",
+                transaction.created,
+                escape_html(&transaction.payee_name()),
+                escape_html(transaction.category().unwrap_or("")),
+                escape_html(&merchant_address(transaction)),
+                escape_html(transaction.metadata.get("notes").map_or("", String::as_str)),
+                util::fmt_decimal(-transaction.amount, &transaction.currency),
+                escape_html(&transaction.currency),
+                receipts.join(" "),
+            ));
+        }
+        s.push_str(
+            "</table>\n<h2>Totals</h2>\n<table>\n\
+            <tr><th>Currency</th><th class=\"amount\">Amount</th></tr>\n",
+        );
+        for (currency, total) in &self.totals {
+            s.push_str(&format!(
+                "<tr><td>{}</td><td class=\"amount\">{}</td></tr>\n",
+                escape_html(currency),
+                util::fmt_decimal(*total, currency)
+            ));
+        }
+        s.push_str("</table>\n</body>\n</html>\n");
+        s
+    }
+
+    pub fn to_csv(&self) -> Result<String, std::io::Error> {
+        #[derive(Serialize)]
+        struct ExpenseRecord<'a> {
+            id: &'a str,
+            time: &'a time::Time,
+            merchant: String,
+            category: &'a str,
+            address: String,
+            notes: &'a str,
+            amount: String,
+            currency: &'a str,
+            receipts: String,
+        }
+
+        util::serde_csv::records_to_string(self.expenses.iter().map(|transaction| ExpenseRecord {
+            id: &transaction.id,
+            time: &transaction.created,
+            merchant: transaction.payee_name(),
+            category: transaction.category().unwrap_or(""),
+            address: merchant_address(transaction),
+            notes: transaction.metadata.get("notes").map_or("", String::as_str),
+            amount: util::fmt_decimal(-transaction.amount, &transaction.currency),
+            currency: &transaction.currency,
+            receipts: self.receipts_of(transaction).join(" "),
+        }))
+    }
+}
+
+/// Downloads the receipts of every expense into `dir/receipts`, recording where each one was
+/// saved so that the report can link to it
+fn download_receipts(report: &mut ExpenseReport, dir: &Path) {
+    let receipts_dir = dir.join("receipts");
+    for transaction in &report.expenses {
+        let transaction_dir = commands::attachment_dir(&receipts_dir, transaction);
+        for attachment in &transaction.attachments {
+            match pollster::block_on(commands::download_attachment(attachment, &transaction_dir)) {
+                Ok(path) => {
+                    // links are relative to the report, which is in `dir`
+                    let link = path.strip_prefix(dir).unwrap_or(&path);
+                    report.receipts.insert(
+                        attachment.id.clone(),
+                        link.to_string_lossy().replace('\\', "/"),
+                    );
+                }
+                Err(e) => println!(
+                    "could not download receipt {}, linking to it instead -- {}",
+                    attachment.id, e
+                ),
+            }
+        }
+    }
+}
+
+/// The `expenses` command, writes a reimbursement report of the transactions tagged with
+/// `#<tag>` along with a csv and their receipts
+pub fn expenses(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (since, before) = util::get_time_range(user, command)?;
+    let account_index = util::get_account_index(user, command)?;
+    let filter = util::get_filter(command)?;
+    let tag = command.kwargs.get("tag").map_or("work", String::as_str);
+    let dir = PathBuf::from(command.kwargs.get("dir").map_or("expenses", String::as_str));
+    let html = match command.kwargs.get("report").map(String::as_str) {
+        Some("html") => true,
+        Some("markdown") | Some("md") | None => false,
+        Some(report) => {
+            return Err(error::InvalidArgumentError(format!(
+                "`{}` is not a valid report format, use `html` or `markdown`",
+                report
+            ))
+            .into())
+        }
+    };
+
+    let mut transactions = commands::load_transactions(
+        user,
+        client,
+        command,
+        since.clone(),
+        before.clone(),
+        account_index,
+    )?;
+    transactions.retain(|transaction| filter.matches(transaction));
+    let mut report = ExpenseReport::new(&transactions, tag, since, before);
+    if report.expenses.is_empty() {
+        println!("no transactions are tagged #{}", report.tag);
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(&dir)?;
+    // receipts can't be downloaded offline, so the report links to them instead
+    if !command.args_set.contains("--no-receipts") && !commands::is_offline(command) {
+        download_receipts(&mut report, &dir);
+    }
+    let report_path = dir.join(if html { "expenses.html" } else { "expenses.md" });
+    std::fs::write(
+        &report_path,
+        if html {
+            report.to_html()
+        } else {
+            report.to_markdown()
+        },
+    )?;
+    let csv_path = dir.join("expenses.csv");
+    std::fs::write(&csv_path, report.to_csv()?)?;
+
+    let mut table = Table::new()
+        .column("CURRENCY", Align::Left)
+        .column("EXPENSES", Align::Right)
+        .column("TOTAL", Align::Right);
+    for (currency, total) in &report.totals {
+        let count = report
+            .expenses
+            .iter()
+            .filter(|transaction| &transaction.currency == currency)
+            .count();
+        table.push_row(vec![
+            currency.clone(),
+            count.to_string(),
+            util::fmt_decimal(*total, currency),
+        ]);
+    }
+    println!("{}", table.render());
+    println!("{}", report_path.display());
+    println!("{}", csv_path.display());
+    Ok(())
+}
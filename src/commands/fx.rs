@@ -0,0 +1,528 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use crate::cli::table::{Align, Table};
+use crate::types::error::BadArgumentError;
+use crate::types::*;
+use crate::*;
+
+/// A transaction spent in another currency, with the rate it was exchanged at
+#[derive(Debug, Clone, Serialize)]
+pub struct FxTransaction<'a> {
+    pub id: &'a str,
+    pub created: &'a time::Time,
+    pub payee: String,
+    /// The amount taken from the account, in minor units of `currency`
+    pub amount: i32,
+    pub currency: &'a str,
+    /// The amount in minor units of `local_currency`
+    pub local_amount: i32,
+    pub local_currency: &'a str,
+    /// How much `local_currency` one unit of `currency` bought
+    pub rate: f64,
+    /// The reference rate on the day, if one was given
+    pub reference_rate: Option<f64>,
+    /// What `local_amount` would have cost at the reference rate, in minor units of `currency`
+    pub reference_cost: Option<i32>,
+}
+
+impl<'a> FxTransaction<'a> {
+    /// The transaction if it was spent in another currency, declined transactions and refunds
+    /// aren't spending
+    pub fn new(transaction: &'a Transaction) -> Option<Self> {
+        let (local_amount, local_currency) = transaction.foreign_amount()?;
+        if transaction.decline_reason.is_some() || transaction.amount >= 0 || local_amount >= 0 {
+            return None;
+        }
+        Some(Self {
+            id: &transaction.id,
+            created: &transaction.created,
+            payee: transaction.payee_name(),
+            amount: transaction.amount,
+            currency: &transaction.currency,
+            local_amount,
+            local_currency,
+            rate: exchange_rate(
+                local_amount,
+                local_currency,
+                transaction.amount,
+                &transaction.currency,
+            ),
+            reference_rate: None,
+            reference_cost: None,
+        })
+    }
+
+    /// How much more (or less, if negative) this cost than at the reference rate, in minor units
+    /// of `currency`
+    pub fn extra_cost(&self) -> Option<i32> {
+        self.reference_cost
+            .map(|reference_cost| -self.amount - reference_cost)
+    }
+}
+
+/// Minor units as an amount in major units, e.g. `1250` GBP is `12.5`
+fn major_units(amount: i32, currency: &str) -> f64 {
+    amount as f64 / 10f64.powi(util::currency_exponent(currency) as i32)
+}
+
+/// How much `local_currency` one unit of `currency` bought
+fn exchange_rate(local_amount: i32, local_currency: &str, amount: i32, currency: &str) -> f64 {
+    major_units(local_amount, local_currency) / major_units(amount, currency)
+}
+
+/// A run of spending in one currency, with no more than the trip gap between transactions
+#[derive(Debug, Clone, Serialize)]
+pub struct Trip<'a> {
+    pub local_currency: &'a str,
+    pub currency: &'a str,
+    pub start: &'a time::Time,
+    pub end: &'a time::Time,
+    /// The total spent, in minor units of `local_currency`
+    pub local_spent: i32,
+    /// The total taken from the account, in minor units of `currency`
+    pub spent: i32,
+    /// The rate over the whole trip, weighted by how much was spent
+    pub average_rate: f64,
+    /// What the trip would have cost at the reference rates, if every transaction has one
+    pub reference_cost: Option<i32>,
+    pub transactions: Vec<FxTransaction<'a>>,
+}
+
+impl<'a> Trip<'a> {
+    fn new(transactions: Vec<FxTransaction<'a>>) -> Self {
+        let first = &transactions[0];
+        let local_spent = -transactions.iter().map(|fx| fx.local_amount).sum::<i32>();
+        let spent = -transactions.iter().map(|fx| fx.amount).sum::<i32>();
+        Trip {
+            local_currency: first.local_currency,
+            currency: first.currency,
+            start: first.created,
+            end: transactions[transactions.len() - 1].created,
+            local_spent,
+            spent,
+            average_rate: exchange_rate(local_spent, first.local_currency, spent, first.currency),
+            reference_cost: transactions.iter().map(|fx| fx.reference_cost).sum(),
+            transactions,
+        }
+    }
+
+    /// How much more (or less, if negative) the trip cost than at the reference rates
+    pub fn extra_cost(&self) -> Option<i32> {
+        self.reference_cost
+            .map(|reference_cost| self.spent - reference_cost)
+    }
+}
+
+/// Reference exchange rates, currency -> (date, how much of the currency one unit of the
+/// account's currency buys), oldest first
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceRates(BTreeMap<String, Vec<(chrono::NaiveDate, f64)>>);
+
+impl ReferenceRates {
+    /// Reads a csv file with `date`, `currency` and `rate` columns, e.g. `2022-06-01,EUR,1.17`
+    /// for 1.17 EUR to the pound
+    pub fn parse(contents: &str) -> Result<Self, BadArgumentError> {
+        let mut reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(contents.trim_start_matches('\u{feff}').as_bytes());
+        let headers = reader
+            .headers()
+            .map_err(|e| BadArgumentError(format!("could not read the header row -- {}", e)))?
+            .clone();
+        let column = |column: &str| {
+            headers
+                .iter()
+                .position(|header| header.eq_ignore_ascii_case(column))
+                .ok_or_else(|| {
+                    BadArgumentError(format!(
+                        "the rates have no `{}` column, the columns are {}",
+                        column,
+                        headers.iter().collect::<Vec<_>>().join(", ")
+                    ))
+                })
+        };
+        let (date, currency, rate) = (column("date")?, column("currency")?, column("rate")?);
+
+        let mut rates = Self::default();
+        for (i, record) in reader.records().enumerate() {
+            // the header is line 1
+            let line = i + 2;
+            let record = record
+                .map_err(|e| BadArgumentError(format!("could not read line {} -- {}", line, e)))?;
+            let field = |column: usize| record.get(column).unwrap_or("");
+            if field(date).is_empty() {
+                continue;
+            }
+            let day = chrono::NaiveDate::parse_from_str(field(date), "%Y-%m-%d")
+                .or_else(|_| chrono::NaiveDate::parse_from_str(field(date), "%d/%m/%Y"))
+                .map_err(|_| {
+                    BadArgumentError(format!(
+                        "`{}` on line {} is not a date like `2022-06-01`",
+                        field(date),
+                        line
+                    ))
+                })?;
+            let value = field(rate)
+                .parse::<f64>()
+                .ok()
+                .filter(|rate| *rate > 0.0)
+                .ok_or_else(|| {
+                    BadArgumentError(format!(
+                        "`{}` on line {} is not an exchange rate",
+                        field(rate),
+                        line
+                    ))
+                })?;
+            rates
+                .0
+                .entry(field(currency).to_uppercase())
+                .or_default()
+                .push((day, value));
+        }
+        for currency_rates in rates.0.values_mut() {
+            currency_rates.sort_by_key(|(day, _)| *day);
+        }
+        Ok(rates)
+    }
+
+    /// The latest rate for `currency` on or before `day`, or the earliest rate if every rate is
+    /// after it
+    pub fn rate(&self, currency: &str, day: chrono::NaiveDate) -> Option<f64> {
+        let rates = self.0.get(currency)?;
+        rates
+            .iter()
+            .rev()
+            .find(|(date, _)| *date <= day)
+            .or_else(|| rates.first())
+            .map(|(_, rate)| *rate)
+    }
+
+    /// Sets the reference rate and cost of `fx`
+    fn apply(&self, fx: &mut FxTransaction) {
+        fx.reference_rate = self.rate(fx.local_currency, fx.created.date_time().date());
+        fx.reference_cost = fx.reference_rate.map(|rate| {
+            let cost = major_units(-fx.local_amount, fx.local_currency) / rate;
+            (cost * 10f64.powi(util::currency_exponent(fx.currency) as i32)).round() as i32
+        });
+    }
+}
+
+/// Groups the spending in other currencies into trips. A trip ends when nothing is spent in its
+/// currency for more than `gap_days`. Trips are oldest first
+pub fn fx_trips<'a>(
+    transactions: &'a [Transaction],
+    rates: Option<&ReferenceRates>,
+    gap_days: i64,
+) -> Vec<Trip<'a>> {
+    let mut spending: Vec<FxTransaction> =
+        transactions.iter().filter_map(FxTransaction::new).collect();
+    spending.sort_by(|a, b| a.created.partial_cmp(b.created).unwrap());
+    if let Some(rates) = rates {
+        for fx in &mut spending {
+            rates.apply(fx);
+        }
+    }
+
+    // local currency -> the transactions of the trip in progress
+    let mut open: BTreeMap<&str, Vec<FxTransaction>> = BTreeMap::new();
+    let mut trips = vec![];
+    for fx in spending {
+        if let Some(trip) = open.get(fx.local_currency) {
+            let last = trip[trip.len() - 1].created;
+            if *fx.created.date_time() - *last.date_time() > chrono::Duration::days(gap_days) {
+                trips.push(Trip::new(open.remove(fx.local_currency).unwrap()));
+            }
+        }
+        open.entry(fx.local_currency).or_default().push(fx);
+    }
+    trips.extend(open.into_values().map(Trip::new));
+    trips.sort_by(|a, b| a.start.partial_cmp(b.start).unwrap());
+    trips
+}
+
+/// The spending in one currency over every trip
+#[derive(Debug, Clone, Serialize)]
+pub struct CurrencySpending<'a> {
+    pub local_currency: &'a str,
+    pub currency: &'a str,
+    pub trips: usize,
+    pub transactions: usize,
+    pub local_spent: i32,
+    pub spent: i32,
+    pub average_rate: f64,
+    pub reference_cost: Option<i32>,
+}
+
+/// Totals `trips` for each local currency
+pub fn fx_currencies<'a>(trips: &[Trip<'a>]) -> Vec<CurrencySpending<'a>> {
+    let mut currencies: BTreeMap<(&str, &str), CurrencySpending> = BTreeMap::new();
+    for trip in trips {
+        let spending = currencies
+            .entry((trip.local_currency, trip.currency))
+            .or_insert(CurrencySpending {
+                local_currency: trip.local_currency,
+                currency: trip.currency,
+                trips: 0,
+                transactions: 0,
+                local_spent: 0,
+                spent: 0,
+                average_rate: 0.0,
+                reference_cost: Some(0),
+            });
+        spending.trips += 1;
+        spending.transactions += trip.transactions.len();
+        spending.local_spent += trip.local_spent;
+        spending.spent += trip.spent;
+        spending.reference_cost = spending
+            .reference_cost
+            .zip(trip.reference_cost)
+            .map(|(a, b)| a + b);
+    }
+    let mut currencies: Vec<CurrencySpending> = currencies.into_values().collect();
+    for spending in &mut currencies {
+        spending.average_rate = exchange_rate(
+            spending.local_spent,
+            spending.local_currency,
+            spending.spent,
+            spending.currency,
+        );
+    }
+    currencies.sort_by_key(|spending| std::cmp::Reverse(spending.spent));
+    currencies
+}
+
+fn fmt_money(amount: i32, currency: &str) -> String {
+    format!("{} {}", util::fmt_decimal(amount, currency), currency)
+}
+
+fn fmt_optional_money(amount: Option<i32>, currency: &str) -> String {
+    amount.map_or(String::new(), |amount| fmt_money(amount, currency))
+}
+
+/// How much more than the reference rate was paid, as a percentage of the reference cost
+fn fmt_markup(extra_cost: Option<i32>, reference_cost: Option<i32>) -> String {
+    match (extra_cost, reference_cost) {
+        (Some(extra_cost), Some(reference_cost)) if reference_cost != 0 => {
+            format!("{:+.2}%", extra_cost as f64 * 100.0 / reference_cost as f64)
+        }
+        _ => String::new(),
+    }
+}
+
+/// The `fx` command, spending in other currencies grouped into trips, with the exchange rate
+/// of every transaction and the average over each trip. `rates=<file>` compares them with
+/// reference rates
+pub fn fx(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (since, before) = util::get_time_range(user, command)?;
+    let account_index = util::get_account_index(user, command)?;
+    let output_type = util::get_output_type(command)?;
+    let filter = util::get_filter(command)?;
+    let detailed = command.args_set.contains("--detailed") || command.args_set.contains("-d");
+    let gap_days = match command.kwargs.get("gap") {
+        Some(gap) => gap.parse::<i64>().map_err(|_| {
+            error::InvalidArgumentError(format!("`{}` is not a number of days", gap))
+        })?,
+        None => 3,
+    };
+    let rates = match command.kwargs.get("rates") {
+        Some(path) => {
+            let contents = std::fs::read_to_string(path).map_err(|e| {
+                error::BadArgumentError(format!("could not read {} -- {}", path, e))
+            })?;
+            Some(ReferenceRates::parse(&contents)?)
+        }
+        None => None,
+    };
+
+    let mut transactions =
+        commands::load_transactions(user, client, command, since, before, account_index)?;
+    transactions.retain(|transaction| filter.matches(transaction));
+    let mut trips = fx_trips(&transactions, rates.as_ref(), gap_days);
+    if let Some(currency) = command.positional_args().first() {
+        trips.retain(|trip| trip.local_currency.eq_ignore_ascii_case(currency));
+    }
+    if rates.is_some() {
+        let missing: Vec<&str> = trips
+            .iter()
+            .filter(|trip| trip.reference_cost.is_none())
+            .map(|trip| trip.local_currency)
+            .collect();
+        if !missing.is_empty() {
+            eprintln!("the rates have nothing for {}", missing.join(", "));
+        }
+    }
+    let currencies = fx_currencies(&trips);
+
+    let output = match output_type {
+        OutputType::Json => {
+            #[derive(Serialize)]
+            struct FxReport<'a> {
+                currencies: &'a [CurrencySpending<'a>],
+                trips: &'a [Trip<'a>],
+            }
+            serde_json::to_string_pretty(&FxReport {
+                currencies: &currencies,
+                trips: &trips,
+            })?
+        }
+        OutputType::Csv => {
+            #[derive(Serialize)]
+            struct FxRecord<'a> {
+                trip: usize,
+                id: &'a str,
+                created: &'a time::Time,
+                payee: &'a str,
+                amount: i32,
+                currency: &'a str,
+                local_amount: i32,
+                local_currency: &'a str,
+                rate: f64,
+                reference_rate: Option<f64>,
+                reference_cost: Option<i32>,
+            }
+
+            util::serde_csv::records_to_string(trips.iter().enumerate().flat_map(|(i, trip)| {
+                trip.transactions.iter().map(move |fx| FxRecord {
+                    trip: i + 1,
+                    id: fx.id,
+                    created: fx.created,
+                    payee: &fx.payee,
+                    amount: fx.amount,
+                    currency: fx.currency,
+                    local_amount: fx.local_amount,
+                    local_currency: fx.local_currency,
+                    rate: fx.rate,
+                    reference_rate: fx.reference_rate,
+                    reference_cost: fx.reference_cost,
+                })
+            }))?
+        }
+        OutputType::Display => {
+            if trips.is_empty() {
+                println!("nothing was spent in another currency");
+                return Ok(());
+            }
+            let mut output = String::new();
+            let mut table = Table::new()
+                .column("CURRENCY", Align::Left)
+                .column("TRIPS", Align::Right)
+                .column("SPENT", Align::Right)
+                .column("COST", Align::Right)
+                .column("RATE", Align::Right);
+            if rates.is_some() {
+                table = table
+                    .column("REFERENCE COST", Align::Right)
+                    .column("MARKUP", Align::Right);
+            }
+            for spending in &currencies {
+                let mut row = vec![
+                    spending.local_currency.to_owned(),
+                    spending.trips.to_string(),
+                    fmt_money(spending.local_spent, spending.local_currency),
+                    fmt_money(spending.spent, spending.currency),
+                    format!("{:.4}", spending.average_rate),
+                ];
+                if rates.is_some() {
+                    let extra_cost = spending
+                        .reference_cost
+                        .map(|reference_cost| spending.spent - reference_cost);
+                    row.push(fmt_optional_money(
+                        spending.reference_cost,
+                        spending.currency,
+                    ));
+                    row.push(fmt_markup(extra_cost, spending.reference_cost));
+                }
+                table.push_row(row);
+            }
+            output += &(table.render() + "\n\n");
+
+            let mut table = Table::new()
+                .column("FROM", Align::Left)
+                .column("TO", Align::Left)
+                .column("CURRENCY", Align::Left)
+                .column("TRANSACTIONS", Align::Right)
+                .column("SPENT", Align::Right)
+                .column("COST", Align::Right)
+                .column("AVERAGE RATE", Align::Right);
+            if rates.is_some() {
+                table = table
+                    .column("REFERENCE COST", Align::Right)
+                    .column("EXTRA", Align::Right)
+                    .column("MARKUP", Align::Right);
+            }
+            for trip in &trips {
+                let mut row = vec![
+                    trip.start.date_time().format("%Y-%m-%d").to_string(),
+                    trip.end.date_time().format("%Y-%m-%d").to_string(),
+                    trip.local_currency.to_owned(),
+                    trip.transactions.len().to_string(),
+                    fmt_money(trip.local_spent, trip.local_currency),
+                    fmt_money(trip.spent, trip.currency),
+                    format!("{:.4}", trip.average_rate),
+                ];
+                if rates.is_some() {
+                    row.push(fmt_optional_money(trip.reference_cost, trip.currency));
+                    row.push(fmt_optional_money(trip.extra_cost(), trip.currency));
+                    row.push(fmt_markup(trip.extra_cost(), trip.reference_cost));
+                }
+                table.push_row(row);
+            }
+            output += &(table.render() + "\n");
+
+            if detailed {
+                for trip in &trips {
+                    let mut table = Table::new()
+                        .column("TIME", Align::Left)
+                        .column("PAYEE", Align::Left)
+                        .column("SPENT", Align::Right)
+                        .column("COST", Align::Right)
+                        .column("RATE", Align::Right);
+                    if rates.is_some() {
+                        table = table
+                            .column("REFERENCE RATE", Align::Right)
+                            .column("EXTRA", Align::Right);
+                    }
+                    for fx in &trip.transactions {
+                        let mut row = vec![
+                            fx.created.to_string(),
+                            fx.payee.clone(),
+                            fmt_money(-fx.local_amount, fx.local_currency),
+                            fmt_money(-fx.amount, fx.currency),
+                            format!("{:.4}", fx.rate),
+                        ];
+                        if rates.is_some() {
+                            row.push(
+                                fx.reference_rate
+                                    .map_or(String::new(), |rate| format!("{:.4}", rate)),
+                            );
+                            row.push(fmt_optional_money(fx.extra_cost(), fx.currency));
+                        }
+                        table.push_row(row);
+                    }
+                    output += &format!(
+                        "\n{} {} to {}\n{}\n",
+                        trip.local_currency,
+                        trip.start.date_time().format("%Y-%m-%d"),
+                        trip.end.date_time().format("%Y-%m-%d"),
+                        table.render()
+                    );
+                }
+            }
+            output
+        }
+        OutputType::Ofx | OutputType::Qif | OutputType::Ledger | OutputType::Beancount => {
+            return Err(error::InvalidArgumentError(
+                "statement formats are only supported by `transactions`".to_owned(),
+            )
+            .into())
+        }
+    };
+    std::io::stdout().write_all(output.as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use std::io::{IsTerminal, Write};
+
+use chrono::NaiveDate;
+
+use crate::cli::chart;
+use crate::types::*;
+use crate::util::FmtCurrencyOptions;
+use crate::*;
+
+/// The height of the chart in lines
+const CHART_HEIGHT: usize = 10;
+
+/// The balance at the end of a day
+#[derive(Debug, Clone, Serialize)]
+pub struct DailyBalance {
+    pub date: String,
+    pub balance: i32,
+    pub currency: String,
+}
+
+/// The closing balance of every day in a range, along with anything that suggests that the
+/// history is wrong
+#[derive(Debug, Clone)]
+pub struct BalanceHistory {
+    pub days: Vec<DailyBalance>,
+    pub warnings: Vec<String>,
+}
+
+/// Reconstructs the closing balance of every day from `start` to `end`.
+///
+/// The balance after each transaction is worked out from the last transaction that has an
+/// `account_balance`, or from `current_balance` (the balance after the last transaction) if
+/// no transaction has one. Every other `account_balance` and `current_balance` are then used
+/// to check the history.
+pub fn balance_history(
+    transactions: &[Transaction],
+    current_balance: Option<i32>,
+    currency: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> BalanceHistory {
+    let mut settled: Vec<&Transaction> = transactions
+        .iter()
+        .filter(|transaction| transaction.decline_reason.is_none())
+        .collect();
+    settled.sort_by(|a, b| a.created.partial_cmp(&b.created).unwrap());
+
+    let anchor = settled
+        .iter()
+        .enumerate()
+        .rev()
+        .find_map(|(i, transaction)| Some((i, transaction.account_balance?)))
+        .or_else(|| Some((settled.len().checked_sub(1)?, current_balance?)));
+    let mut after = vec![0; settled.len()];
+    if let Some((anchor, balance)) = anchor {
+        after[anchor] = balance;
+        for i in anchor + 1..settled.len() {
+            after[i] = after[i - 1] + settled[i].amount;
+        }
+        for i in (0..anchor).rev() {
+            after[i] = after[i + 1] - settled[i + 1].amount;
+        }
+    }
+
+    let mut warnings = vec![];
+    let mismatches = settled
+        .iter()
+        .zip(&after)
+        .filter(|(transaction, after)| {
+            transaction
+                .account_balance
+                .is_some_and(|balance| balance != **after)
+        })
+        .count();
+    if mismatches > 0 {
+        warnings.push(format!(
+            "{} transactions have a balance that doesn't match the history, some transactions \
+            may be missing",
+            mismatches
+        ));
+    }
+    if let (Some(current_balance), Some(last)) = (current_balance, after.last()) {
+        if current_balance != *last {
+            warnings.push(format!(
+                "the history ends at {} but the balance is {}, some transactions may be missing",
+                util::fmt_currency(*last, currency, &FmtCurrencyOptions::default()),
+                util::fmt_currency(current_balance, currency, &FmtCurrencyOptions::default()),
+            ));
+        }
+    }
+
+    // days before the first transaction have the balance from before it
+    let mut balance = match settled.first() {
+        Some(first) => after[0] - first.amount,
+        None => current_balance.unwrap_or(0),
+    };
+    let mut next = 0;
+    let mut days = vec![];
+    for date in start.iter_days().take_while(|date| date <= &end) {
+        while next < settled.len() && settled[next].created.date_time().date() <= date {
+            balance = after[next];
+            next += 1;
+        }
+        days.push(DailyBalance {
+            date: date.format("%Y-%m-%d").to_string(),
+            balance,
+            currency: currency.to_owned(),
+        });
+    }
+    BalanceHistory { days, warnings }
+}
+
+/// The `history` command
+pub fn history(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (since, before) = util::get_time_range(user, command)?;
+    let account_index = util::get_account_index(user, command)?;
+    let output_type = util::get_output_type(command)?;
+    let sparkline = command.args_set.contains("--sparkline");
+    let ascii = command.args_set.contains("--ascii");
+
+    let transactions = commands::load_transactions(
+        user,
+        client,
+        command,
+        since.clone(),
+        before.clone(),
+        account_index,
+    )?;
+    // the current balance is only the end of the history if the history goes up to now
+    let balance = commands::load_balance(user, client, command, account_index)?;
+    let current_balance = if before.is_none() {
+        Some(balance.balance)
+    } else {
+        None
+    };
+
+    let today = time::Time::now().date_time().date();
+    let end = before.map_or(today, |before| before.date_time().date());
+    let start = since.map_or(end, |since| since.date_time().date());
+    let history = balance_history(
+        &transactions,
+        current_balance,
+        &balance.currency,
+        start,
+        end,
+    );
+    for warning in &history.warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    let fmt =
+        |amount: i32| util::fmt_currency(amount, &balance.currency, &FmtCurrencyOptions::default());
+    let output = match output_type {
+        OutputType::Json => serde_json::to_string_pretty(&history.days)?,
+        OutputType::Csv => util::serde_csv::records_to_string(&history.days)?,
+        OutputType::Ofx | OutputType::Qif | OutputType::Ledger | OutputType::Beancount => {
+            return Err(error::InvalidArgumentError(
+                "statement formats are only supported by `transactions`".to_owned(),
+            )
+            .into())
+        }
+        OutputType::Display => {
+            let values: Vec<i64> = history.days.iter().map(|day| day.balance as i64).collect();
+            let lowest = history.days.iter().min_by_key(|day| day.balance);
+            let highest = history.days.iter().max_by_key(|day| day.balance);
+            let (lowest, highest, first, last) =
+                match (lowest, highest, history.days.first(), history.days.last()) {
+                    (Some(lowest), Some(highest), Some(first), Some(last)) => {
+                        (lowest, highest, first, last)
+                    }
+                    _ => return Err("there are no days in this range".into()),
+                };
+
+            let mut lines = vec![];
+            if sparkline {
+                lines.push(format!(
+                    "{} {} {}",
+                    fmt(first.balance),
+                    chart::sparkline(&values, ascii),
+                    fmt(last.balance)
+                ));
+            } else {
+                let width = if std::io::stdout().is_terminal() {
+                    crossterm::terminal::size().map_or(80, |(width, _)| width as usize)
+                } else {
+                    80
+                };
+                let (low_label, high_label) = (fmt(lowest.balance), fmt(highest.balance));
+                lines.extend(chart::area_chart(
+                    &values,
+                    (&low_label, &high_label),
+                    width,
+                    CHART_HEIGHT,
+                    ascii,
+                ));
+                // label the first and last day under the chart
+                let indent =
+                    std::cmp::max(low_label.chars().count(), high_label.chars().count()) + 3;
+                let chart_width = std::cmp::min(values.len(), width.saturating_sub(indent));
+                lines.push(format!(
+                    "{}{:<w$}{}",
+                    " ".repeat(indent),
+                    first.date,
+                    if chart_width > 2 * first.date.len() {
+                        &last.date
+                    } else {
+                        ""
+                    },
+                    w = chart_width.saturating_sub(last.date.len()),
+                ));
+            }
+            lines.push(format!(
+                "opening {}, closing {}, lowest {} on {}, highest {} on {}",
+                fmt(first.balance),
+                fmt(last.balance),
+                fmt(lowest.balance),
+                lowest.date,
+                fmt(highest.balance),
+                highest.date
+            ));
+            lines.join("\n")
+        }
+    };
+
+    std::io::stdout().write_all(output.as_bytes())?;
+    println!();
+    Ok(())
+}
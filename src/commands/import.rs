@@ -0,0 +1,91 @@
+use std::collections::HashSet;
+
+use crate::types::*;
+use crate::*;
+
+/// The `import` command, reads statements from another bank into the account of an import in
+/// the config. Transactions that were already imported are replaced rather than duplicated
+pub fn import(
+    user: &mut user::User,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config_file::load_config_file()?;
+    let (name, files) = match command.positional_args().as_slice() {
+        [name, files @ ..] if !files.is_empty() => (name.to_string(), files.to_vec()),
+        _ => {
+            return Err(error::BadArgumentError(format!(
+                "use `import <import> <file>...`, the imports in the config are {}",
+                if config.imports.is_empty() {
+                    "none, see `imports` in the README".to_owned()
+                } else {
+                    config
+                        .imports
+                        .keys()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                }
+            ))
+            .into())
+        }
+    };
+    let source = config.imports.get(&name).ok_or_else(|| {
+        error::BadArgumentError(format!("there is no `{}` import in the config", name))
+    })?;
+
+    let mut transactions = vec![];
+    for file in &files {
+        let contents = std::fs::read_to_string(file)
+            .map_err(|e| error::BadArgumentError(format!("could not read {} -- {}", file, e)))?;
+        let statement = import::parse_statement(&name, source, file, &contents)
+            .map_err(|e| error::BadArgumentError(format!("could not import {} -- {}", file, e)))?;
+        transactions.extend(statement);
+    }
+    let rules = rules::Rules::new(&config.rules)?;
+    for transaction in &mut transactions {
+        rules.apply(transaction);
+    }
+
+    let account_id = import::import_account_id(&name);
+    let currency = transactions
+        .first()
+        .map_or(source.currency.as_str(), |transaction| {
+            transaction.currency.as_str()
+        });
+    let account = import::import_account(&name, source, currency);
+    match user
+        .accounts
+        .iter_mut()
+        .find(|account| account.id == account_id)
+    {
+        Some(existing) => {
+            existing.account_number = account.account_number;
+            existing.sort_code = account.sort_code;
+        }
+        None => {
+            println!(
+                "added the account `{}`, select it with `account={}`",
+                name, name
+            );
+            user.accounts.push(account);
+        }
+    }
+    let cached: HashSet<&str> = user
+        .cached_transactions(&account_id)
+        .into_iter()
+        .map(|transaction| transaction.id.as_str())
+        .collect();
+    let new = transactions
+        .iter()
+        .filter(|transaction| !cached.contains(transaction.id.as_str()))
+        .count();
+    user.cache_transactions(&transactions);
+    user_file::update_user_file(user)?;
+    println!(
+        "imported {} transactions into `{}`, {} of them new",
+        transactions.len(),
+        name,
+        new
+    );
+    Ok(())
+}
@@ -0,0 +1,367 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+
+use crate::cli::table::{Align, Table};
+use crate::types::*;
+use crate::util::FmtCurrencyOptions;
+use crate::*;
+
+/// Whether a directory entry is a merchant or someone paid by bank transfer
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayeeKind {
+    Merchant,
+    Counterparty,
+}
+
+/// Everything paid to or received from one merchant or counterparty
+#[derive(Debug, Clone, Serialize)]
+pub struct DirectoryEntry<'a> {
+    /// The merchant group id, merchant id or counterparty details that identify this entry
+    pub key: String,
+    pub name: String,
+    pub kind: PayeeKind,
+    pub category: Option<String>,
+    /// The address of the merchant, or how many branches there are for chains
+    pub address: Option<String>,
+    /// The number of transactions that weren't declined
+    pub visits: usize,
+    /// currency -> amount spent, in minor units
+    pub spent: BTreeMap<String, i32>,
+    /// currency -> amount received, in minor units
+    pub received: BTreeMap<String, i32>,
+    pub first_seen: time::Time,
+    pub last_seen: time::Time,
+    #[serde(skip)]
+    pub transactions: Vec<&'a Transaction>,
+}
+
+impl<'a> DirectoryEntry<'a> {
+    /// The amount spent in every currency, e.g. `12.50 GBP, 3.00 EUR`
+    fn spent_string(&self) -> String {
+        self.spent
+            .iter()
+            .map(|(currency, amount)| {
+                format!("{} {}", util::fmt_decimal(*amount, currency), currency)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// The key that a transaction is grouped by, and whether it is a merchant or counterparty.
+/// Transactions with neither (e.g. pot transfers) aren't in the directory
+fn directory_key(transaction: &Transaction, collapse_chains: bool) -> Option<(String, PayeeKind)> {
+    if let Some(merchant) = &transaction.merchant {
+        let id = if collapse_chains && !merchant.group_id.is_empty() {
+            &merchant.group_id
+        } else {
+            &merchant.id
+        };
+        return Some((id.clone(), PayeeKind::Merchant));
+    }
+    let counterparty = transaction.counterparty.as_ref()?;
+    let key = match (
+        &counterparty.user_id,
+        &counterparty.sort_code,
+        &counterparty.account_number,
+    ) {
+        (Some(user_id), _, _) => user_id.clone(),
+        (None, Some(sort_code), Some(account_number)) => {
+            format!("{} {}", sort_code, account_number)
+        }
+        _ => format!("name:{}", counterparty.name.as_ref()?.to_lowercase()),
+    };
+    Some((key, PayeeKind::Counterparty))
+}
+
+/// Groups `transactions` by merchant and counterparty, with the most spent first. If
+/// `collapse_chains` is true every branch of a chain is one entry
+pub fn merchant_directory(
+    transactions: &[Transaction],
+    collapse_chains: bool,
+) -> Vec<DirectoryEntry<'_>> {
+    let mut transactions: Vec<&Transaction> = transactions.iter().collect();
+    transactions.sort_by(|a, b| a.created.partial_cmp(&b.created).unwrap());
+
+    let mut entries: Vec<DirectoryEntry> = vec![];
+    let mut indices = HashMap::<String, usize>::new();
+    let mut branches = HashMap::<String, HashSet<&str>>::new();
+    for transaction in transactions {
+        let (key, kind) = match directory_key(transaction, collapse_chains) {
+            Some(key) => key,
+            None => continue,
+        };
+        let index = *indices.entry(key.clone()).or_insert_with(|| {
+            entries.push(DirectoryEntry {
+                key: key.clone(),
+                name: String::new(),
+                kind,
+                category: None,
+                address: None,
+                visits: 0,
+                spent: BTreeMap::new(),
+                received: BTreeMap::new(),
+                first_seen: transaction.created.clone(),
+                last_seen: transaction.created.clone(),
+                transactions: vec![],
+            });
+            entries.len() - 1
+        });
+        let entry = &mut entries[index];
+        entry.transactions.push(transaction);
+        if transaction.decline_reason.is_some() {
+            continue;
+        }
+        // the latest transaction has the most up to date name, category and address
+        entry.name = transaction.payee_name();
+        entry.category = transaction.category().map(str::to_owned);
+        entry.last_seen = transaction.created.clone();
+        if let Some(merchant) = &transaction.merchant {
+            let branches = branches.entry(key.clone()).or_default();
+            branches.insert(&merchant.id);
+            entry.address = Some(if branches.len() > 1 {
+                format!("{} branches", branches.len())
+            } else {
+                merchant.address.one_line()
+            });
+        }
+        entry.visits += 1;
+        if transaction.amount < 0 {
+            *entry.spent.entry(transaction.currency.clone()).or_default() -= transaction.amount;
+        } else {
+            *entry
+                .received
+                .entry(transaction.currency.clone())
+                .or_default() += transaction.amount;
+        }
+    }
+    // payees that only ever declined have no name yet
+    for entry in &mut entries {
+        if entry.name.is_empty() {
+            entry.name = entry.transactions[0].payee_name();
+        }
+    }
+    entries.sort_by(|a, b| {
+        let spent = |entry: &DirectoryEntry| entry.spent.values().map(|x| *x as i64).sum::<i64>();
+        spent(b)
+            .cmp(&spent(a))
+            .then_with(|| b.visits.cmp(&a.visits))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    entries
+}
+
+/// Finds the entry for the `merchants <merchant>` detail view, by key or by name
+fn find_entry<'a, 'b>(
+    entries: &'b [DirectoryEntry<'a>],
+    selector: &str,
+) -> Result<&'b DirectoryEntry<'a>, error::BadArgumentError> {
+    if let Some(entry) = entries.iter().find(|entry| {
+        entry.key == selector
+            || entry.transactions.iter().any(|transaction| {
+                transaction
+                    .merchant
+                    .as_ref()
+                    .is_some_and(|merchant| merchant.id == selector)
+            })
+    }) {
+        return Ok(entry);
+    }
+    let selector_lowercase = selector.to_lowercase();
+    let matches: Vec<&DirectoryEntry> = entries
+        .iter()
+        .filter(|entry| entry.name.to_lowercase().contains(&selector_lowercase))
+        .collect();
+    match matches.as_slice() {
+        [entry] => Ok(entry),
+        [] => Err(error::BadArgumentError(format!(
+            "no merchant or counterparty matches `{}`",
+            selector
+        ))),
+        _ => match matches
+            .iter()
+            .find(|entry| entry.name.to_lowercase() == selector_lowercase)
+        {
+            Some(entry) => Ok(entry),
+            None => Err(error::BadArgumentError(format!(
+                "`{}` matches more than one merchant, use one of\n{}",
+                selector,
+                matches
+                    .iter()
+                    .map(|entry| format!("  {} [{}]", entry.name, entry.key))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ))),
+        },
+    }
+}
+
+fn print_entry(entry: &DirectoryEntry) {
+    let mut table = Table::new().column("", Align::Left).column("", Align::Left);
+    let mut row = |key: &str, value: String| table.push_row(vec![key.to_owned(), value]);
+    row("NAME", entry.name.clone());
+    row("KEY", entry.key.clone());
+    row("CATEGORY", util::unwrap_to_string(&entry.category, "NULL"));
+    row("ADDRESS", util::unwrap_to_string(&entry.address, "NULL"));
+    row("VISITS", entry.visits.to_string());
+    row("SPENT", entry.spent_string());
+    row("FIRST SEEN", entry.first_seen.to_string());
+    row("LAST SEEN", entry.last_seen.to_string());
+    println!("{}\n", table.render());
+
+    let mut table = Table::new()
+        .column("TIME", Align::Left)
+        .column("AMOUNT", Align::Right)
+        .column("BRANCH", Align::Left)
+        .column("NOTES", Align::Left);
+    for transaction in entry.transactions.iter().rev() {
+        let amount = util::fmt_currency(
+            transaction.amount,
+            &transaction.currency,
+            &FmtCurrencyOptions {
+                include_positive_sign: true,
+                colored: true,
+            },
+        );
+        table.push_row(vec![
+            transaction.created.to_string(),
+            match &transaction.decline_reason {
+                Some(decline_reason) => format!("{} (declined: {})", amount, decline_reason),
+                None => amount,
+            },
+            transaction
+                .merchant
+                .as_ref()
+                .map_or(String::new(), |merchant| merchant.address.one_line()),
+            transaction
+                .metadata
+                .get("notes")
+                .cloned()
+                .unwrap_or_default(),
+        ]);
+    }
+    println!("{}", table.render());
+}
+
+/// The `merchants` command, a directory of everywhere money has gone to or come from.
+/// `merchants <merchant>` shows one of them with all of its transactions
+pub fn merchants(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (since, before) = util::get_time_range(user, command)?;
+    let account_index = util::get_account_index(user, command)?;
+    let output_type = util::get_output_type(command)?;
+    let filter = util::get_filter(command)?;
+    let collapse_chains = !command.args_set.contains("--branches");
+
+    let mut transactions =
+        commands::load_transactions(user, client, command, since, before, account_index)?;
+    transactions.retain(|transaction| filter.matches(transaction));
+    let mut entries = merchant_directory(&transactions, collapse_chains);
+    match command.kwargs.get("sort").map(String::as_str) {
+        Some("spent") | None => {}
+        Some("visits") => entries.sort_by_key(|entry| std::cmp::Reverse(entry.visits)),
+        Some("recent") => entries.sort_by(|a, b| b.last_seen.partial_cmp(&a.last_seen).unwrap()),
+        Some("name") => entries.sort_by_key(|entry| entry.name.to_lowercase()),
+        Some(sort) => {
+            return Err(error::InvalidArgumentError(format!(
+                "`{}` is not a valid sort, use `spent`, `visits`, `recent` or `name`",
+                sort
+            ))
+            .into())
+        }
+    }
+
+    if let Some(selector) = command.positional_args().first() {
+        let entry = find_entry(&entries, selector)?;
+        return match output_type {
+            OutputType::Json => {
+                #[derive(Serialize)]
+                struct EntryWithTransactions<'a> {
+                    #[serde(flatten)]
+                    entry: &'a DirectoryEntry<'a>,
+                    transactions: &'a [&'a Transaction],
+                }
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&EntryWithTransactions {
+                        entry,
+                        transactions: &entry.transactions,
+                    })?
+                );
+                Ok(())
+            }
+            OutputType::Display => {
+                print_entry(entry);
+                Ok(())
+            }
+            _ => Err(error::InvalidArgumentError(
+                "one merchant can only be shown as `display` or `json`".to_owned(),
+            )
+            .into()),
+        };
+    }
+
+    let output = match output_type {
+        OutputType::Json => serde_json::to_string_pretty(&entries)?,
+        OutputType::Csv => {
+            #[derive(Serialize)]
+            struct DirectoryRecord<'a> {
+                key: &'a str,
+                name: &'a str,
+                kind: PayeeKind,
+                category: &'a str,
+                address: &'a str,
+                visits: usize,
+                spent: String,
+                first_seen: &'a time::Time,
+                last_seen: &'a time::Time,
+            }
+
+            util::serde_csv::records_to_string(entries.iter().map(|entry| DirectoryRecord {
+                key: &entry.key,
+                name: &entry.name,
+                kind: entry.kind,
+                category: entry.category.as_deref().unwrap_or(""),
+                address: entry.address.as_deref().unwrap_or(""),
+                visits: entry.visits,
+                spent: entry.spent_string(),
+                first_seen: &entry.first_seen,
+                last_seen: &entry.last_seen,
+            }))?
+        }
+        OutputType::Display => {
+            let mut table = Table::new()
+                .column("NAME", Align::Left)
+                .column("VISITS", Align::Right)
+                .column("SPENT", Align::Right)
+                .column("FIRST SEEN", Align::Left)
+                .column("LAST SEEN", Align::Left)
+                .column("CATEGORY", Align::Left)
+                .column("ADDRESS", Align::Left);
+            for entry in &entries {
+                table.push_row(vec![
+                    entry.name.clone(),
+                    entry.visits.to_string(),
+                    entry.spent_string(),
+                    entry.first_seen.to_string(),
+                    entry.last_seen.to_string(),
+                    entry.category.clone().unwrap_or_default(),
+                    entry.address.clone().unwrap_or_default(),
+                ]);
+            }
+            table.render() + "\n"
+        }
+        OutputType::Ofx | OutputType::Qif | OutputType::Ledger | OutputType::Beancount => {
+            return Err(error::InvalidArgumentError(
+                "statement formats are only supported by `transactions`".to_owned(),
+            )
+            .into())
+        }
+    };
+    std::io::stdout().write_all(output.as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+
+use crate::types::*;
+use crate::*;
+
+/// Escapes a label value for the Prometheus text format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Converts minor currency units to major units, metrics are in e.g. pounds rather than pence
+fn major_units(amount: i64, currency: &str) -> f64 {
+    amount as f64 / 10f64.powi(util::currency_exponent(currency) as i32)
+}
+
+/// Writes metrics in the Prometheus text format, every sample of a metric is written together
+/// under its `# HELP` and `# TYPE` lines
+#[derive(Default)]
+struct MetricsWriter {
+    metrics: Vec<(&'static str, &'static str, &'static str, Vec<String>)>,
+}
+
+impl MetricsWriter {
+    fn sample(
+        &mut self,
+        name: &'static str,
+        metric_type: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        value: f64,
+    ) {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+            .collect();
+        let sample = format!("{}{{{}}} {}", name, labels.join(","), value);
+        match self.metrics.iter_mut().find(|metric| metric.0 == name) {
+            Some(metric) => metric.3.push(sample),
+            None => self.metrics.push((name, metric_type, help, vec![sample])),
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut s = String::new();
+        for (name, metric_type, help, samples) in &self.metrics {
+            s.push_str(&format!("# HELP {} {}\n", name, help));
+            s.push_str(&format!("# TYPE {} {}\n", name, metric_type));
+            for sample in samples {
+                s.push_str(sample);
+                s.push('\n');
+            }
+        }
+        s
+    }
+}
+
+/// The balances of an account and its pots, for `render_metrics`
+pub struct AccountMetrics<'a> {
+    pub account: &'a Account,
+    pub balance: &'a Balance,
+    pub pots: &'a [Pot],
+    pub transactions: Vec<&'a Transaction>,
+}
+
+/// Renders gauges for every balance and counters of the spending in the cached transactions,
+/// in the Prometheus text format
+pub fn render_metrics(accounts: &[AccountMetrics]) -> String {
+    let mut metrics = MetricsWriter::default();
+    for AccountMetrics {
+        account,
+        balance,
+        pots,
+        transactions,
+    } in accounts
+    {
+        let labels = [
+            ("account_id", account.id.as_str()),
+            ("account_type", account.account_type.as_str()),
+            ("currency", balance.currency.as_str()),
+        ];
+        let currency = balance.currency.as_str();
+        metrics.sample(
+            "monzo_balance",
+            "gauge",
+            "The balance of an account, in major currency units",
+            &labels,
+            major_units(balance.balance as i64, currency),
+        );
+        metrics.sample(
+            "monzo_total_balance",
+            "gauge",
+            "The balance of an account and its pots, in major currency units",
+            &labels,
+            major_units(balance.total_balance as i64, currency),
+        );
+        metrics.sample(
+            "monzo_balance_including_flexible_savings",
+            "gauge",
+            "The balance of an account and its flexible savings, in major currency units",
+            &labels,
+            major_units(balance.balance_including_flexible_savings as i64, currency),
+        );
+        metrics.sample(
+            "monzo_spend_today",
+            "gauge",
+            "The amount spent from an account today, in major currency units",
+            &labels,
+            major_units(balance.spend_today as i64, currency),
+        );
+        for pot in pots.iter().filter(|pot| !pot.deleted) {
+            metrics.sample(
+                "monzo_pot_balance",
+                "gauge",
+                "The balance of a pot, in major currency units",
+                &[
+                    ("account_id", account.id.as_str()),
+                    ("pot_id", pot.id.as_str()),
+                    ("pot_name", pot.name.as_str()),
+                    ("currency", pot.currency.as_str()),
+                ],
+                major_units(pot.balance as i64, &pot.currency),
+            );
+        }
+
+        // (currency, category or merchant) -> amount spent
+        let mut categories = BTreeMap::<(&str, String), i64>::new();
+        let mut merchants = BTreeMap::<(&str, String), i64>::new();
+        let mut count = 0;
+        for transaction in transactions {
+            if transaction.decline_reason.is_some() {
+                continue;
+            }
+            count += 1;
+            if transaction.amount >= 0 {
+                continue;
+            }
+            let spent = -(transaction.amount as i64);
+            let currency = transaction.currency.as_str();
+            let category = match transaction.category() {
+                Some(category) => category.to_owned(),
+                None => "uncategorised".to_owned(),
+            };
+            *categories.entry((currency, category)).or_default() += spent;
+            *merchants
+                .entry((currency, transaction.payee_name()))
+                .or_default() += spent;
+        }
+        metrics.sample(
+            "monzo_transactions_total",
+            "counter",
+            "The number of cached transactions that weren't declined",
+            &[("account_id", account.id.as_str())],
+            count as f64,
+        );
+        for ((currency, category), spent) in categories {
+            metrics.sample(
+                "monzo_category_spend_total",
+                "counter",
+                "The amount spent in each category in the cached transactions, in major currency \
+                units",
+                &[
+                    ("account_id", account.id.as_str()),
+                    ("category", &category),
+                    ("currency", currency),
+                ],
+                major_units(spent, currency),
+            );
+        }
+        for ((currency, merchant), spent) in merchants {
+            metrics.sample(
+                "monzo_merchant_spend_total",
+                "counter",
+                "The amount spent at each merchant or counterparty in the cached transactions, \
+                in major currency units",
+                &[
+                    ("account_id", account.id.as_str()),
+                    ("merchant", &merchant),
+                    ("currency", currency),
+                ],
+                major_units(spent, currency),
+            );
+        }
+    }
+    metrics.render()
+}
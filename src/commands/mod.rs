@@ -6,7 +6,39 @@ mod get_accounts;
 pub use get_accounts::*;
 mod account;
 pub use account::*;
+mod accounts;
+pub use accounts::*;
 mod token;
 pub use token::*;
 mod transactions;
-pub use transactions::*;
\ No newline at end of file
+pub use transactions::*;
+mod recurring;
+pub use recurring::*;
+mod history;
+pub use history::*;
+mod daemon;
+pub use daemon::*;
+mod serve;
+pub use serve::*;
+mod metrics;
+pub use metrics::*;
+mod attachments;
+pub use attachments::*;
+mod expenses;
+pub use expenses::*;
+mod split;
+pub use split::*;
+mod categorise;
+pub use categorise::*;
+mod merchants;
+pub use merchants::*;
+mod fx;
+pub use fx::*;
+mod import;
+pub use import::*;
+mod reconcile;
+pub use reconcile::*;
+mod offline;
+pub use offline::*;
+mod tui;
+pub use tui::*;
\ No newline at end of file
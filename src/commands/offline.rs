@@ -0,0 +1,204 @@
+use crate::types::user::CachedBalance;
+use crate::types::*;
+use crate::*;
+
+/// Whether `--offline` was given, or the access token expired and only the cache can be used
+pub fn is_offline(command: &cli::Command) -> bool {
+    command.args_set.contains("--offline")
+}
+
+/// Says that the output is made from cached data. This goes to stderr so that json and csv
+/// output can still be parsed
+fn warn_stale(reason: &str, what: &str) {
+    eprintln!(
+        "{}",
+        cli::AnsiStringBuilder::new()
+            .set_bold(true)
+            .push_str(&format!("{}, {}", reason, what))
+            .build()
+    );
+}
+
+/// Why the cache is being used, `error` is the error from trying to reach Monzo
+fn stale_reason(error: Option<&dyn std::fmt::Display>) -> String {
+    match error {
+        Some(e) => format!("STALE: could not reach Monzo ({})", e),
+        None => "STALE: offline".to_owned(),
+    }
+}
+
+fn save_cache(user: &user::User) {
+    // the cache is only a fallback, so failing to save it shouldn't stop the command
+    if let Err(e) = user_file::update_user_file(user) {
+        eprintln!("could not update the cache -- {}", e);
+    }
+}
+
+/// The transactions of an account from `since` until `before`, with the categories and tags
+/// from the rules in the config. They are fetched and cached, or read from the cache when
+/// offline or when Monzo can't be reached
+pub fn load_transactions(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+    since: Option<time::Time>,
+    before: Option<time::Time>,
+    account_index: usize,
+) -> Result<Vec<Transaction>, Box<dyn std::error::Error>> {
+    let config = config_file::load_config_file()?;
+    let rules = rules::Rules::new(&config.rules)?;
+    // imported accounts are only ever in the cache, so they are never stale
+    let imported = user.accounts[account_index].import_source().is_some();
+    let reason = if is_offline(command) && !imported {
+        stale_reason(None)
+    } else {
+        match pollster::block_on(commands::get_transactions(
+            user,
+            client,
+            since.clone(),
+            before.clone(),
+            account_index,
+        )) {
+            Ok(mut transactions) => {
+                for transaction in &mut transactions {
+                    rules.apply(transaction);
+                }
+                user.cache_transactions(&transactions);
+                save_cache(user);
+                return Ok(transactions);
+            }
+            Err(e) => stale_reason(Some(&e)),
+        }
+    };
+
+    let account_id = user.accounts[account_index].id.clone();
+    let mut transactions: Vec<Transaction> = user
+        .cached_transactions_between(&account_id, since.as_ref(), before.as_ref())
+        .into_iter()
+        .cloned()
+        .collect();
+    // the rules may have changed since the transactions were cached
+    for transaction in &mut transactions {
+        rules.apply(transaction);
+    }
+    match user.cached_transactions(&account_id).last() {
+        Some(newest) => warn_stale(
+            &reason,
+            &format!(
+                "showing cached transactions, the newest is from {}",
+                newest.created
+            ),
+        ),
+        None => warn_stale(&reason, "there are no cached transactions for this account"),
+    }
+    Ok(transactions)
+}
+
+/// Reads the last known balance of an account, for when it can't be fetched
+fn cached_balance<'a>(
+    user: &'a user::User,
+    account_index: usize,
+    reason: &str,
+) -> Result<&'a CachedBalance, Box<dyn std::error::Error>> {
+    let account = &user.accounts[account_index];
+    match user.balances.get(&account.id) {
+        Some(cached) => {
+            warn_stale(
+                reason,
+                &format!(
+                    "showing the balance of {} from {}",
+                    account.account_number, cached.fetched
+                ),
+            );
+            Ok(cached)
+        }
+        None => Err(error::BadArgumentError(format!(
+            "{}, and the balance of {} has never been fetched",
+            reason, account.account_number
+        ))
+        .into()),
+    }
+}
+
+/// The balance of an account, fetched and cached, or the last known balance when offline or
+/// when Monzo can't be reached
+pub fn load_balance(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+    account_index: usize,
+) -> Result<Balance, Box<dyn std::error::Error>> {
+    let imported = user.accounts[account_index].import_source().is_some();
+    let reason = if is_offline(command) && !imported {
+        stale_reason(None)
+    } else {
+        match pollster::block_on(commands::get_balance(user, client, account_index)) {
+            Ok(balance) => {
+                let account_id = user.accounts[account_index].id.clone();
+                user.cache_balance(&account_id, &balance, None);
+                save_cache(user);
+                return Ok(balance);
+            }
+            Err(e) => stale_reason(Some(&e)),
+        }
+    };
+    Ok(cached_balance(user, account_index, &reason)?
+        .balance
+        .clone())
+}
+
+/// The balance and pots of each account
+type AccountBalances = Vec<(Balance, Vec<Pot>)>;
+
+/// The balance and pots of each of the accounts at `account_indices`, like `load_balance`
+pub fn load_all_balances(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+    account_indices: &[usize],
+) -> Result<AccountBalances, Box<dyn std::error::Error>> {
+    let offline = is_offline(command);
+    let balances = pollster::block_on(refresh_all_balances(user, client, offline, account_indices));
+    if !offline {
+        save_cache(user);
+    }
+    balances
+}
+
+/// Fetches the balance and pots of each of the accounts at `account_indices` and caches them
+/// in `user` without saving the user file, or reads the last known ones when `offline` or
+/// when Monzo can't be reached
+pub async fn refresh_all_balances(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    offline: bool,
+    account_indices: &[usize],
+) -> Result<AccountBalances, Box<dyn std::error::Error>> {
+    let reason = if offline {
+        stale_reason(None)
+    } else {
+        match commands::get_all_balances(user, client, account_indices).await {
+            Ok(balances) => {
+                for (&account_index, (balance, pots)) in account_indices.iter().zip(&balances) {
+                    let account_id = user.accounts[account_index].id.clone();
+                    user.cache_balance(&account_id, balance, Some(pots));
+                }
+                return Ok(balances);
+            }
+            Err(e) => stale_reason(Some(&e)),
+        }
+    };
+    account_indices
+        .iter()
+        .map(|&account_index| {
+            let account = &user.accounts[account_index];
+            if account.import_source().is_some() {
+                let transactions = user.cached_transactions(&account.id);
+                let balance = import::statement_balance(&transactions, &account.currency);
+                return Ok((balance, vec![]));
+            }
+            let cached = cached_balance(user, account_index, &reason)?;
+            Ok((cached.balance.clone(), cached.pots.clone()))
+        })
+        .collect()
+}
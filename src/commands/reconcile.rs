@@ -0,0 +1,426 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::cli::table::{Align, Table};
+use crate::types::*;
+use crate::*;
+
+/// A statement entry and the Monzo transaction it was matched with
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconciledPair<'a> {
+    pub entry: &'a Transaction,
+    pub transaction: &'a Transaction,
+    /// The number of days between the date on the statement and the Monzo transaction
+    pub days_apart: i64,
+}
+
+/// Where the balance on the statement stops agreeing with the Monzo balance, or by how much
+/// it disagrees changes
+#[derive(Debug, Clone, Serialize)]
+pub struct BalanceDrift<'a> {
+    pub entry: &'a Transaction,
+    pub transaction: &'a Transaction,
+    pub statement_balance: i32,
+    pub monzo_balance: i32,
+    /// The statement balance minus the Monzo balance
+    pub difference: i32,
+}
+
+/// Two charges at the same merchant, for the same amount, a few minutes apart
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateCharge<'a> {
+    pub first: &'a Transaction,
+    pub second: &'a Transaction,
+    pub minutes_apart: i64,
+}
+
+/// How the entries of a statement match up with Monzo transactions
+#[derive(Debug, Clone, Serialize)]
+pub struct Reconciliation<'a> {
+    pub matched: Vec<ReconciledPair<'a>>,
+    /// Entries on the statement that aren't in Monzo
+    pub unmatched_entries: Vec<&'a Transaction>,
+    /// Monzo transactions from the time the statement covers that aren't on it
+    pub unmatched_transactions: Vec<&'a Transaction>,
+    pub drifts: Vec<BalanceDrift<'a>>,
+}
+
+/// The lower case words in `s`, ignoring single characters such as the `*` in `AMZN*MKTP`
+fn words(s: &str) -> HashSet<String> {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// The number of words that the description on the statement shares with the transaction
+fn similarity(entry: &Transaction, transaction: &Transaction) -> usize {
+    let transaction_words = words(&format!(
+        "{} {}",
+        transaction.payee_name(),
+        transaction.description
+    ));
+    words(&entry.description)
+        .intersection(&transaction_words)
+        .count()
+}
+
+fn days_apart(a: &time::Time, b: &time::Time) -> i64 {
+    (a.date_time().date() - b.date_time().date())
+        .num_days()
+        .abs()
+}
+
+/// Matches the entries of a statement with Monzo transactions. Entries exported from Monzo
+/// are matched by id, any others are matched with a transaction for the same amount at most
+/// `window_days` days apart, preferring the most similar description and then the closest
+/// date. Declined transactions and transactions for nothing (e.g. card checks) are left out,
+/// as they are never on statements. Both lists are oldest first
+pub fn match_statement<'a>(
+    entries: &'a [Transaction],
+    transactions: &'a [Transaction],
+    window_days: i64,
+) -> Reconciliation<'a> {
+    let entries: Vec<&Transaction> = entries.iter().filter(|entry| entry.amount != 0).collect();
+    let transactions: Vec<&Transaction> = transactions
+        .iter()
+        .filter(|transaction| transaction.decline_reason.is_none() && transaction.amount != 0)
+        .collect();
+    let mut matched_entries = vec![None; entries.len()];
+    let mut matched_transactions = vec![false; transactions.len()];
+
+    for (i, entry) in entries.iter().enumerate() {
+        if let Some(j) = transactions
+            .iter()
+            .position(|transaction| transaction.id == entry.id)
+        {
+            matched_entries[i] = Some(j);
+            matched_transactions[j] = true;
+        }
+    }
+    for (i, entry) in entries.iter().enumerate() {
+        if matched_entries[i].is_some() {
+            continue;
+        }
+        let best = transactions
+            .iter()
+            .enumerate()
+            .filter(|(j, transaction)| {
+                !matched_transactions[*j]
+                    && transaction.amount == entry.amount
+                    && days_apart(&entry.created, &transaction.created) <= window_days
+            })
+            .min_by_key(|(_, transaction)| {
+                (
+                    std::cmp::Reverse(similarity(entry, transaction)),
+                    days_apart(&entry.created, &transaction.created),
+                )
+            });
+        if let Some((j, _)) = best {
+            matched_entries[i] = Some(j);
+            matched_transactions[j] = true;
+        }
+    }
+
+    let mut matched: Vec<ReconciledPair> = entries
+        .iter()
+        .zip(&matched_entries)
+        .filter_map(|(entry, j)| {
+            let transaction = transactions[(*j)?];
+            Some(ReconciledPair {
+                entry,
+                transaction,
+                days_apart: days_apart(&entry.created, &transaction.created),
+            })
+        })
+        .collect();
+    matched.sort_by(|a, b| {
+        a.transaction
+            .created
+            .partial_cmp(&b.transaction.created)
+            .unwrap()
+    });
+
+    // a drift is only reported where it changes, rather than on every transaction after it
+    let mut drifts = vec![];
+    let mut last_difference = 0;
+    for pair in &matched {
+        if let (Some(statement_balance), Some(monzo_balance)) =
+            (pair.entry.account_balance, pair.transaction.account_balance)
+        {
+            let difference = statement_balance - monzo_balance;
+            if difference != last_difference {
+                drifts.push(BalanceDrift {
+                    entry: pair.entry,
+                    transaction: pair.transaction,
+                    statement_balance,
+                    monzo_balance,
+                    difference,
+                });
+                last_difference = difference;
+            }
+        }
+    }
+
+    // statements are by day, so the last day is covered until its end
+    let covered = |transaction: &Transaction| match (entries.first(), entries.last()) {
+        (Some(first), Some(last)) => {
+            let date = transaction.created.date_time().date();
+            date >= first.created.date_time().date() && date <= last.created.date_time().date()
+        }
+        _ => false,
+    };
+    Reconciliation {
+        matched,
+        unmatched_entries: entries
+            .iter()
+            .zip(&matched_entries)
+            .filter(|(_, j)| j.is_none())
+            .map(|(entry, _)| *entry)
+            .collect(),
+        unmatched_transactions: transactions
+            .iter()
+            .zip(&matched_transactions)
+            .filter(|(transaction, matched)| !**matched && covered(transaction))
+            .map(|(transaction, _)| *transaction)
+            .collect(),
+        drifts,
+    }
+}
+
+/// Card charges at the same merchant for the same amount at most `minutes` minutes apart,
+/// which are often the same thing charged twice. `transactions` are oldest first
+pub fn duplicate_charges(transactions: &[Transaction], minutes: i64) -> Vec<DuplicateCharge<'_>> {
+    let charges: Vec<&Transaction> = transactions
+        .iter()
+        .filter(|transaction| {
+            transaction.merchant.is_some()
+                && transaction.decline_reason.is_none()
+                && transaction.amount < 0
+        })
+        .collect();
+    let mut duplicates = vec![];
+    // a charge is only reported once, so three charges are two duplicates rather than three
+    let mut reported = HashSet::new();
+    for (i, first) in charges.iter().enumerate() {
+        for second in &charges[i + 1..] {
+            let minutes_apart =
+                (*second.created.date_time() - *first.created.date_time()).num_minutes();
+            if minutes_apart > minutes {
+                break;
+            }
+            let same_merchant = first.merchant.as_ref().map(|merchant| &merchant.id)
+                == second.merchant.as_ref().map(|merchant| &merchant.id);
+            if same_merchant && first.amount == second.amount && reported.insert(&second.id) {
+                duplicates.push(DuplicateCharge {
+                    first,
+                    second,
+                    minutes_apart,
+                });
+            }
+        }
+    }
+    duplicates
+}
+
+/// Reads the entries of a statement or journal, oldest first. Journals are read for the
+/// postings to `ledger_account`
+fn read_statement(
+    path: &str,
+    config: &config::Config,
+    command: &cli::Command,
+    ledger_account: &str,
+    currency: &str,
+) -> Result<Vec<Transaction>, Box<dyn std::error::Error>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| error::BadArgumentError(format!("could not read {} -- {}", path, e)))?;
+    let extension = Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let mut entries = match extension.as_deref() {
+        Some("ledger") | Some("journal") | Some("hledger") | Some("dat") | Some("beancount")
+        | Some("bean") => import::parse_ledger("statement", &contents, ledger_account, currency)?,
+        _ => {
+            let source = match command.kwargs.get("import") {
+                Some(name) => config.imports.get(name).cloned().ok_or_else(|| {
+                    error::BadArgumentError(format!("there is no `{}` import in the config", name))
+                })?,
+                None => config::ImportSource {
+                    currency: currency.to_owned(),
+                    ..Default::default()
+                },
+            };
+            import::parse_statement("statement", &source, path, &contents)?
+        }
+    };
+    entries.sort_by(|a, b| a.created.partial_cmp(&b.created).unwrap());
+    Ok(entries)
+}
+
+fn fmt_amount(amount: i32, currency: &str) -> String {
+    format!("{} {}", util::fmt_decimal(amount, currency), currency)
+}
+
+fn print_reconciliation(reconciliation: &Reconciliation, duplicates: &[DuplicateCharge]) {
+    let entries = reconciliation.matched.len() + reconciliation.unmatched_entries.len();
+    println!(
+        "matched {} of {} statement entries",
+        reconciliation.matched.len(),
+        entries
+    );
+
+    if !reconciliation.unmatched_entries.is_empty() {
+        let mut table = Table::new()
+            .column("DATE", Align::Left)
+            .column("DESCRIPTION", Align::Left)
+            .column("AMOUNT", Align::Right);
+        for entry in &reconciliation.unmatched_entries {
+            table.push_row(vec![
+                entry.created.date_time().format("%Y-%m-%d").to_string(),
+                entry.description.clone(),
+                fmt_amount(entry.amount, &entry.currency),
+            ]);
+        }
+        println!("\non the statement but not in Monzo\n{}", table.render());
+    }
+
+    if !reconciliation.unmatched_transactions.is_empty() {
+        let mut table = Table::new()
+            .column("TIME", Align::Left)
+            .column("PAYEE", Align::Left)
+            .column("AMOUNT", Align::Right)
+            .column("ID", Align::Left);
+        for transaction in &reconciliation.unmatched_transactions {
+            table.push_row(vec![
+                transaction.created.to_string(),
+                transaction.payee_name(),
+                fmt_amount(transaction.amount, &transaction.currency),
+                transaction.id.clone(),
+            ]);
+        }
+        println!("\nin Monzo but not on the statement\n{}", table.render());
+    }
+
+    if !reconciliation.drifts.is_empty() {
+        let mut table = Table::new()
+            .column("TIME", Align::Left)
+            .column("PAYEE", Align::Left)
+            .column("STATEMENT BALANCE", Align::Right)
+            .column("MONZO BALANCE", Align::Right)
+            .column("DIFFERENCE", Align::Right);
+        for drift in &reconciliation.drifts {
+            let currency = &drift.transaction.currency;
+            table.push_row(vec![
+                drift.transaction.created.to_string(),
+                drift.transaction.payee_name(),
+                fmt_amount(drift.statement_balance, currency),
+                fmt_amount(drift.monzo_balance, currency),
+                fmt_amount(drift.difference, currency),
+            ]);
+        }
+        println!("\nthe balances drift apart\n{}", table.render());
+    }
+
+    if !duplicates.is_empty() {
+        let mut table = Table::new()
+            .column("TIME", Align::Left)
+            .column("PAYEE", Align::Left)
+            .column("AMOUNT", Align::Right)
+            .column("APART", Align::Right)
+            .column("IDS", Align::Left);
+        for duplicate in duplicates {
+            table.push_row(vec![
+                duplicate.second.created.to_string(),
+                duplicate.second.payee_name(),
+                fmt_amount(duplicate.second.amount, &duplicate.second.currency),
+                format!("{}m", duplicate.minutes_apart),
+                format!("{} {}", duplicate.first.id, duplicate.second.id),
+            ]);
+        }
+        println!("\nlikely duplicate charges\n{}", table.render());
+    }
+}
+
+/// The `reconcile` command, matches a statement or journal with Monzo transactions and reports
+/// what is missing from either side, where the balances disagree and likely duplicate charges
+pub fn reconcile(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = match command.positional_args().as_slice() {
+        [path] => path.to_string(),
+        _ => {
+            return Err(error::BadArgumentError(
+                "use `reconcile <file>` with a csv or ofx statement or a ledger or beancount \
+                journal"
+                    .to_owned(),
+            )
+            .into())
+        }
+    };
+    let account_index = util::get_account_index(user, command)?;
+    let output_type = util::get_output_type(command)?;
+    let number_kwarg = |name: &str, default: i64| match command.kwargs.get(name) {
+        Some(value) => value
+            .parse::<i64>()
+            .map_err(|_| error::BadArgumentError(format!("`{}` must be a whole number", name))),
+        None => Ok(default),
+    };
+    let window_days = number_kwarg("window", 3)?;
+    let minutes = number_kwarg("minutes", 10)?;
+
+    let config = config_file::load_config_file()?;
+    let account = &user.accounts[account_index];
+    let ledger_account = match command.kwargs.get("ledger_account") {
+        Some(ledger_account) => ledger_account.clone(),
+        None => config.ledger_for(account).account,
+    };
+    let mut entries = read_statement(&path, &config, command, &ledger_account, &account.currency)?;
+
+    // fetch the transactions from the time the statement covers, with some room either side
+    let (mut since, mut before) = util::get_time_range(user, command)?;
+    let window = chrono::Duration::days(window_days);
+    if !command.kwargs.contains_key("since") {
+        since = entries.first().map(|entry| entry.created.add(&-window));
+    }
+    if !command.kwargs.contains_key("before") {
+        before = entries
+            .last()
+            .map(|entry| entry.created.add(&(window + chrono::Duration::days(1))));
+    }
+    entries.retain(|entry| {
+        since.as_ref().is_none_or(|since| &entry.created >= since)
+            && before.as_ref().is_none_or(|before| &entry.created < before)
+    });
+    let transactions =
+        commands::load_transactions(user, client, command, since, before, account_index)?;
+
+    let reconciliation = match_statement(&entries, &transactions, window_days);
+    let duplicates = duplicate_charges(&transactions, minutes);
+    match output_type {
+        OutputType::Json => {
+            #[derive(Serialize)]
+            struct Report<'a> {
+                #[serde(flatten)]
+                reconciliation: &'a Reconciliation<'a>,
+                duplicates: &'a [DuplicateCharge<'a>],
+            }
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&Report {
+                    reconciliation: &reconciliation,
+                    duplicates: &duplicates,
+                })?
+            );
+        }
+        OutputType::Display => print_reconciliation(&reconciliation, &duplicates),
+        _ => {
+            return Err(error::InvalidArgumentError(
+                "a reconciliation can only be shown as `display` or `json`".to_owned(),
+            )
+            .into())
+        }
+    }
+    Ok(())
+}
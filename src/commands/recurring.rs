@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::cli::table::{Align, Table};
+use crate::types::*;
+use crate::util::FmtCurrencyOptions;
+use crate::*;
+
+/// Charges of the same amount from the same place within this many hours of each other are
+/// considered duplicates
+const DUPLICATE_WINDOW_HOURS: i64 = 48;
+
+/// How often a recurring payment is taken
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Cadence {
+    Weekly,
+    Monthly,
+    Annual,
+}
+
+impl Cadence {
+    /// Classifies the gap between two payments, returns `None` if the gap doesn't look like
+    /// any cadence
+    fn from_days(days: i64) -> Option<Self> {
+        match days {
+            5..=9 => Some(Self::Weekly),
+            26..=35 => Some(Self::Monthly),
+            350..=380 => Some(Self::Annual),
+            _ => None,
+        }
+    }
+
+    /// The rough number of days in one period
+    fn days(&self) -> i64 {
+        match self {
+            Self::Weekly => 7,
+            Self::Monthly => 30,
+            Self::Annual => 365,
+        }
+    }
+
+    /// How late a payment can be before it is considered missed
+    fn grace_days(&self) -> i64 {
+        match self {
+            Self::Weekly => 3,
+            Self::Monthly => 7,
+            Self::Annual => 30,
+        }
+    }
+
+    /// Returns `time` advanced by `n` periods
+    fn advance(&self, time: &time::Time, n: u32) -> time::Time {
+        let date_time = *time.date_time();
+        time::Time::from(match self {
+            Self::Weekly => date_time + chrono::Duration::weeks(n as i64),
+            Self::Monthly => date_time
+                .checked_add_months(chrono::Months::new(n))
+                .unwrap_or(date_time),
+            Self::Annual => date_time
+                .checked_add_months(chrono::Months::new(12 * n))
+                .unwrap_or(date_time),
+        })
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Weekly => "weekly",
+            Self::Monthly => "monthly",
+            Self::Annual => "annual",
+        }
+    }
+}
+
+/// Something about a recurring payment that the user probably wants to know about
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RecurringFlag {
+    PriceRise {
+        from: i32,
+        to: i32,
+        at: time::Time,
+    },
+    Missed {
+        expected: time::Time,
+    },
+    Duplicate {
+        transaction_id: String,
+        at: time::Time,
+    },
+}
+
+impl RecurringFlag {
+    fn to_string(&self, currency: &str) -> String {
+        let fmt =
+            |amount: i32| util::fmt_currency(amount, currency, &FmtCurrencyOptions::default());
+        match self {
+            Self::PriceRise { from, to, at } => {
+                format!("price rise from {} to {} on {}", fmt(*from), fmt(*to), at)
+            }
+            Self::Missed { expected } => format!("missed payment, expected on {}", expected),
+            Self::Duplicate { transaction_id, at } => {
+                format!("duplicate charge on {} [{}]", at, transaction_id)
+            }
+        }
+    }
+}
+
+/// A subscription or standing payment detected in transaction history
+#[derive(Debug, Clone, Serialize)]
+pub struct RecurringPayment {
+    pub name: String,
+    pub cadence: Cadence,
+    pub occurrences: usize,
+    pub last_charged: time::Time,
+    pub expected_next: time::Time,
+    pub expected_amount: i32,
+    pub currency: String,
+    pub flags: Vec<RecurringFlag>,
+}
+
+/// The key that groups transactions to the same payee
+fn payee_key(transaction: &Transaction) -> String {
+    if let Some(merchant) = &transaction.merchant {
+        return format!("merchant:{}", merchant.group_id);
+    }
+    if let Some(counterparty) = &transaction.counterparty {
+        if counterparty.account_number.is_some() || counterparty.user_id.is_some() {
+            return format!(
+                "counterparty:{}:{}:{}",
+                util::unwrap_to_string(&counterparty.sort_code, ""),
+                util::unwrap_to_string(&counterparty.account_number, ""),
+                util::unwrap_to_string(&counterparty.user_id, ""),
+            );
+        }
+    }
+    format!("description:{}", transaction.description)
+}
+
+fn days_between(a: &time::Time, b: &time::Time) -> i64 {
+    b.date_time()
+        .signed_duration_since(*a.date_time())
+        .num_days()
+}
+
+/// Detects subscriptions and standing payments in `transactions`, `now` is used to decide
+/// whether the latest payment has been missed
+pub fn detect_recurring(transactions: &[Transaction], now: &time::Time) -> Vec<RecurringPayment> {
+    let mut payees = HashMap::<String, Vec<&Transaction>>::new();
+    for transaction in transactions {
+        if transaction.decline_reason.is_some() || transaction.amount >= 0 {
+            continue;
+        }
+        payees
+            .entry(payee_key(transaction))
+            .or_default()
+            .push(transaction);
+    }
+
+    let mut recurring = vec![];
+    for (_, mut payments) in payees {
+        payments.sort_by(|a, b| a.created.partial_cmp(&b.created).unwrap());
+
+        let mut flags = vec![];
+        let mut charges: Vec<&Transaction> = vec![];
+        for payment in payments {
+            if let Some(previous) = charges.last() {
+                let hours = payment
+                    .created
+                    .date_time()
+                    .signed_duration_since(*previous.created.date_time())
+                    .num_hours();
+                if payment.amount == previous.amount && hours < DUPLICATE_WINDOW_HOURS {
+                    flags.push(RecurringFlag::Duplicate {
+                        transaction_id: payment.id.clone(),
+                        at: payment.created.clone(),
+                    });
+                    continue;
+                }
+            }
+            charges.push(payment);
+        }
+        if charges.len() < 2 {
+            continue;
+        }
+
+        let gaps: Vec<i64> = charges
+            .windows(2)
+            .map(|w| days_between(&w[0].created, &w[1].created))
+            .collect();
+        let cadence = {
+            let mut sorted_gaps = gaps.clone();
+            sorted_gaps.sort();
+            match Cadence::from_days(sorted_gaps[sorted_gaps.len() / 2]) {
+                Some(cadence) => cadence,
+                None => continue,
+            }
+        };
+        // a single pair of payments is only convincing for yearly payments, anything more
+        // frequent should have shown up at least three times
+        if charges.len() < 3 && cadence != Cadence::Annual {
+            continue;
+        }
+
+        // Every gap should be a whole number of periods, with more than one period meaning
+        // that we missed a payment
+        let mut irregular_gaps = 0;
+        for (i, gap) in gaps.iter().enumerate() {
+            let periods = (*gap as f64 / cadence.days() as f64).round() as i64;
+            if periods == 0
+                || (*gap - periods * cadence.days()).abs() > cadence.grace_days() * periods
+            {
+                irregular_gaps += 1;
+                continue;
+            }
+            for n in 1..periods {
+                flags.push(RecurringFlag::Missed {
+                    expected: cadence.advance(&charges[i].created, n as u32),
+                });
+            }
+        }
+        if irregular_gaps * 3 > gaps.len() {
+            continue;
+        }
+
+        // Groceries from the same shop every week aren't a subscription, so the amount should
+        // be pretty stable
+        let mut amount_changes = 0;
+        for w in charges.windows(2) {
+            if w[0].amount != w[1].amount {
+                amount_changes += 1;
+                if w[1].amount < w[0].amount {
+                    flags.push(RecurringFlag::PriceRise {
+                        from: w[0].amount,
+                        to: w[1].amount,
+                        at: w[1].created.clone(),
+                    });
+                }
+            }
+        }
+        if amount_changes > std::cmp::max(1, charges.len() / 3) {
+            continue;
+        }
+
+        let last = charges[charges.len() - 1];
+        let expected_next = cadence.advance(&last.created, 1);
+        if days_between(&expected_next, now) > cadence.grace_days() {
+            flags.push(RecurringFlag::Missed {
+                expected: expected_next.clone(),
+            });
+        }
+
+        flags.sort_by(|a, b| {
+            let at = |flag: &RecurringFlag| match flag {
+                RecurringFlag::PriceRise { at, .. } => at.clone(),
+                RecurringFlag::Missed { expected } => expected.clone(),
+                RecurringFlag::Duplicate { at, .. } => at.clone(),
+            };
+            at(a).partial_cmp(&at(b)).unwrap()
+        });
+        recurring.push(RecurringPayment {
+            name: last.payee_name(),
+            cadence,
+            occurrences: charges.len(),
+            last_charged: last.created.clone(),
+            expected_next,
+            expected_amount: last.amount,
+            currency: last.currency.clone(),
+            flags,
+        });
+    }
+
+    recurring.sort_by(|a, b| a.expected_next.partial_cmp(&b.expected_next).unwrap());
+    recurring
+}
+
+/// The `recurring` command
+pub fn recurring(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (since, before) = util::get_time_range(user, command)?;
+    let account_index = util::get_account_index(user, command)?;
+    let output_type = util::get_output_type(command)?;
+    let filter = util::get_filter(command)?;
+
+    let mut transactions =
+        commands::load_transactions(user, client, command, since, before, account_index)?;
+    transactions.retain(|transaction| filter.matches(transaction));
+    let recurring = detect_recurring(&transactions, &time::Time::now());
+
+    let output = match output_type {
+        OutputType::Json => serde_json::to_string_pretty(&recurring)?,
+        OutputType::Ofx | OutputType::Qif | OutputType::Ledger | OutputType::Beancount => {
+            return Err(error::InvalidArgumentError(
+                "statement formats are only supported by `transactions`".to_owned(),
+            )
+            .into())
+        }
+        OutputType::Csv => {
+            #[derive(Serialize)]
+            struct RecurringRecord<'a> {
+                name: &'a str,
+                cadence: Cadence,
+                occurrences: usize,
+                last_charged: &'a time::Time,
+                expected_next: &'a time::Time,
+                expected_amount: i32,
+                currency: &'a str,
+                flags: String,
+            }
+
+            util::serde_csv::records_to_string(recurring.iter().map(|payment| {
+                RecurringRecord {
+                    name: &payment.name,
+                    cadence: payment.cadence,
+                    occurrences: payment.occurrences,
+                    last_charged: &payment.last_charged,
+                    expected_next: &payment.expected_next,
+                    expected_amount: payment.expected_amount,
+                    currency: &payment.currency,
+                    flags: payment
+                        .flags
+                        .iter()
+                        .map(|flag| flag.to_string(&payment.currency))
+                        .collect::<Vec<_>>()
+                        .join("; "),
+                }
+            }))?
+        }
+        OutputType::Display => {
+            let mut table = Table::new()
+                .column("AMOUNT", Align::Right)
+                .column("CADENCE", Align::Left)
+                .column("NAME", Align::Left)
+                .column("NEXT EXPECTED", Align::Left)
+                .column("SEEN", Align::Right)
+                .column("FLAGS", Align::Left);
+            for payment in &recurring {
+                let flags: Vec<String> = payment
+                    .flags
+                    .iter()
+                    .map(|flag| {
+                        cli::AnsiStringBuilder::new()
+                            .set_foreground_color(255, 170, 20)
+                            .push_str(&flag.to_string(&payment.currency))
+                            .build()
+                    })
+                    .collect();
+                table.push_row(vec![
+                    util::fmt_currency(
+                        payment.expected_amount,
+                        &payment.currency,
+                        &FmtCurrencyOptions {
+                            include_positive_sign: true,
+                            colored: true,
+                        },
+                    ),
+                    payment.cadence.as_str().to_owned(),
+                    payment.name.clone(),
+                    payment.expected_next.to_string(),
+                    payment.occurrences.to_string(),
+                    flags.first().cloned().unwrap_or_default(),
+                ]);
+                // every other flag goes on its own line
+                for flag in flags.iter().skip(1) {
+                    let mut row = vec![String::new(); 5];
+                    row.push(flag.clone());
+                    table.push_row(row);
+                }
+            }
+            if table.is_empty() {
+                String::from("no recurring payments found")
+            } else {
+                table.render()
+            }
+        }
+    };
+
+    std::io::stdout().write_all(output.as_bytes())?;
+    println!();
+    Ok(())
+}
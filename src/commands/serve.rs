@@ -0,0 +1,464 @@
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::io::Read;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use warp::http::StatusCode;
+use warp::Filter;
+
+use crate::types::filter;
+use crate::types::*;
+use crate::*;
+
+/// The port that `serve` listens on by default, `auth` uses `consts::PORT`
+const DEFAULT_PORT: u16 = 3001;
+
+/// Transactions are fetched from the API at most this often for each account, anything more
+/// recent is served from the cache
+const CACHE_SECONDS: u64 = 60;
+
+/// Everything the request handlers share
+pub(crate) struct ServeState {
+    pub user: user::User,
+    pub client: reqwest::Client,
+    pub config: config::Config,
+    /// When the transactions of each account were last fetched
+    pub synced: HashMap<String, std::time::Instant>,
+    /// The last response to `/metrics` and when it was made, so that scrapes don't each
+    /// fetch balances
+    pub metrics: Option<(std::time::Instant, String)>,
+}
+
+pub(crate) type SharedState = Arc<Mutex<ServeState>>;
+
+impl ServeState {
+    /// Finds the account for an `account` query parameter, defaults to the first open retail
+    /// account
+    fn account_index(&self, selector: &Option<String>) -> Result<usize, ApiError> {
+        match selector {
+            Some(selector) => {
+                util::resolve_account(&self.user.accounts, &self.config.accounts, selector)
+                    .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.0))
+            }
+            None => util::default_account(&self.user.accounts).ok_or_else(|| {
+                ApiError::new(StatusCode::NOT_FOUND, "there are no accounts".to_owned())
+            }),
+        }
+    }
+
+    /// Brings the cached transactions of an account up to date, unless they were fetched
+    /// recently. If the API can't be reached the cache is used as it is
+    pub async fn sync_transactions(&mut self, account_index: usize) {
+        let account_id = self.user.accounts[account_index].id.clone();
+        if self
+            .synced
+            .get(&account_id)
+            .is_some_and(|synced| synced.elapsed().as_secs() < CACHE_SECONDS)
+        {
+            return;
+        }
+        let since = self.user.resync_since(&account_id);
+        let fetched =
+            commands::get_transactions(&self.user, &self.client, Some(since), None, account_index)
+                .await
+                .map_err(|e| e.to_string());
+        match fetched {
+            Ok(mut transactions) => {
+                // the rules were checked when the server started
+                if let Ok(rules) = rules::Rules::new(&self.config.rules) {
+                    for transaction in &mut transactions {
+                        rules.apply(transaction);
+                    }
+                }
+                let _ = user_file::merge_into_user_file(&mut self.user, &transactions);
+                self.synced.insert(account_id, std::time::Instant::now());
+            }
+            Err(e) => eprintln!("could not fetch transactions, using the cache -- {}", e),
+        }
+    }
+}
+
+/// An error response, sent as `{"error": "..."}`
+#[derive(Debug)]
+pub(crate) struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: String) -> Self {
+        Self { status, message }
+    }
+
+    fn reply(self) -> warp::reply::WithStatus<warp::reply::Json> {
+        #[derive(Serialize)]
+        struct Error {
+            error: String,
+        }
+        warp::reply::with_status(
+            warp::reply::json(&Error {
+                error: self.message,
+            }),
+            self.status,
+        )
+    }
+}
+
+/// Turns the result of a handler into a response
+pub(crate) fn reply<T: serde::Serialize>(
+    result: Result<T, ApiError>,
+) -> warp::reply::WithStatus<warp::reply::Json> {
+    match result {
+        Ok(value) => warp::reply::with_status(warp::reply::json(&value), StatusCode::OK),
+        Err(e) => e.reply(),
+    }
+}
+
+/// The query parameters accepted by every route
+#[derive(Deserialize, Debug, Default)]
+pub(crate) struct Query {
+    account: Option<String>,
+    since: Option<String>,
+    before: Option<String>,
+    /// A filter expression, see `types::filter::Filter`
+    q: Option<String>,
+}
+
+impl Query {
+    fn time(s: &Option<String>) -> Result<Option<time::Time>, ApiError> {
+        s.as_deref()
+            .map(time::Time::try_parse_str)
+            .transpose()
+            .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.0))
+    }
+
+    /// The cached transactions of an account that match the query, oldest first
+    pub(crate) fn transactions<'a>(
+        &self,
+        state: &'a ServeState,
+        account_index: usize,
+    ) -> Result<Vec<&'a Transaction>, ApiError> {
+        let since = Self::time(&self.since)?;
+        let before = Self::time(&self.before)?;
+        let filter = filter::Filter::parse(self.q.as_deref().unwrap_or(""))
+            .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.0))?;
+        Ok(state
+            .user
+            .cached_transactions(&state.user.accounts[account_index].id)
+            .into_iter()
+            .filter(|transaction| {
+                since
+                    .as_ref()
+                    .is_none_or(|since| &transaction.created >= since)
+            })
+            .filter(|transaction| {
+                before
+                    .as_ref()
+                    .is_none_or(|before| &transaction.created < before)
+            })
+            .filter(|transaction| filter.matches(transaction))
+            .collect())
+    }
+}
+
+/// Money in and out over a range of transactions
+#[derive(Serialize, Debug)]
+pub(crate) struct Summary {
+    account_id: String,
+    currency: String,
+    transactions: usize,
+    declined: usize,
+    income: i64,
+    spending: i64,
+    net: i64,
+    /// Spending for each category, transactions with no category are `uncategorised`
+    categories: BTreeMap<String, i64>,
+}
+
+pub(crate) fn summarise(account: &Account, transactions: &[&Transaction]) -> Summary {
+    let mut summary = Summary {
+        account_id: account.id.clone(),
+        currency: account.currency.clone(),
+        transactions: 0,
+        declined: 0,
+        income: 0,
+        spending: 0,
+        net: 0,
+        categories: BTreeMap::new(),
+    };
+    for transaction in transactions {
+        if transaction.decline_reason.is_some() {
+            summary.declined += 1;
+            continue;
+        }
+        let amount = transaction.amount as i64;
+        summary.transactions += 1;
+        summary.net += amount;
+        if amount > 0 {
+            summary.income += amount;
+        } else {
+            summary.spending += amount;
+            let category = match transaction.category() {
+                Some(category) => category.to_owned(),
+                None => "uncategorised".to_owned(),
+            };
+            *summary.categories.entry(category).or_default() += amount;
+        }
+    }
+    summary
+}
+
+/// Balances and pots of every open account, or of the account in `query`. The last known
+/// balances are used when Monzo can't be reached
+async fn balances(state: SharedState, query: Query) -> Result<Vec<serde_json::Value>, ApiError> {
+    let mut state = state.lock().await;
+    let state = &mut *state;
+    let account_indices: Vec<usize> = match &query.account {
+        Some(_) => vec![state.account_index(&query.account)?],
+        None => state
+            .user
+            .accounts
+            .iter()
+            .enumerate()
+            .filter(|(_, account)| !account.closed)
+            .map(|(i, _)| i)
+            .collect(),
+    };
+    let balances =
+        commands::refresh_all_balances(&mut state.user, &state.client, false, &account_indices)
+            .await
+            .map_err(|e| ApiError::new(StatusCode::BAD_GATEWAY, e.to_string()))?;
+    let _ = user_file::merge_into_user_file(&mut state.user, &[]);
+    Ok(account_indices
+        .iter()
+        .zip(balances)
+        .map(|(account_index, (balance, pots))| {
+            serde_json::json!({
+                "account_id": state.user.accounts[*account_index].id,
+                "balance": balance,
+                "pots": pots.into_iter().filter(|pot| !pot.deleted).collect::<Vec<_>>(),
+            })
+        })
+        .collect())
+}
+
+async fn transactions(state: SharedState, query: Query) -> Result<Vec<Transaction>, ApiError> {
+    let mut state = state.lock().await;
+    let account_index = state.account_index(&query.account)?;
+    state.sync_transactions(account_index).await;
+    Ok(query
+        .transactions(&state, account_index)?
+        .into_iter()
+        .cloned()
+        .collect())
+}
+
+async fn summary(state: SharedState, query: Query) -> Result<Summary, ApiError> {
+    let mut state = state.lock().await;
+    let account_index = state.account_index(&query.account)?;
+    state.sync_transactions(account_index).await;
+    let transactions = query.transactions(&state, account_index)?;
+    Ok(summarise(
+        &state.user.accounts[account_index],
+        &transactions,
+    ))
+}
+
+/// Every balance as Prometheus metrics, along with the spending in the cached transactions.
+/// The last known balances are used when Monzo can't be reached
+async fn metrics(state: SharedState) -> Result<String, ApiError> {
+    let mut state = state.lock().await;
+    if let Some((made, metrics)) = &state.metrics {
+        if made.elapsed().as_secs() < CACHE_SECONDS {
+            return Ok(metrics.clone());
+        }
+    }
+
+    let account_indices: Vec<usize> = state
+        .user
+        .accounts
+        .iter()
+        .enumerate()
+        .filter(|(_, account)| !account.closed)
+        .map(|(i, _)| i)
+        .collect();
+    for account_index in &account_indices {
+        state.sync_transactions(*account_index).await;
+    }
+    let state = &mut *state;
+    let balances =
+        commands::refresh_all_balances(&mut state.user, &state.client, false, &account_indices)
+            .await
+            .map_err(|e| ApiError::new(StatusCode::BAD_GATEWAY, e.to_string()))?;
+    let _ = user_file::merge_into_user_file(&mut state.user, &[]);
+    let accounts: Vec<commands::AccountMetrics> = account_indices
+        .iter()
+        .zip(&balances)
+        .map(|(account_index, (balance, pots))| {
+            let account = &state.user.accounts[*account_index];
+            commands::AccountMetrics {
+                account,
+                balance,
+                pots,
+                transactions: state.user.cached_transactions(&account.id),
+            }
+        })
+        .collect();
+    let metrics = commands::render_metrics(&accounts);
+    state.metrics = Some((std::time::Instant::now(), metrics.clone()));
+    Ok(metrics)
+}
+
+/// Makes a token for clients to authenticate with, from the system's random number generator
+fn generate_token() -> std::io::Result<String> {
+    let mut bytes = [0u8; 24];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Rejects requests that don't have the token, either as a bearer token or as `?token=` for
+/// clients that can't set headers (e.g. spreadsheets)
+pub(crate) fn authorized(
+    token: String,
+) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(warp::query::<HashMap<String, String>>())
+        .and_then(
+            move |header: Option<String>, query: HashMap<String, String>| {
+                let token = token.clone();
+                async move {
+                    let bearer = header
+                        .as_deref()
+                        .and_then(|header| header.strip_prefix("Bearer "));
+                    if bearer == Some(token.as_str()) || query.get("token") == Some(&token) {
+                        Ok(())
+                    } else {
+                        Err(warp::reject::custom(Unauthorized))
+                    }
+                }
+            },
+        )
+        .untuple_one()
+}
+
+#[derive(Debug)]
+struct Unauthorized;
+
+impl warp::reject::Reject for Unauthorized {}
+
+async fn handle_rejection(
+    rejection: warp::Rejection,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, std::convert::Infallible> {
+    Ok(if rejection.find::<Unauthorized>().is_some() {
+        ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "a valid token is needed, use `Authorization: Bearer <token>` or `?token=<token>`"
+                .to_owned(),
+        )
+        .reply()
+    } else if rejection.is_not_found() {
+        ApiError::new(StatusCode::NOT_FOUND, "not found".to_owned()).reply()
+    } else {
+        ApiError::new(StatusCode::BAD_REQUEST, format!("{:?}", rejection)).reply()
+    })
+}
+
+/// The `serve` command, serves the user's data as json on localhost
+pub async fn serve(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let port = match command.uint_kwarg::<u64>("port") {
+        Some(port) => u16::try_from(port?)
+            .map_err(|_| error::BadArgumentError("`port` must be less than 65536".to_owned()))?,
+        None => DEFAULT_PORT,
+    };
+    if user.serve_token.is_none() || command.args_set.contains("--new-token") {
+        user.serve_token = Some(generate_token()?);
+        user_file::update_user_file(user)?;
+    }
+    let token = user.serve_token.clone().unwrap_or_default();
+
+    let config = config_file::load_config_file()?;
+    let mut served = user.clone();
+    // the rules may have changed since the transactions were cached
+    let rules = rules::Rules::new(&config.rules)?;
+    for transaction in &mut served.transactions {
+        rules.apply(transaction);
+    }
+
+    let state: SharedState = Arc::new(Mutex::new(ServeState {
+        user: served,
+        client: client.clone(),
+        config,
+        synced: HashMap::new(),
+        metrics: None,
+    }));
+    let with_state = {
+        let state = state.clone();
+        warp::any().map(move || state.clone())
+    };
+    let query = warp::query::<Query>();
+
+    let accounts =
+        warp::path!("accounts")
+            .and(with_state.clone())
+            .then(|state: SharedState| async move {
+                reply(Ok::<_, ApiError>(state.lock().await.user.accounts.clone()))
+            });
+    let balance = warp::path!("balance")
+        .and(with_state.clone())
+        .and(query)
+        .then(|state, query| async move { reply(balances(state, query).await) });
+    let transactions_route = warp::path!("transactions")
+        .and(with_state.clone())
+        .and(query)
+        .then(|state, query| async move { reply(transactions(state, query).await) });
+    let summary_route = warp::path!("summary")
+        .and(with_state.clone())
+        .and(query)
+        .then(|state, query| async move { reply(summary(state, query).await) });
+    let metrics_route = warp::path!("metrics")
+        .and(with_state.clone())
+        .then(|state| async move {
+            match metrics(state).await {
+                Ok(metrics) => Box::new(warp::reply::with_header(
+                    metrics,
+                    "content-type",
+                    "text/plain; version=0.0.4",
+                )) as Box<dyn warp::Reply>,
+                Err(e) => Box::new(e.reply()),
+            }
+        });
+
+    let routes = warp::get()
+        .and(authorized(token.clone()))
+        .and(
+            accounts
+                .or(balance)
+                .unify()
+                .or(transactions_route)
+                .unify()
+                .or(summary_route)
+                .unify()
+                .map(|reply| Box::new(reply) as Box<dyn warp::Reply>)
+                .or(metrics_route)
+                .unify(),
+        )
+        .recover(handle_rejection);
+
+    println!("serving on http://localhost:{}", port);
+    println!("token: {}", token);
+    warp::serve(routes)
+        .bind_with_graceful_shutdown((consts::IP, port), async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .1
+        .await;
+
+    // keep the transactions that were fetched while serving
+    *user = state.lock().await.user.clone();
+    Ok(())
+}
@@ -0,0 +1,265 @@
+use crate::cli::table::{Align, Table};
+use crate::types::split::{Settlement, Split, SplitBalance};
+use crate::types::*;
+use crate::*;
+
+/// Parses `<person>` or `<person>:<amount>`, amounts are in minor currency units
+fn parse_share(arg: &str) -> Result<(String, Option<i32>), error::BadArgumentError> {
+    match arg.split_once(':') {
+        Some((person, amount)) => match amount.parse::<i32>() {
+            Ok(amount) => Ok((person.to_lowercase(), Some(amount))),
+            Err(_) => Err(error::BadArgumentError(format!(
+                "`{}` is not a valid share, amounts are in minor currency units e.g. `sam:1250`",
+                arg
+            ))),
+        },
+        None => Ok((arg.to_lowercase(), None)),
+    }
+}
+
+/// Finds a transaction in the cache, fetching it if it isn't there
+fn find_transaction(
+    user: &user::User,
+    client: &reqwest::Client,
+    transaction_id: &str,
+) -> Result<Transaction, Box<dyn std::error::Error>> {
+    match user
+        .transactions
+        .iter()
+        .find(|transaction| transaction.id == transaction_id)
+    {
+        Some(transaction) => Ok(transaction.clone()),
+        None => pollster::block_on(commands::get_transaction(client, transaction_id)),
+    }
+}
+
+/// Fetches recent transactions and records any transfers that settle what people owe
+fn sync_settlements(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<Vec<Settlement>, Box<dyn std::error::Error>> {
+    let (since, before) = util::get_time_range(user, command)?;
+    let account_index = util::get_account_index(user, command)?;
+    if !commands::is_offline(command) {
+        match pollster::block_on(commands::get_transactions(
+            user,
+            client,
+            since,
+            before,
+            account_index,
+        )) {
+            Ok(transactions) => user.cache_transactions(&transactions),
+            Err(e) => println!(
+                "could not fetch transactions, using cached transactions -- {}",
+                e
+            ),
+        }
+    }
+    // people are always lower case in the ledger
+    let people = config_file::load_config_file()?
+        .people
+        .into_iter()
+        .map(|(name, person)| (name.to_lowercase(), person))
+        .collect();
+    let transactions = user.transactions.clone();
+    Ok(user.splits.settle(&transactions, &people))
+}
+
+fn fmt_amount(amount: i32, currency: &str) -> String {
+    format!("{} {}", util::fmt_decimal(amount, currency), currency)
+}
+
+fn print_balances(
+    balances: &[SplitBalance],
+    output_type: OutputType,
+    user: &user::User,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let output = match output_type {
+        OutputType::Json => {
+            #[derive(Serialize)]
+            struct Summary<'a> {
+                balances: &'a [SplitBalance],
+                splits: Vec<&'a Split>,
+                settlements: &'a [Settlement],
+            }
+            serde_json::to_string_pretty(&Summary {
+                balances,
+                splits: user.splits.splits.values().collect(),
+                settlements: &user.splits.settlements,
+            })?
+        }
+        OutputType::Csv => util::serde_csv::records_to_string(balances)?,
+        OutputType::Display => {
+            if balances.is_empty() {
+                println!("nothing has been split, use `split add <transaction_id> <person>...`");
+                return Ok(());
+            }
+            let mut table = Table::new()
+                .column("PERSON", Align::Left)
+                .column("SHARED", Align::Right)
+                .column("SETTLED", Align::Right)
+                .column("OUTSTANDING", Align::Right);
+            for balance in balances {
+                table.push_row(vec![
+                    balance.person.clone(),
+                    fmt_amount(balance.shared, &balance.currency),
+                    fmt_amount(balance.settled, &balance.currency),
+                    fmt_amount(balance.outstanding, &balance.currency),
+                ]);
+            }
+            let mut output = table.render();
+            output.push('\n');
+            for balance in balances {
+                output.push('\n');
+                output.push_str(&match balance.outstanding {
+                    0 => format!("{} is settled up", balance.person),
+                    outstanding if outstanding > 0 => format!(
+                        "{} owes you {}",
+                        balance.person,
+                        fmt_amount(outstanding, &balance.currency)
+                    ),
+                    outstanding => format!(
+                        "you owe {} {}",
+                        balance.person,
+                        fmt_amount(-outstanding, &balance.currency)
+                    ),
+                });
+            }
+            output
+        }
+        OutputType::Ofx | OutputType::Qif | OutputType::Ledger | OutputType::Beancount => {
+            return Err(error::InvalidArgumentError(
+                "statement formats are only supported by `transactions`".to_owned(),
+            )
+            .into())
+        }
+    };
+    println!("{}", output);
+    Ok(())
+}
+
+/// The `split` command, shares transactions with other people and keeps track of who owes
+/// what
+pub fn split(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let args = command.positional_args();
+    match args.as_slice() {
+        ["add", transaction_id, people @ ..] => {
+            let transaction = find_transaction(user, client, transaction_id)?;
+            if transaction.decline_reason.is_some() {
+                return Err(
+                    error::BadArgumentError(format!("{} was declined", transaction_id)).into(),
+                );
+            }
+            let people = people
+                .iter()
+                .map(|arg| parse_share(arg))
+                .collect::<Result<Vec<_>, _>>()?;
+            let split = Split::new(
+                &transaction,
+                &people,
+                !command.args_set.contains("--not-me"),
+            )?;
+            for (person, share) in &split.shares {
+                println!(
+                    "{} owes {} for {}",
+                    person,
+                    fmt_amount(*share, &split.currency),
+                    split.description
+                );
+            }
+            user.splits
+                .splits
+                .insert(split.transaction_id.clone(), split);
+            user_file::update_user_file(user)?;
+        }
+        ["remove", transaction_id] => {
+            if user.splits.splits.remove(*transaction_id).is_none() {
+                return Err(error::BadArgumentError(format!(
+                    "{} hasn't been split",
+                    transaction_id
+                ))
+                .into());
+            }
+            user_file::update_user_file(user)?;
+        }
+        ["settle", person, amount] => {
+            let amount = amount.parse::<i32>().map_err(|_| {
+                error::BadArgumentError(format!(
+                    "`{}` is not a valid amount, amounts are in minor currency units",
+                    amount
+                ))
+            })?;
+            user.splits.settlements.push(Settlement {
+                transaction_id: None,
+                person: person.to_lowercase(),
+                created: time::Time::now(),
+                currency: command
+                    .kwargs
+                    .get("currency")
+                    .map_or("GBP", String::as_str)
+                    .to_uppercase(),
+                amount,
+            });
+            user_file::update_user_file(user)?;
+            print_balances(&user.splits.balances(), OutputType::Display, user)?;
+        }
+        ["list"] => {
+            let mut table = Table::new()
+                .column("TIME", Align::Left)
+                .column("PAYEE", Align::Left)
+                .column("AMOUNT", Align::Right)
+                .column("SHARES", Align::Left);
+            for split in user.splits.splits.values() {
+                table.push_row(vec![
+                    split.created.to_string(),
+                    split.description.clone(),
+                    fmt_amount(split.amount, &split.currency),
+                    split
+                        .shares
+                        .iter()
+                        .map(|(person, share)| {
+                            format!("{} {}", person, util::fmt_decimal(*share, &split.currency))
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ]);
+            }
+            if table.is_empty() {
+                println!("nothing has been split");
+            } else {
+                println!("{}", table.render());
+            }
+        }
+        [] | ["balance"] => {
+            let output_type = util::get_output_type(command)?;
+            let settlements = sync_settlements(user, client, command)?;
+            user_file::update_user_file(user)?;
+            if let OutputType::Display = output_type {
+                for settlement in &settlements {
+                    println!(
+                        "{} paid back {} on {}",
+                        settlement.person,
+                        fmt_amount(settlement.amount, &settlement.currency),
+                        settlement.created
+                    );
+                }
+            }
+            print_balances(&user.splits.balances(), output_type, user)?;
+        }
+        _ => {
+            return Err(error::BadArgumentError(
+                "use `split add <transaction_id> <person>[:<amount>]...`, `split remove \
+                <transaction_id>`, `split settle <person> <amount>`, `split list` or `split \
+                balance`"
+                    .to_owned(),
+            )
+            .into())
+        }
+    }
+    Ok(())
+}
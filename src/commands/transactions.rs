@@ -1,6 +1,6 @@
 use std::io::Write;
 
-use crate::types::error::AuthorizationError;
+use crate::cli::table::{Align, Table};
 use crate::types::*;
 use crate::util::FmtCurrencyOptions;
 use crate::*;
@@ -22,66 +22,291 @@ pub async fn get_transactions(
         before: Option<time::Time>,
     }
 
-    let transactions = client
-        .get(&format!(
-            "{}/transactions?{}",
+    let account = &user.accounts[account_index];
+    let transactions = if account.import_source().is_some() {
+        // statements from other banks are imported into the cache, there is nothing to fetch
+        user.cached_transactions_between(&account.id, since.as_ref(), before.as_ref())
+            .into_iter()
+            .cloned()
+            .collect()
+    } else {
+        let transactions = client
+            .get(&format!(
+                "{}/transactions?{}",
+                consts::MONZO_API,
+                serde_qs::to_string(&QueryString {
+                    account_id: &account.id,
+                    expand: "merchant",
+                    since,
+                    before,
+                })
+                .unwrap()
+            ))
+            .send()
+            .await?
+            .bytes()
+            .await?;
+
+        #[derive(Deserialize)]
+        struct Transactions {
+            transactions: Vec<Transaction>,
+        }
+        serde_json::from_slice::<Transactions>(&transactions)?.transactions
+    };
+    Ok(transactions)
+}
+
+/// Sets the notes on a transaction
+pub async fn update_transaction_notes(
+    client: &reqwest::Client,
+    transaction_id: &str,
+    notes: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    #[derive(Serialize)]
+    struct Form<'a> {
+        #[serde(rename = "metadata[notes]")]
+        notes: &'a str,
+    }
+
+    client
+        .patch(&format!(
+            "{}/transactions/{}",
             consts::MONZO_API,
-            serde_qs::to_string(&QueryString {
-                account_id: &user.accounts[account_index].id,
-                expand: "merchant",
-                since,
-                before,
-            })
-            .unwrap()
+            transaction_id
         ))
+        .form(&Form { notes })
         .send()
         .await?
-        .bytes()
-        .await?;
+        .error_for_status()?;
+    Ok(())
+}
 
-    #[derive(Deserialize)]
-    struct Transactions {
-        transactions: Vec<Transaction>,
+/// Every detail of a transaction as a list of labels and values, labels of values that
+/// belong to the label above are indented
+pub fn transaction_details(transaction: &Transaction) -> Vec<(String, String)> {
+    let mut details = vec![];
+    let mut row = |key: &str, value: String| details.push((key.to_owned(), value));
+    row(
+        "ACCOUNT_BALANCE",
+        util::unwrap_to_string(&transaction.account_balance, "NULL"),
+    );
+    row("AMOUNT", transaction.amount.to_string());
+    if transaction.amount_is_pending {
+        row("  PENDING", "the amount may still change".to_owned());
+    }
+    row("TIME", transaction.created.to_string());
+    row(
+        "SETTLED",
+        match &transaction.settled {
+            Some(settled) => settled.to_string(),
+            None if transaction.is_pending() => "pending".to_owned(),
+            None => "NULL".to_owned(),
+        },
+    );
+    row("CURRENCY", transaction.currency.clone());
+    if let Some((local_amount, local_currency)) = transaction.foreign_amount() {
+        row(
+            "LOCAL AMOUNT",
+            format!(
+                "{} {}",
+                util::fmt_decimal(local_amount, local_currency),
+                local_currency
+            ),
+        );
+    }
+    row("DESCRIPTION", format!("\"{}\"", transaction.description));
+    match &transaction.merchant {
+        Some(merchant) => {
+            row("MERCHANT", String::new());
+            row("  ADDRESS", merchant.address.address.clone());
+            row("", merchant.address.postcode.clone());
+            row("", merchant.address.city.clone());
+            row("  EMOJI", merchant.emoji.clone());
+            row("  NAME", merchant.name.clone());
+            row("  CATEGORY", merchant.category.clone());
+        }
+        None => row("MERCHANT", "NULL".to_owned()),
     }
-    let transactions: Transactions = serde_json::from_slice(&transactions)?;
-    Ok(transactions.transactions)
+    if let Some(category) = &transaction.custom_category {
+        row("CATEGORY", category.clone());
+    }
+    if !transaction.tags.is_empty() {
+        row("TAGS", transaction.tags.join(", "));
+    }
+    if let Some(counterparty) = &transaction.counterparty {
+        row("COUNTERPARTY", String::new());
+        row("  NAME", util::unwrap_to_string(&counterparty.name, "NULL"));
+        row(
+            "  ACCOUNT NUMBER",
+            util::unwrap_to_string(&counterparty.account_number, "NULL"),
+        );
+        row(
+            "  SORT CODE",
+            util::unwrap_to_string(&counterparty.sort_code, "NULL"),
+        );
+    }
+    if let Some(decline_reason) = &transaction.decline_reason {
+        row("DECLINED", decline_reason.clone());
+    }
+    if !transaction.metadata.is_empty() {
+        row("METADATA", String::new());
+        let mut metadata: Vec<_> = transaction.metadata.iter().collect();
+        metadata.sort();
+        for (key, value) in metadata {
+            row(&format!("  {}", key), value.clone());
+        }
+    }
+    row("ID", transaction.id.clone());
+    details
+}
+
+/// A transaction with only the fields shown in the minimal display
+#[derive(Serialize)]
+struct MinimalTransaction<'a> {
+    amount: i32,
+    currency: &'a str,
+    local_amount: Option<i32>,
+    local_currency: Option<&'a str>,
+    pending: bool,
+    notes: String,
+}
+
+impl<'a> MinimalTransaction<'a> {
+    fn new(transaction: &'a Transaction) -> Self {
+        MinimalTransaction {
+            amount: transaction.amount,
+            currency: &transaction.currency,
+            local_amount: transaction.foreign_amount().map(|(amount, _)| amount),
+            local_currency: transaction.foreign_amount().map(|(_, currency)| currency),
+            pending: transaction.is_pending(),
+            notes: if let Some(merchant) = &transaction.merchant {
+                format!("at '{}'", merchant.name)
+            } else if let Some(counterparty) = &transaction.counterparty {
+                let mut s = counterparty.name.clone().unwrap_or("".to_owned());
+                if let Some(notes) = transaction.metadata.get("notes") {
+                    s.extend([" \"", notes, "\""]);
+                }
+                s
+            } else {
+                String::from("no notes")
+            },
+        }
+    }
+}
+
+/// The transactions as csv, one line for each transaction
+pub fn transactions_to_csv(transactions: &[Transaction]) -> Result<String, std::io::Error> {
+    #[derive(Serialize)]
+    struct TransactionRecord<'a> {
+        id: &'a str,
+        time: &'a time::Time,
+        payee: String,
+        category: &'a str,
+        tags: String,
+        notes: &'a str,
+        amount: String,
+        currency: &'a str,
+        local_amount: String,
+        local_currency: &'a str,
+        pending: bool,
+        decline_reason: &'a str,
+    }
+
+    util::serde_csv::records_to_string(transactions.iter().map(|transaction| {
+        let foreign_amount = transaction.foreign_amount();
+        TransactionRecord {
+            id: &transaction.id,
+            time: &transaction.created,
+            payee: transaction.payee_name(),
+            category: transaction.category().unwrap_or(""),
+            tags: transaction.tags.join(" "),
+            notes: transaction.metadata.get("notes").map_or("", String::as_str),
+            amount: util::fmt_decimal(transaction.amount, &transaction.currency),
+            currency: &transaction.currency,
+            local_amount: foreign_amount.map_or(String::new(), |(amount, currency)| {
+                util::fmt_decimal(amount, currency)
+            }),
+            local_currency: foreign_amount.map_or("", |(_, currency)| currency),
+            pending: transaction.is_pending(),
+            decline_reason: transaction.decline_reason.as_deref().unwrap_or(""),
+        }
+    }))
+}
+
+fn declined(s: &str) -> String {
+    cli::AnsiStringBuilder::new()
+        .set_style(&cli::theme().declined)
+        .push_str(s)
+        .build()
+}
+
+fn pending(s: &str) -> String {
+    cli::AnsiStringBuilder::new()
+        .set_style(&cli::theme().pending)
+        .push_str(s)
+        .build()
 }
 
 pub fn transactions(
-    user: &user::User,
+    user: &mut user::User,
     client: &reqwest::Client,
     command: &cli::Command,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let now = time::Time::now();
-    let before = if let Some(time_str) = command.kwargs.get("before") {
-        Some(time::Time::try_parse_str(time_str)?)
-    } else {
-        None
-    };
-    let earliest_since = now.add(&(chrono::Duration::days(-90) + chrono::Duration::seconds(100)));
-    let since = if let Some(time_str) = command.kwargs.get("since") {
-        let since = time::Time::try_parse_str(time_str)?;
-        if since < earliest_since {
-            if user.access_token.created < now.add(&chrono::Duration::seconds(-60 * 4 - 30)) {
-                return Err(AuthorizationError::Custom(
-                    "cannot access more than 90d of transactions, unles the user has been authorized \
-                    in the last 5 minutes\nrun `auth` and try again".to_owned()
-                ).into());
-            }
-        }
-        Some(since)
-    } else {
-        Some(earliest_since)
-    };
+    let (since, before) = util::get_time_range(user, command)?;
     let account_index = util::get_account_index(user, command)?;
-    let output_type = if let Some(output_type_str) = command.kwargs.get("format") {
-        types::OutputType::from_str(output_type_str)?
-    } else {
-        types::OutputType::Display
-    };
+    let output_type = util::get_output_type(command)?;
+    let filter = util::get_filter(command)?;
+    let template = util::get_template(command)?;
 
-    let transactions =
-        pollster::block_on(get_transactions(user, client, since, before, account_index))?;
+    let mut transactions =
+        commands::load_transactions(user, client, command, since, before, account_index)?;
+    transactions.retain(|transaction| filter.matches(transaction));
+
+    // these formats are a whole statement, or a table, rather than a list of transactions. The
+    // balances only add up when no transactions are filtered out
+    let account = &user.accounts[account_index];
+    let filtered = command.kwargs.contains_key("filter");
+    let include_pending = command.args_set.contains("--pending");
+    match output_type {
+        OutputType::Ofx => {
+            let ofx = export::to_ofx(account, &transactions, include_pending);
+            std::io::stdout().write_all(ofx.as_bytes())?;
+            return Ok(());
+        }
+        OutputType::Qif => {
+            let qif = export::to_qif(account, &transactions, include_pending);
+            std::io::stdout().write_all(qif.as_bytes())?;
+            return Ok(());
+        }
+        OutputType::Ledger => {
+            let config = config_file::load_config_file()?;
+            let ledger = export::to_ledger(
+                account,
+                &transactions,
+                &config.ledger_for(account),
+                !filtered,
+            );
+            std::io::stdout().write_all(ledger.as_bytes())?;
+            return Ok(());
+        }
+        OutputType::Beancount => {
+            let config = config_file::load_config_file()?;
+            let beancount = export::to_beancount(
+                account,
+                &transactions,
+                &config.ledger_for(account),
+                !filtered,
+            );
+            std::io::stdout().write_all(beancount.as_bytes())?;
+            return Ok(());
+        }
+        OutputType::Csv => {
+            let csv = transactions_to_csv(&transactions)?;
+            std::io::stdout().write_all(csv.as_bytes())?;
+            return Ok(());
+        }
+        _ => {}
+    }
 
     let detailed = command.args_set.contains("--detailed") || command.args_set.contains("-d");
 
@@ -95,112 +320,90 @@ pub fn transactions(
         match output_type {
             OutputType::Display => display(serializable),
             OutputType::Json => Ok(serde_json::to_string_pretty(serializable)?),
-            OutputType::Csv => Ok(util::serde_csv::to_string(serializable)?),
+            OutputType::Csv
+            | OutputType::Ofx
+            | OutputType::Qif
+            | OutputType::Ledger
+            | OutputType::Beancount => unreachable!(),
         }
     }
     let fmt_transaction = |transaction: &Transaction| -> Result<String> {
-        if detailed {
+        if let Some(template) = &template {
             fmt_serializable(&transaction, output_type, |transaction| {
-                Ok(format!(
-                    "\
-                    ACCOUNT_BALANCE : {}\n\
-                    AMOUNT          : {}\n\
-                    TIME            : {}\n\
-                    CURRENCY        : {}\n\
-                    DESCRIPTION     : \"{}\"\n\
-                    MERCHANT        : {}\
-                    ",
-                    util::unwrap_to_string(&transaction.account_balance, "NULL"),
-                    transaction.amount,
-                    transaction.created,
-                    transaction.currency,
-                    transaction.description,
-                    match &transaction.merchant {
-                        Some(merchant) => format!(
-                            "\n\t\t\
-                                ADDRESS  : {}\n\t\t\
-                                EMOJI    : {}\n\t\t\
-                                NAME     : {}\n\t\t\
-                                CATEGORY : {}\
-                                ",
-                            {
-                                format!(
-                                    "\n\t\t\t  \
-                                        {}\n\t\t\t  \
-                                        {}\n\t\t\t  \
-                                        {}\
-                                    ",
-                                    merchant.address.address,
-                                    merchant.address.postcode,
-                                    merchant.address.city,
-                                )
-                            },
-                            merchant.emoji,
-                            merchant.name,
-                            merchant.category,
-                        ),
-                        None => "NULL".to_string(),
-                    }
-                ))
+                Ok(template.render(transaction))
+            })
+        } else if detailed {
+            fmt_serializable(&transaction, output_type, |transaction| {
+                let mut table = Table::new().column("", Align::Left).column("", Align::Left);
+                for (key, value) in transaction_details(transaction) {
+                    table.push_row(vec![key, value]);
+                }
+                Ok(table.render() + "\n")
             })
         } else {
-            #[derive(Serialize)]
-            struct MinimalTransaction<'a> {
-                amount: i32,
-                currency: &'a str,
-                notes: String,
-            }
+            // the display is a table of every transaction, which is rendered separately
+            fmt_serializable(
+                &MinimalTransaction::new(transaction),
+                output_type,
+                |_| unreachable!(),
+            )
+        }
+    };
 
+    if let (OutputType::Display, None, false) = (output_type, &template, detailed) {
+        let mut table = Table::new()
+            .column("TIME", Align::Left)
+            .column("AMOUNT", Align::Right)
+            .column("NOTES", Align::Left);
+        for transaction in &transactions {
             let transaction_was_declined = transaction.decline_reason.is_some();
-            let transaction = MinimalTransaction {
-                amount: transaction.amount,
-                currency: &transaction.currency,
-                notes: if let Some(merchant) = &transaction.merchant {
-                    format!("at '{}'", merchant.name)
+            let minimal_transaction = MinimalTransaction::new(transaction);
+            let style = |s: &str| {
+                if transaction_was_declined {
+                    declined(s)
+                } else if minimal_transaction.pending {
+                    pending(s)
                 } else {
-                    if let Some(counterparty) = &transaction.counterparty {
-                        let mut s = counterparty.name.clone().unwrap_or("".to_owned());
-                        if let Some(notes) = transaction.metadata.get("notes") {
-                            s.extend([" \"", notes, "\""]);
-                        }
-                        s
-                    } else {
-                        String::from("no notes")
-                    }
-                },
+                    s.to_owned()
+                }
             };
-
-            fn strikethrough(s: &str) -> String {
-                cli::AnsiStringBuilder::new()
-                    .set_strikethrough(true)
-                    .push_str(s)
-                    .build()
+            let mut amount = util::fmt_currency(
+                minimal_transaction.amount,
+                minimal_transaction.currency,
+                &FmtCurrencyOptions {
+                    include_positive_sign: true,
+                    colored: true,
+                },
+            );
+            // the amount keeps its colour, only what is written beside it is styled
+            if let (Some(local_amount), Some(local_currency)) = (
+                minimal_transaction.local_amount,
+                minimal_transaction.local_currency,
+            ) {
+                amount += &style(&format!(
+                    " ({} {})",
+                    util::fmt_decimal(local_amount, local_currency),
+                    local_currency
+                ));
             }
-            fmt_serializable(&transaction, output_type, |transaction| {
-                let s = format!(
-                    "{} {}",
-                    util::fmt_currency(
-                        transaction.amount,
-                        transaction.currency,
-                        &FmtCurrencyOptions {
-                            include_positive_sign: true,
-                            colored: true,
-                        }
-                    ),
-                    if transaction_was_declined {
-                        strikethrough(&transaction.notes)
-                    } else {
-                        transaction.notes.clone()
-                    }
-                );
-                Ok(if transaction_was_declined {
-                    strikethrough(&s)
+            let mut notes = minimal_transaction.notes.clone();
+            if minimal_transaction.pending {
+                notes += " (pending)";
+            }
+            table.push_row(vec![
+                style(&transaction.created.to_string()),
+                if transaction_was_declined {
+                    declined(&amount)
                 } else {
-                    s
-                })
-            })
+                    amount
+                },
+                style(&notes),
+            ]);
         }
-    };
+        std::io::stdout().write_all(table.render().as_bytes())?;
+        println!();
+        return Ok(());
+    }
 
     let mut output = vec![];
     for transaction in transactions.iter() {
@@ -208,8 +411,12 @@ pub fn transactions(
     }
     let output = match output_type {
         OutputType::Json => format!("[\n{}\n]", output.join(",\n")),
-        OutputType::Csv => unimplemented!(),
         OutputType::Display => output.join("\n"),
+        OutputType::Csv
+        | OutputType::Ofx
+        | OutputType::Qif
+        | OutputType::Ledger
+        | OutputType::Beancount => unreachable!(),
     };
 
     std::io::stdout().write(&output.as_bytes())?;
@@ -0,0 +1,457 @@
+use std::io::Write;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, style, terminal, QueueableCommand};
+
+use crate::types::filter::Filter;
+use crate::types::*;
+use crate::util::FmtCurrencyOptions;
+use crate::*;
+
+/// The keys shown at the bottom of the screen
+const HELP: &str = "↑/↓ move  / filter  n notes  tab account  r refresh  q quit";
+
+/// What typing does at the moment
+enum Mode {
+    Browse,
+    Filter,
+    Notes(String),
+}
+
+struct Tui<'a> {
+    user: &'a mut user::User,
+    client: &'a reqwest::Client,
+    since: Option<time::Time>,
+    before: Option<time::Time>,
+    account_index: usize,
+    /// The indices of the accounts that can be switched between, see `commands::shown_accounts`
+    accounts: Vec<usize>,
+    /// Only the cached transactions are shown and nothing is sent to Monzo
+    offline: bool,
+    /// The rules from the config, for the categories and tags of fetched transactions
+    rules: rules::Rules<'a>,
+    /// Ids of the transactions that match the filter, newest first
+    visible: Vec<String>,
+    selected: usize,
+    scroll: usize,
+    filter: Filter,
+    filter_str: String,
+    input: String,
+    mode: Mode,
+    status: String,
+}
+
+/// Puts the terminal back to normal when dropped, so that errors and panics don't leave the
+/// terminal in raw mode
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> std::io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut stdout = std::io::stdout();
+        stdout.queue(terminal::EnterAlternateScreen)?;
+        stdout.queue(cursor::Hide)?;
+        stdout.flush()?;
+        Ok(TerminalGuard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let mut stdout = std::io::stdout();
+        let _ = stdout.queue(cursor::Show);
+        let _ = stdout.queue(terminal::LeaveAlternateScreen);
+        let _ = stdout.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Pads or cuts `s` to exactly `width` visible characters
+pub(crate) fn fit(s: &str, width: usize) -> String {
+    let visible_width = cli::visible_width(s);
+    if width == 0 {
+        String::new()
+    } else if visible_width > width {
+        cli::truncate_ansi(s, width.saturating_sub(1)) + "…"
+    } else {
+        s.to_owned() + &" ".repeat(width - visible_width)
+    }
+}
+
+impl<'a> Tui<'a> {
+    fn account(&self) -> &Account {
+        &self.user.accounts[self.account_index]
+    }
+
+    fn selected_transaction(&self) -> Option<&Transaction> {
+        let id = self.visible.get(self.selected)?;
+        self.user
+            .transactions
+            .iter()
+            .find(|transaction| &transaction.id == id)
+    }
+
+    /// Fetches the transactions for the current account and adds them to the cache, if this
+    /// fails then the cached transactions are still shown
+    fn refresh(&mut self) {
+        if self.offline {
+            self.status = "STALE: offline, showing cached transactions".to_owned();
+            self.update_visible();
+            return;
+        }
+        let fetched = pollster::block_on(commands::get_transactions(
+            self.user,
+            self.client,
+            self.since.clone(),
+            self.before.clone(),
+            self.account_index,
+        ));
+        self.status = match fetched {
+            Ok(mut transactions) => {
+                for transaction in &mut transactions {
+                    self.rules.apply(transaction);
+                }
+                self.user.cache_transactions(&transactions);
+                match user_file::update_user_file(self.user) {
+                    Ok(()) => format!("fetched {} transactions", transactions.len()),
+                    Err(e) => format!("could not update the cache -- {}", e),
+                }
+            }
+            Err(e) => format!("showing cached transactions, could not fetch -- {}", e),
+        };
+        self.update_visible();
+    }
+
+    fn update_visible(&mut self) {
+        let selected_id = self.visible.get(self.selected).cloned();
+        let account_id = self.account().id.clone();
+        self.visible = self
+            .user
+            .cached_transactions(&account_id)
+            .into_iter()
+            .rev()
+            .filter(|transaction| self.filter.matches(transaction))
+            .map(|transaction| transaction.id.clone())
+            .collect();
+        // try to stay on the same transaction
+        self.selected = selected_id
+            .and_then(|id| self.visible.iter().position(|visible| visible == &id))
+            .unwrap_or(0);
+    }
+
+    fn switch_account(&mut self, forwards: bool) {
+        let accounts = self.accounts.len();
+        let position = self
+            .accounts
+            .iter()
+            .position(|&i| i == self.account_index)
+            .unwrap_or(0);
+        let position = if forwards {
+            (position + 1) % accounts
+        } else {
+            (position + accounts - 1) % accounts
+        };
+        self.account_index = self.accounts[position];
+        self.selected = 0;
+        self.scroll = 0;
+        self.visible.clear();
+        self.refresh();
+    }
+
+    fn save_notes(&mut self, notes: String) {
+        let id = match self.visible.get(self.selected) {
+            Some(id) => id.clone(),
+            None => return,
+        };
+        if self.offline {
+            self.status = "notes can't be saved offline".to_owned();
+            return;
+        }
+        let result =
+            pollster::block_on(commands::update_transaction_notes(self.client, &id, &notes));
+        self.status = match result {
+            Ok(()) => {
+                if let Some(transaction) = self.user.transactions.iter_mut().find(|t| t.id == id) {
+                    transaction.metadata.insert("notes".to_owned(), notes);
+                }
+                let _ = user_file::update_user_file(self.user);
+                "saved notes".to_owned()
+            }
+            Err(e) => format!("could not save notes -- {}", e),
+        };
+        self.update_visible();
+    }
+
+    /// Handles a key press, returns false when the tui should close
+    fn handle_key(&mut self, key: KeyEvent, list_height: usize) -> bool {
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return false,
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return false
+                }
+                KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => self.selected += 1,
+                KeyCode::PageUp => self.selected = self.selected.saturating_sub(list_height),
+                KeyCode::PageDown => self.selected += list_height,
+                KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+                KeyCode::End | KeyCode::Char('G') => self.selected = usize::MAX,
+                KeyCode::Tab | KeyCode::Char('a') => self.switch_account(true),
+                KeyCode::BackTab | KeyCode::Char('A') => self.switch_account(false),
+                KeyCode::Char('r') => self.refresh(),
+                KeyCode::Char('/') => {
+                    self.input = self.filter_str.clone();
+                    self.mode = Mode::Filter;
+                }
+                KeyCode::Char('n') => {
+                    if let Some(transaction) = self.selected_transaction() {
+                        let id = transaction.id.clone();
+                        self.input = transaction
+                            .metadata
+                            .get("notes")
+                            .cloned()
+                            .unwrap_or_default();
+                        self.mode = Mode::Notes(id);
+                    }
+                }
+                _ => {}
+            },
+            Mode::Filter => match key.code {
+                KeyCode::Enter | KeyCode::Esc => {}
+                code => {
+                    self.mode = Mode::Filter;
+                    if self.edit_input(code) {
+                        // filter as the user types, keeping the last filter that worked
+                        match Filter::parse(&self.input) {
+                            Ok(filter) => {
+                                self.filter = filter;
+                                self.filter_str = self.input.clone();
+                                self.status.clear();
+                                self.update_visible();
+                            }
+                            Err(e) => self.status = e.to_string(),
+                        }
+                    }
+                }
+            },
+            Mode::Notes(id) => match key.code {
+                KeyCode::Esc => {}
+                KeyCode::Enter => {
+                    if self.visible.get(self.selected) == Some(&id) {
+                        self.save_notes(self.input.clone());
+                    }
+                }
+                code => {
+                    self.edit_input(code);
+                    self.mode = Mode::Notes(id);
+                }
+            },
+        }
+        self.selected = std::cmp::min(self.selected, self.visible.len().saturating_sub(1));
+        true
+    }
+
+    /// Edits the input line, returns true if it changed
+    fn edit_input(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => return self.input.pop().is_some(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn draw(&mut self, width: usize, height: usize) -> std::io::Result<()> {
+        let list_height = height.saturating_sub(3);
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if self.selected >= self.scroll + list_height {
+            self.scroll = self.selected + 1 - list_height;
+        }
+        let list_width = std::cmp::max(width * 11 / 20, std::cmp::min(width, 40));
+        let details_width = width.saturating_sub(list_width + 3);
+
+        let mut lines = vec![];
+        let account = self.account();
+        lines.push(
+            cli::AnsiStringBuilder::new()
+                .set_bold(true)
+                .push_str(&fit(
+                    &format!(
+                        " [{}] {} {} -- {} of {} transactions{}",
+                        self.account_index,
+                        account.account_number,
+                        account.sort_code,
+                        self.visible.len(),
+                        self.user.cached_transactions(&account.id).len(),
+                        if self.filter_str.is_empty() {
+                            String::new()
+                        } else {
+                            format!(" matching `{}`", self.filter_str)
+                        }
+                    ),
+                    width,
+                ))
+                .build(),
+        );
+
+        let details = self
+            .selected_transaction()
+            .map(commands::transaction_details)
+            .unwrap_or_default();
+        let label_width = details
+            .iter()
+            .map(|(key, _)| key.chars().count())
+            .max()
+            .unwrap_or(0);
+        for row in 0..list_height + 1 {
+            let list_line = match self.visible.get(self.scroll + row) {
+                Some(id) if row < list_height => {
+                    let transaction = self
+                        .user
+                        .transactions
+                        .iter()
+                        .find(|transaction| &transaction.id == id)
+                        .unwrap();
+                    let amount = |colored| {
+                        util::fmt_currency(
+                            transaction.amount,
+                            &transaction.currency,
+                            &FmtCurrencyOptions {
+                                include_positive_sign: true,
+                                colored,
+                            },
+                        )
+                    };
+                    // pad before colouring, since the escape codes would count towards the width
+                    let padding = " ".repeat(10usize.saturating_sub(amount(false).chars().count()));
+                    let line = fit(
+                        &format!(
+                            "{} {}{} {}",
+                            transaction.created.date_time().format("%d %b %y"),
+                            padding,
+                            amount(true),
+                            transaction.payee_name()
+                        ),
+                        list_width,
+                    );
+                    let mut builder = cli::AnsiStringBuilder::new();
+                    if transaction.decline_reason.is_some() {
+                        builder = builder.set_style(&cli::theme().declined);
+                    } else if transaction.is_pending() {
+                        builder = builder.set_style(&cli::theme().pending);
+                    }
+                    if self.scroll + row == self.selected {
+                        format!(
+                            "{}{}{}",
+                            style::Attribute::Reverse,
+                            cli::strip_ansi(&line),
+                            style::Attribute::Reset
+                        )
+                    } else if transaction.decline_reason.is_some() || transaction.is_pending() {
+                        builder.push_str(&cli::strip_ansi(&line)).build()
+                    } else {
+                        line
+                    }
+                }
+                _ => " ".repeat(list_width),
+            };
+            let details_line = match details.get(row) {
+                Some((key, value)) => fit(
+                    &format!("{:width$}  {}", key, value, width = label_width),
+                    details_width,
+                ),
+                None => String::new(),
+            };
+            if row < list_height {
+                lines.push(format!("{} │ {}", list_line, details_line));
+            }
+        }
+
+        lines.push(match &self.mode {
+            Mode::Browse => fit(
+                if self.status.is_empty() {
+                    HELP
+                } else {
+                    &self.status
+                },
+                width,
+            ),
+            Mode::Filter => fit(&format!("filter: {}▏ {}", self.input, self.status), width),
+            Mode::Notes(_) => fit(&format!("notes: {}▏", self.input), width),
+        });
+
+        let mut stdout = std::io::stdout();
+        stdout.queue(terminal::Clear(terminal::ClearType::All))?;
+        for (i, line) in lines.iter().enumerate() {
+            stdout.queue(cursor::MoveTo(0, i as u16))?;
+            stdout.queue(style::Print(line))?;
+        }
+        stdout.flush()
+    }
+}
+
+/// The `tui` command, a full screen transaction browser
+pub fn tui(
+    user: &mut user::User,
+    client: &reqwest::Client,
+    command: &cli::Command,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (since, before) = util::get_time_range(user, command)?;
+    let account_index = util::get_account_index(user, command)?;
+    // an account chosen with `account=` can be switched back to, even if it is closed
+    let mut accounts: Vec<usize> = commands::shown_accounts(user, command)
+        .into_iter()
+        .map(|(i, _)| i)
+        .collect();
+    if !accounts.contains(&account_index) {
+        accounts.push(account_index);
+        accounts.sort_unstable();
+    }
+    let filter_str = command.kwargs.get("filter").cloned().unwrap_or_default();
+    let filter = Filter::parse(&filter_str)?;
+    let config = config_file::load_config_file()?;
+    let rules = rules::Rules::new(&config.rules)?;
+    // the rules may have changed since the transactions were cached
+    for transaction in &mut user.transactions {
+        rules.apply(transaction);
+    }
+
+    let mut tui = Tui {
+        user,
+        client,
+        since,
+        before,
+        account_index,
+        accounts,
+        offline: commands::is_offline(command),
+        rules,
+        visible: vec![],
+        selected: 0,
+        scroll: 0,
+        filter,
+        filter_str,
+        input: String::new(),
+        mode: Mode::Browse,
+        status: String::new(),
+    };
+    let _guard = TerminalGuard::new()?;
+    tui.update_visible();
+    let (width, height) = terminal::size()?;
+    tui.status = "fetching transactions...".to_owned();
+    tui.draw(width as usize, height as usize)?;
+    tui.refresh();
+
+    loop {
+        let (width, height) = terminal::size()?;
+        tui.draw(width as usize, height as usize)?;
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        if !tui.handle_key(key, (height as usize).saturating_sub(3)) {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use crate::*;
+use std::{fs, io::Read};
+
+fn get_config_file_path() -> std::io::Result<std::path::PathBuf> {
+    let mut config_file_path = std::env::current_exe()?;
+    config_file_path.pop();
+    config_file_path.push("config.json");
+    Ok(config_file_path)
+}
+
+/// Gets the config in the config.json file, or the default config if there is no config.json
+pub fn load_config_file() -> std::io::Result<types::config::Config> {
+    let mut buf = Vec::new();
+    match fs::File::open(get_config_file_path()?) {
+        Ok(mut config_file) => config_file.read_to_end(&mut buf)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Default::default()),
+        Err(e) => return Err(e),
+    };
+    serde_json::from_slice(&buf).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("config.json has a bad format -- {}", e),
+        )
+    })
+}
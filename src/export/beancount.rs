@@ -0,0 +1,97 @@
+use std::collections::BTreeSet;
+
+use crate::types::config::LedgerConfig;
+use crate::types::*;
+use crate::*;
+
+/// Escapes a string for use inside double quotes
+fn quote(s: &str) -> String {
+    format!(
+        "\"{}\"",
+        s.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', " ")
+    )
+}
+
+/// Creates a beancount file for `transactions`, with one entry per transaction. The monzo id
+/// is kept as `monzo_id` metadata. If `balances` is true, as for `to_ledger`, the balance
+/// before the transactions is the opening balance and the balance at the end of each day is
+/// asserted at the start of the next (which is when beancount checks balances)
+pub fn to_beancount(
+    account: &Account,
+    transactions: &[Transaction],
+    config: &LedgerConfig,
+    balances: bool,
+) -> String {
+    let transactions = super::ledger::sorted_postings(transactions);
+    let amount = |amount: i32| {
+        format!(
+            "{} {}",
+            util::fmt_decimal(amount, &account.currency),
+            account.currency
+        )
+    };
+
+    let opening_balance = super::ledger::opening_balance(&transactions).filter(|_| balances);
+    let mut accounts = BTreeSet::new();
+    accounts.insert(config.account.clone());
+    if opening_balance.is_some() {
+        accounts.insert(config.opening_balances.clone());
+    }
+    for transaction in &transactions {
+        accounts.insert(config.account_for(transaction));
+    }
+    let opened = account.created.date_time().format("%Y-%m-%d");
+    let mut beancount = String::new();
+    for open in &accounts {
+        beancount.push_str(&format!("{} open {}\n", opened, open));
+    }
+    beancount.push('\n');
+
+    if let Some(opening_balance) = opening_balance {
+        beancount.push_str(&format!(
+            "{} * \"Opening balance\"\n  {}  {}\n  {}\n\n",
+            transactions[0].created.date_time().format("%Y-%m-%d"),
+            config.account,
+            amount(opening_balance),
+            config.opening_balances,
+        ));
+    }
+    for (i, transaction) in transactions.iter().enumerate() {
+        let date = transaction.created.date_time().date();
+        beancount.push_str(&format!(
+            "{} {} {} {}\n  monzo_id: {}\n",
+            date.format("%Y-%m-%d"),
+            super::ledger::flag(transaction),
+            quote(&transaction.payee_name()),
+            quote(
+                transaction
+                    .metadata
+                    .get("notes")
+                    .unwrap_or(&transaction.description)
+            ),
+            quote(&transaction.id),
+        ));
+        beancount.push_str(&format!(
+            "  {}  {}\n  {}  {}\n\n",
+            config.account_for(transaction),
+            amount(-transaction.amount),
+            config.account,
+            amount(transaction.amount),
+        ));
+
+        let last_of_day = transactions
+            .get(i + 1)
+            .is_none_or(|next| next.created.date_time().date() != date);
+        if let (true, true, Some(balance)) = (balances, last_of_day, transaction.account_balance) {
+            beancount.push_str(&format!(
+                "{} balance {}  {}\n\n",
+                date.succ_opt().unwrap_or(date).format("%Y-%m-%d"),
+                config.account,
+                amount(balance),
+            ));
+        }
+    }
+    beancount
+}
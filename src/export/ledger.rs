@@ -0,0 +1,91 @@
+use crate::types::config::LedgerConfig;
+use crate::types::*;
+use crate::*;
+
+/// Sorts transactions by time then id so that the output is the same every time, declined
+/// transactions are left out
+pub(crate) fn sorted_postings(transactions: &[Transaction]) -> Vec<&Transaction> {
+    let mut transactions: Vec<&Transaction> = transactions
+        .iter()
+        .filter(|transaction| transaction.decline_reason.is_none())
+        .collect();
+    transactions.sort_by(|a, b| {
+        a.created
+            .partial_cmp(&b.created)
+            .unwrap()
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    transactions
+}
+
+/// The balance before the first of `transactions`, if it has a balance
+pub(crate) fn opening_balance(transactions: &[&Transaction]) -> Option<i32> {
+    let first = transactions.first()?;
+    Some(first.account_balance? - first.amount)
+}
+
+/// The flag of an entry, pending transactions may still change so they aren't cleared
+pub(crate) fn flag(transaction: &Transaction) -> char {
+    if transaction.is_pending() {
+        '!'
+    } else {
+        '*'
+    }
+}
+
+/// Creates a ledger (or hledger) journal for `transactions`, with one entry per transaction.
+/// The monzo id is kept as `monzo_id` metadata. If `balances` is true, `transactions` are all
+/// of the account's transactions over some time, so the balance before them is the opening
+/// balance and the running account balance is asserted on every posting
+pub fn to_ledger(
+    account: &Account,
+    transactions: &[Transaction],
+    config: &LedgerConfig,
+    balances: bool,
+) -> String {
+    let transactions = sorted_postings(transactions);
+    let amount = |amount: i32| {
+        format!(
+            "{} {}",
+            util::fmt_decimal(amount, &account.currency),
+            account.currency
+        )
+    };
+    let mut ledger = String::new();
+    if let (true, Some(opening_balance)) = (balances, opening_balance(&transactions)) {
+        ledger.push_str(&format!(
+            "{} * Opening balance\n    {}  {}\n    {}\n\n",
+            transactions[0].created.date_time().format("%Y/%m/%d"),
+            config.account,
+            amount(opening_balance),
+            config.opening_balances,
+        ));
+    }
+    for transaction in transactions {
+        ledger.push_str(&format!(
+            "{} {} {}\n    ; monzo_id: {}\n",
+            transaction.created.date_time().format("%Y/%m/%d"),
+            flag(transaction),
+            transaction.payee_name().replace('\n', " "),
+            transaction.id,
+        ));
+        if let Some(notes) = transaction.metadata.get("notes").filter(|n| !n.is_empty()) {
+            ledger.push_str(&format!("    ; {}\n", notes.replace('\n', " ")));
+        }
+        ledger.push_str(&format!(
+            "    {}  {}\n",
+            config.account_for(transaction),
+            amount(-transaction.amount)
+        ));
+        ledger.push_str(&format!(
+            "    {}  {}{}\n\n",
+            config.account,
+            amount(transaction.amount),
+            match transaction.account_balance {
+                Some(balance) if balances => format!(" = {}", amount(balance)),
+                _ => String::new(),
+            }
+        ));
+    }
+    ledger
+}
@@ -0,0 +1,8 @@
+mod ofx;
+pub use ofx::*;
+mod qif;
+pub use qif::*;
+mod ledger;
+pub use ledger::*;
+mod beancount;
+pub use beancount::*;
@@ -0,0 +1,127 @@
+use crate::types::*;
+use crate::*;
+
+/// OFX 1.x limits `NAME` to 32 characters
+const MAX_NAME_LENGTH: usize = 32;
+
+/// The format OFX uses for dates
+const OFX_TIME_FMT: &str = "%Y%m%d%H%M%S";
+
+/// Escapes the characters that aren't allowed in OFX element content, an element's content
+/// ends with its line so newlines become spaces
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\n', " ")
+}
+
+fn fmt_time(time: &time::Time) -> String {
+    time.date_time().format(OFX_TIME_FMT).to_string()
+}
+
+/// Creates an OFX bank statement for `transactions`, declined transactions are left out. Each
+/// transaction's `FITID` is its monzo id so that importing the same transactions twice doesn't
+/// duplicate them. Importers skip a `FITID` they have seen, so pending transactions are left
+/// out too (a later import wouldn't pick up the settled amount) unless `include_pending` is true
+pub fn to_ofx(account: &Account, transactions: &[Transaction], include_pending: bool) -> String {
+    let transactions: Vec<&Transaction> = transactions
+        .iter()
+        .filter(|transaction| transaction.decline_reason.is_none())
+        .filter(|transaction| include_pending || !transaction.is_pending())
+        .collect();
+    let now = time::Time::now();
+    let start = transactions
+        .first()
+        .map_or_else(|| now.clone(), |transaction| transaction.created.clone());
+    let end = transactions
+        .last()
+        .map_or_else(|| now.clone(), |transaction| transaction.created.clone());
+
+    let mut ofx = format!(
+        "OFXHEADER:100\n\
+        DATA:OFXSGML\n\
+        VERSION:102\n\
+        SECURITY:NONE\n\
+        ENCODING:UTF-8\n\
+        CHARSET:NONE\n\
+        COMPRESSION:NONE\n\
+        OLDFILEUID:NONE\n\
+        NEWFILEUID:NONE\n\
+        \n\
+        <OFX>\n\
+        <SIGNONMSGSRSV1><SONRS>\n\
+        <STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\n\
+        <DTSERVER>{}</DTSERVER>\n\
+        <LANGUAGE>ENG</LANGUAGE>\n\
+        </SONRS></SIGNONMSGSRSV1>\n\
+        <BANKMSGSRSV1><STMTTRNRS>\n\
+        <TRNUID>1</TRNUID>\n\
+        <STATUS><CODE>0</CODE><SEVERITY>INFO</SEVERITY></STATUS>\n\
+        <STMTRS>\n\
+        <CURDEF>{}</CURDEF>\n\
+        <BANKACCTFROM>\n\
+        <BANKID>{}</BANKID>\n\
+        <ACCTID>{}</ACCTID>\n\
+        <ACCTTYPE>CHECKING</ACCTTYPE>\n\
+        </BANKACCTFROM>\n\
+        <BANKTRANLIST>\n\
+        <DTSTART>{}</DTSTART>\n\
+        <DTEND>{}</DTEND>\n",
+        fmt_time(&now),
+        account.currency,
+        escape(&account.sort_code),
+        escape(&account.account_number),
+        fmt_time(&start),
+        fmt_time(&end),
+    );
+
+    for transaction in &transactions {
+        let name = transaction.payee_name();
+        let name: String = name.chars().take(MAX_NAME_LENGTH).collect();
+        ofx.push_str(&format!(
+            "<STMTTRN>\n\
+            <TRNTYPE>{}</TRNTYPE>\n\
+            <DTPOSTED>{}</DTPOSTED>\n\
+            <TRNAMT>{}</TRNAMT>\n\
+            <FITID>{}</FITID>\n\
+            <NAME>{}</NAME>\n",
+            if transaction.amount < 0 {
+                "DEBIT"
+            } else {
+                "CREDIT"
+            },
+            fmt_time(&transaction.created),
+            util::fmt_decimal(transaction.amount, &account.currency),
+            escape(&transaction.id),
+            escape(&name),
+        ));
+        if let Some(notes) = transaction.metadata.get("notes").filter(|n| !n.is_empty()) {
+            ofx.push_str(&format!("<MEMO>{}</MEMO>\n", escape(notes)));
+        }
+        ofx.push_str("</STMTTRN>\n");
+    }
+    ofx.push_str("</BANKTRANLIST>\n");
+
+    // the running balance after the latest transaction is the closing balance
+    if let Some((balance, time)) = transactions.iter().rev().find_map(|transaction| {
+        transaction
+            .account_balance
+            .map(|balance| (balance, &transaction.created))
+    }) {
+        ofx.push_str(&format!(
+            "<LEDGERBAL>\n\
+            <BALAMT>{}</BALAMT>\n\
+            <DTASOF>{}</DTASOF>\n\
+            </LEDGERBAL>\n",
+            util::fmt_decimal(balance, &account.currency),
+            fmt_time(time),
+        ));
+    }
+    ofx.push_str(
+        "</STMTRS>\n\
+        </STMTTRNRS></BANKMSGSRSV1>\n\
+        </OFX>\n",
+    );
+    ofx
+}
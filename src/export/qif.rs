@@ -0,0 +1,31 @@
+use crate::types::*;
+use crate::*;
+
+/// Creates a QIF bank statement for `transactions`, declined transactions are left out. QIF
+/// has no unique transaction id, so the monzo id is used as the check number (`N`), which is
+/// what most importers use to detect duplicates. For the same reason pending transactions are
+/// left out unless `include_pending` is true, as for `to_ofx`
+pub fn to_qif(account: &Account, transactions: &[Transaction], include_pending: bool) -> String {
+    let mut qif = String::from("!Type:Bank\n");
+    for transaction in transactions {
+        if transaction.decline_reason.is_some() || (!include_pending && transaction.is_pending()) {
+            continue;
+        }
+        let payee = transaction.payee_name();
+        qif.push_str(&format!(
+            "D{}\n\
+            T{}\n\
+            N{}\n\
+            P{}\n",
+            transaction.created.date_time().format("%Y-%m-%d"),
+            util::fmt_decimal(transaction.amount, &account.currency),
+            transaction.id,
+            payee.replace('\n', " "),
+        ));
+        if let Some(notes) = transaction.metadata.get("notes").filter(|n| !n.is_empty()) {
+            qif.push_str(&format!("M{}\n", notes.replace('\n', " ")));
+        }
+        qif.push_str("^\n");
+    }
+    qif
+}
@@ -0,0 +1,118 @@
+use std::convert::TryFrom;
+
+use crate::types::config::ImportSource;
+use crate::types::error::BadArgumentError;
+use crate::types::*;
+
+use super::{parse_amount, statement_transaction, LineIds};
+
+/// Parses a date in `format`, or as `%Y-%m-%d`, as midnight on that day
+fn parse_date(s: &str, format: &str) -> Option<time::Time> {
+    let s = s.trim();
+    let date = chrono::NaiveDate::parse_from_str(s, format)
+        .or_else(|_| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d"))
+        .ok()?;
+    Some(date.and_time(chrono::NaiveTime::MIN).into())
+}
+
+/// Reads a csv statement using the columns of `source`
+pub fn parse_delimited(
+    name: &str,
+    source: &ImportSource,
+    contents: &str,
+) -> Result<Vec<Transaction>, BadArgumentError> {
+    let delimiter = u8::try_from(source.delimiter).map_err(|_| {
+        BadArgumentError(format!(
+            "the delimiter of the `{}` import must be a single byte",
+            name
+        ))
+    })?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(contents.trim_start_matches('\u{feff}').as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| BadArgumentError(format!("could not read the header row -- {}", e)))?
+        .clone();
+    let column = |column: &str| {
+        headers
+            .iter()
+            .position(|header| header.eq_ignore_ascii_case(column))
+            .ok_or_else(|| {
+                BadArgumentError(format!(
+                    "there is no `{}` column, the columns are {}",
+                    column,
+                    headers.iter().collect::<Vec<_>>().join(", ")
+                ))
+            })
+    };
+    let optional_column =
+        |column_name: &Option<String>| column_name.as_deref().map(column).transpose();
+
+    let date = column(&source.date)?;
+    let description = column(&source.description)?;
+    let balance = optional_column(&source.balance)?;
+    let (amount, debit, credit) = match (&source.amount, &source.debit, &source.credit) {
+        (None, None, None) => (Some(column("Amount")?), None, None),
+        (amount, debit, credit) => (
+            optional_column(amount)?,
+            optional_column(debit)?,
+            optional_column(credit)?,
+        ),
+    };
+
+    let mut ids = LineIds::new(name);
+    let mut transactions = vec![];
+    for (i, record) in reader.records().enumerate() {
+        // the header is line 1
+        let line = i + 2;
+        let record = record
+            .map_err(|e| BadArgumentError(format!("could not read line {} -- {}", line, e)))?;
+        let field = |column: usize| record.get(column).unwrap_or("");
+        let amount_of = |column: Option<usize>| -> Result<i32, BadArgumentError> {
+            match column.map(field) {
+                Some(value) if !value.is_empty() => parse_amount(value, &source.currency)
+                    .ok_or_else(|| {
+                        BadArgumentError(format!("`{}` on line {} is not an amount", value, line))
+                    }),
+                _ => Ok(0),
+            }
+        };
+        // blank lines and footers such as `Total` have no date
+        let created = match parse_date(field(date), &source.date_format) {
+            Some(created) => created,
+            None if field(date).is_empty() => continue,
+            None => {
+                return Err(BadArgumentError(format!(
+                    "`{}` on line {} is not a date in the format `{}`",
+                    field(date),
+                    line,
+                    source.date_format
+                )))
+            }
+        };
+        let mut amount = amount_of(amount)?;
+        if source.negate {
+            amount = -amount;
+        }
+        // debits are sometimes written with a minus sign and sometimes without
+        amount += amount_of(credit)?.abs() - amount_of(debit)?.abs();
+        let balance = match balance {
+            Some(column) if !field(column).is_empty() => Some(amount_of(balance)?),
+            _ => None,
+        };
+        let description = field(description).to_owned();
+        transactions.push(statement_transaction(
+            name,
+            ids.next(&created, &description, amount),
+            created,
+            description,
+            amount,
+            &source.currency,
+            balance,
+        ));
+    }
+    Ok(transactions)
+}
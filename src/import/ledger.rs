@@ -0,0 +1,223 @@
+use crate::types::error::BadArgumentError;
+use crate::types::*;
+
+use super::{parse_amount, statement_transaction, LineIds};
+
+/// Beancount directives that aren't transactions
+const DIRECTIVES: &[&str] = &[
+    "open",
+    "close",
+    "commodity",
+    "pad",
+    "note",
+    "document",
+    "price",
+    "event",
+    "query",
+    "custom",
+];
+
+/// The amount and currency of a posting or balance assertion such as `-12.50 GBP` or `£12.50`
+fn parse_posting_amount(s: &str, default_currency: &str) -> Option<(i32, String)> {
+    let currency = s
+        .split_whitespace()
+        .find(|token| token.len() == 3 && token.chars().all(|c| c.is_ascii_uppercase()))
+        .unwrap_or(default_currency);
+    let number = s
+        .split_whitespace()
+        .find(|token| token.chars().any(|c| c.is_ascii_digit()))?;
+    Some((parse_amount(number, currency)?, currency.to_owned()))
+}
+
+/// A posting in the entry being read, `amount` is `None` when ledger is left to work it out
+struct Posting {
+    account: String,
+    amount: Option<(i32, String)>,
+    balance: Option<i32>,
+}
+
+/// An entry being read, it becomes a transaction once all of its postings have been read
+struct Entry {
+    created: time::Time,
+    description: String,
+    id: Option<String>,
+    postings: Vec<Posting>,
+}
+
+impl Entry {
+    /// The transaction for the postings to `account`, if there are any
+    fn into_transaction(
+        self,
+        name: &str,
+        account: &str,
+        default_currency: &str,
+        ids: &mut LineIds,
+    ) -> Option<Transaction> {
+        let known: i32 = self
+            .postings
+            .iter()
+            .filter_map(|posting| posting.amount.as_ref().map(|(amount, _)| amount))
+            .sum();
+        let mut amount = 0;
+        let mut currency = default_currency.to_owned();
+        let mut balance = None;
+        let mut posted = false;
+        for posting in &self.postings {
+            if !posting.account.eq_ignore_ascii_case(account) {
+                continue;
+            }
+            posted = true;
+            match &posting.amount {
+                Some((posting_amount, posting_currency)) => {
+                    amount += posting_amount;
+                    currency = posting_currency.clone();
+                }
+                // the amount that balances the entry
+                None => amount -= known,
+            }
+            balance = posting.balance.or(balance);
+        }
+        if !posted {
+            return None;
+        }
+        let id = match self.id {
+            Some(id) => id,
+            None => ids.next(&self.created, &self.description, amount),
+        };
+        Some(statement_transaction(
+            name,
+            id,
+            self.created,
+            self.description,
+            amount,
+            &currency,
+            balance,
+        ))
+    }
+}
+
+/// Reads the postings to `account` in a ledger, hledger or beancount journal, such as the
+/// `ledger` and `beancount` exports of `transactions`. Entries exported from Monzo keep their
+/// `monzo_id`, and balance assertions become the account balance
+pub fn parse_ledger(
+    name: &str,
+    contents: &str,
+    account: &str,
+    default_currency: &str,
+) -> Result<Vec<Transaction>, BadArgumentError> {
+    let mut ids = LineIds::new(name);
+    let mut transactions: Vec<Transaction> = vec![];
+    let mut entry: Option<Entry> = None;
+
+    for (i, line) in contents.lines().enumerate() {
+        let line_number = i + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if line.starts_with(char::is_whitespace) {
+            let entry = match &mut entry {
+                Some(entry) => entry,
+                None => continue,
+            };
+            let comment = trimmed.trim_start_matches(';').trim();
+            if let Some(id) = comment.strip_prefix("monzo_id:") {
+                entry.id = Some(id.trim().trim_matches('"').to_owned());
+                continue;
+            }
+            if trimmed.starts_with(';') || trimmed.starts_with('#') {
+                continue;
+            }
+            // `Assets:Monzo  -12.50 GBP = 100.00 GBP`, the account ends at two spaces or a tab
+            let (posting_account, rest) = match trimmed.find("  ").or_else(|| trimmed.find('\t')) {
+                Some(end) => (&trimmed[..end], trimmed[end..].trim()),
+                None => (trimmed, ""),
+            };
+            // beancount metadata such as `category: "groceries"`
+            if posting_account.ends_with(':') || posting_account.contains(": ") {
+                continue;
+            }
+            let rest = rest.split(';').next().unwrap_or("");
+            let (amount, balance) = match rest.split_once('=') {
+                Some((amount, balance)) => (amount, Some(balance)),
+                None => (rest, None),
+            };
+            let parse = |s: &str| {
+                parse_posting_amount(s, default_currency).ok_or_else(|| {
+                    BadArgumentError(format!(
+                        "`{}` on line {} is not an amount",
+                        s.trim(),
+                        line_number
+                    ))
+                })
+            };
+            entry.postings.push(Posting {
+                account: posting_account.to_owned(),
+                amount: match amount.trim() {
+                    "" => None,
+                    amount => Some(parse(amount)?),
+                },
+                balance: balance.map(parse).transpose()?.map(|(balance, _)| balance),
+            });
+            continue;
+        }
+
+        if let Some(entry) = entry.take() {
+            transactions.extend(entry.into_transaction(name, account, default_currency, &mut ids));
+        }
+        let (date, rest) = trimmed
+            .split_once(char::is_whitespace)
+            .unwrap_or((trimmed, ""));
+        let created = match chrono::NaiveDate::parse_from_str(date, "%Y/%m/%d")
+            .or_else(|_| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+        {
+            Ok(date) => time::Time::from(date.and_time(chrono::NaiveTime::MIN)),
+            // `account` declarations, `include`s, options and so on
+            Err(_) => continue,
+        };
+        let rest = rest.trim();
+        let (keyword, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        if keyword == "balance" {
+            // `2022-01-04 balance Assets:Monzo  95.00 GBP` is the balance at the start of the
+            // day, which is the balance after the last transaction before it
+            let (balance_account, amount) = after
+                .trim()
+                .split_once(char::is_whitespace)
+                .unwrap_or((after, ""));
+            if balance_account.eq_ignore_ascii_case(account) {
+                let last = transactions
+                    .iter_mut()
+                    .rev()
+                    .find(|transaction| transaction.created < created);
+                if let (Some(last), Some((balance, _))) =
+                    (last, parse_posting_amount(amount, default_currency))
+                {
+                    last.account_balance = Some(balance);
+                }
+            }
+            continue;
+        }
+        if DIRECTIVES.contains(&keyword) {
+            continue;
+        }
+        let description = match keyword {
+            "*" | "!" | "txn" => after.trim(),
+            _ => rest,
+        };
+        // beancount quotes the payee and narration, the payee is used
+        let description = match description.strip_prefix('"') {
+            Some(quoted) => quoted.split('"').next().unwrap_or(""),
+            None => description.split(';').next().unwrap_or("").trim(),
+        };
+        entry = Some(Entry {
+            created,
+            description: description.to_owned(),
+            id: None,
+            postings: vec![],
+        });
+    }
+    if let Some(entry) = entry {
+        transactions.extend(entry.into_transaction(name, account, default_currency, &mut ids));
+    }
+    Ok(transactions)
+}
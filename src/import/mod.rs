@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+
+use crate::types::config::{ImportSource, StatementFormat};
+use crate::types::error::BadArgumentError;
+use crate::types::*;
+use crate::*;
+
+mod delimited;
+pub use delimited::*;
+mod ledger;
+pub use ledger::*;
+mod ofx;
+pub use ofx::*;
+
+/// The id of the account that the statements of the import called `name` are imported into
+pub fn import_account_id(name: &str) -> String {
+    format!("{}{}", IMPORT_ACCOUNT_PREFIX, name)
+}
+
+/// The account that the statements of the import called `name` are imported into, accounts
+/// at other banks are listed alongside the Monzo accounts
+pub fn import_account(name: &str, source: &ImportSource, currency: &str) -> Account {
+    Account {
+        id: import_account_id(name),
+        closed: false,
+        account_type: "import".to_owned(),
+        created: time::Time::now(),
+        description: name.to_owned(),
+        currency: currency.to_owned(),
+        country_code: String::new(),
+        owners: vec![],
+        account_number: source.account_number.clone(),
+        sort_code: source.sort_code.clone(),
+    }
+}
+
+/// Parses an amount such as `-1,234.50`, `£12.00` or `(12.00)` into minor currency units
+pub fn parse_amount(s: &str, currency: &str) -> Option<i32> {
+    // currency symbols before the sign, e.g. `£-12.00`
+    let s = s
+        .trim()
+        .trim_start_matches(|c: char| !c.is_ascii_digit() && !"-+(.".contains(c));
+    let (negative, s) = match s.strip_prefix('(').and_then(|s| s.strip_suffix(')')) {
+        Some(s) => (true, s),
+        None => match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        },
+    };
+    // currency symbols and thousands separators
+    let s: String = s
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    let (major, minor) = s.split_once('.').unwrap_or((&s, ""));
+    let exponent = util::currency_exponent(currency) as usize;
+    if major.is_empty() && minor.is_empty() || minor.len() > exponent || minor.contains('.') {
+        return None;
+    }
+    let major = if major.is_empty() {
+        0
+    } else {
+        major.parse::<i32>().ok()?
+    };
+    let minor = if minor.is_empty() {
+        0
+    } else {
+        format!("{:0<width$}", minor, width = exponent)
+            .parse::<i32>()
+            .ok()?
+    };
+    let amount = major
+        .checked_mul(10i32.pow(exponent as u32))?
+        .checked_add(minor)?;
+    Some(if negative { -amount } else { amount })
+}
+
+/// A hash of `s` that is the same every time, so that importing the same statement twice
+/// gives its transactions the same ids
+fn stable_hash(s: &str) -> u64 {
+    // FNV-1a
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in s.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+/// Gives statement lines that don't have an id of their own one from their contents. Identical
+/// lines on the same day (e.g. two coffees) are told apart by the order they appear in
+struct LineIds<'a> {
+    name: &'a str,
+    seen: HashMap<u64, u32>,
+}
+
+impl<'a> LineIds<'a> {
+    fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            seen: HashMap::new(),
+        }
+    }
+
+    fn next(&mut self, created: &time::Time, description: &str, amount: i32) -> String {
+        let hash = stable_hash(&format!(
+            "{}|{}|{}",
+            created.as_iso_8601_string(),
+            description,
+            amount
+        ));
+        let occurrence = self.seen.entry(hash).or_insert(0);
+        *occurrence += 1;
+        format!(
+            "{}_{:016x}_{}",
+            import_account_id(self.name),
+            hash,
+            occurrence
+        )
+    }
+}
+
+/// A transaction from another bank's statement, these have no merchant or counterparty
+fn statement_transaction(
+    name: &str,
+    id: String,
+    created: time::Time,
+    description: String,
+    amount: i32,
+    currency: &str,
+    balance: Option<i32>,
+) -> Transaction {
+    let mut metadata = HashMap::new();
+    metadata.insert("import".to_owned(), name.to_owned());
+    Transaction {
+        account_id: import_account_id(name),
+        account_balance: balance,
+        amount,
+        settled: Some(created.clone()),
+        created,
+        currency: currency.to_owned(),
+        local_amount: amount,
+        local_currency: currency.to_owned(),
+        amount_is_pending: false,
+        updated: None,
+        description,
+        id,
+        merchant: None,
+        counterparty: None,
+        decline_reason: None,
+        metadata,
+        attachments: vec![],
+        custom_category: None,
+        tags: vec![],
+    }
+}
+
+/// The balance of an imported account from its transactions, oldest first. This is the last
+/// balance that a statement gave plus any transactions after it
+pub fn statement_balance(transactions: &[&Transaction], currency: &str) -> Balance {
+    let mut balance = 0;
+    for transaction in transactions {
+        balance = match transaction.account_balance {
+            Some(account_balance) => account_balance,
+            None => balance + transaction.amount,
+        };
+    }
+    Balance {
+        balance,
+        total_balance: balance,
+        balance_including_flexible_savings: balance,
+        currency: currency.to_owned(),
+        spend_today: 0,
+    }
+}
+
+/// Reads a statement for the import called `name`, the format is `source.format` or else
+/// guessed from `file_name`. The transactions are oldest first
+pub fn parse_statement(
+    name: &str,
+    source: &ImportSource,
+    file_name: &str,
+    contents: &str,
+) -> Result<Vec<Transaction>, BadArgumentError> {
+    let format = match source.format {
+        Some(format) => format,
+        None => {
+            let extension = std::path::Path::new(file_name)
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase());
+            match extension.as_deref() {
+                Some("ofx") | Some("qfx") => StatementFormat::Ofx,
+                Some("csv") | Some("tsv") | Some("txt") => StatementFormat::Csv,
+                _ => {
+                    return Err(BadArgumentError(format!(
+                        "can't tell what format {} is in, set `format` to `csv` or `ofx` in \
+                        the `{}` import",
+                        file_name, name
+                    )))
+                }
+            }
+        }
+    };
+    let mut transactions = match format {
+        StatementFormat::Csv => parse_delimited(name, source, contents)?,
+        StatementFormat::Ofx => parse_ofx(name, contents)?,
+    };
+    // most banks list the newest first, reverse them so that lines on the same day stay in
+    // the order they happened in
+    if transactions.first().map(|first| &first.created)
+        > transactions.last().map(|last| &last.created)
+    {
+        transactions.reverse();
+    }
+    transactions.sort_by(|a, b| a.created.partial_cmp(&b.created).unwrap());
+    Ok(transactions)
+}
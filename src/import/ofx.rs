@@ -0,0 +1,106 @@
+use crate::types::error::BadArgumentError;
+use crate::types::*;
+
+use super::{import_account_id, parse_amount, statement_transaction, LineIds};
+
+/// The contents of the first `<tag>` element in `sgml`. OFX 1.x is SGML, where elements that
+/// only hold a value don't have to be closed, so the value ends at the next tag
+fn element(sgml: &str, tag: &str) -> Option<String> {
+    let open = format!("<{}>", tag);
+    let start = find_ignore_case(sgml, &open)? + open.len();
+    let rest = &sgml[start..];
+    let end = rest.find('<').unwrap_or(rest.len());
+    Some(
+        rest[..end]
+            .trim()
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&"),
+    )
+}
+
+/// Each block between `<tag>` and `</tag>` in `sgml`
+fn blocks<'a>(sgml: &'a str, tag: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    let mut blocks = vec![];
+    let mut rest = sgml;
+    while let Some(start) = find_ignore_case(rest, &open) {
+        rest = &rest[start + open.len()..];
+        let end = find_ignore_case(rest, &close).unwrap_or(rest.len());
+        blocks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    blocks
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .to_ascii_uppercase()
+        .find(&needle.to_ascii_uppercase())
+}
+
+/// Parses an OFX date such as `20220131`, `20220131120000` or `20220131120000.000[-5:EST]`,
+/// time zones are ignored
+fn parse_date(s: &str) -> Option<time::Time> {
+    let digits: String = s.chars().take_while(char::is_ascii_digit).collect();
+    match digits.len() {
+        8 => chrono::NaiveDate::parse_from_str(&digits, "%Y%m%d")
+            .ok()
+            .map(|date| date.and_time(chrono::NaiveTime::MIN).into()),
+        14 => chrono::NaiveDateTime::parse_from_str(&digits, "%Y%m%d%H%M%S")
+            .ok()
+            .map(Into::into),
+        _ => None,
+    }
+}
+
+/// Reads an OFX bank or credit card statement. Transactions keep the `FITID` that the bank
+/// gave them, so importing overlapping statements doesn't duplicate them
+pub fn parse_ofx(name: &str, contents: &str) -> Result<Vec<Transaction>, BadArgumentError> {
+    let currency = element(contents, "CURDEF")
+        .unwrap_or_else(|| "GBP".to_owned())
+        .to_uppercase();
+    let statements = blocks(contents, "STMTTRN");
+    if statements.is_empty() && find_ignore_case(contents, "<OFX>").is_none() {
+        return Err(BadArgumentError(
+            "this isn't an OFX statement, there is no <OFX> element".to_owned(),
+        ));
+    }
+
+    let mut ids = LineIds::new(name);
+    let mut transactions = vec![];
+    for statement in statements {
+        let field = |tag: &str| {
+            element(statement, tag).ok_or_else(|| {
+                BadArgumentError(format!("a transaction in the statement has no <{}>", tag))
+            })
+        };
+        let posted = field("DTPOSTED")?;
+        let created = parse_date(&posted)
+            .ok_or_else(|| BadArgumentError(format!("`{}` is not an OFX date", posted)))?;
+        let amount = field("TRNAMT")?;
+        let amount = parse_amount(&amount, &currency)
+            .ok_or_else(|| BadArgumentError(format!("`{}` is not an amount", amount)))?;
+        let description = match (element(statement, "NAME"), element(statement, "MEMO")) {
+            (Some(payee), Some(memo)) if !memo.is_empty() && memo != payee => {
+                format!("{} {}", payee, memo)
+            }
+            (Some(payee), _) => payee,
+            (None, memo) => memo.unwrap_or_default(),
+        };
+        let id = match element(statement, "FITID") {
+            Some(fitid) if !fitid.is_empty() => format!("{}_{}", import_account_id(name), fitid),
+            _ => ids.next(&created, &description, amount),
+        };
+        transactions.push(statement_transaction(
+            name,
+            id,
+            created,
+            description,
+            amount,
+            &currency,
+            None,
+        ));
+    }
+    Ok(transactions)
+}
@@ -1,6 +1,9 @@
 #![feature(async_closure)]
 
 pub mod commands;
+pub mod config_file;
+pub mod export;
+pub mod import;
 pub mod types;
 pub mod user_file;
 pub mod client;
@@ -1,4 +1,21 @@
+use std::convert::TryFrom;
+
+use crate::cli::chart;
+use crate::cli::{foreground_color_codes, ColorSupport};
+use crate::commands::{
+    attachment_dir, authorized, balance_all_table, balance_history, detect_recurring,
+    duplicate_charges, fit, fx_currencies, fx_trips, low_balance_event, match_statement,
+    merchant_directory, merge_accounts, render_metrics, summarise, transaction_events,
+    AccountMetrics, ExpenseReport, Query, ReferenceRates, ServeState,
+};
+use crate::import::{parse_amount, parse_ledger, parse_statement, statement_balance};
+use crate::types::config::{Colour, ImportSource, LedgerConfig, Person};
+use crate::types::filter::Filter;
+use crate::types::rules::{Rule, Rules};
+use crate::types::split::{Split, SplitLedger};
 use crate::types::time::Time;
+use crate::types::user::User;
+use crate::types::{AccessTokenResponse, Account, Balance, Counterparty, Pot, Transaction};
 
 fn display_result<T: std::fmt::Display, E: std::fmt::Display>(result: Result<T, E>) -> String {
     match result {
@@ -7,6 +24,88 @@ fn display_result<T: std::fmt::Display, E: std::fmt::Display>(result: Result<T,
     }
 }
 
+fn access_token_response(user_id: &str) -> AccessTokenResponse {
+    AccessTokenResponse {
+        access_token: "token".to_owned(),
+        client_id: "client".to_owned(),
+        expires_in: 3600,
+        refresh_token: None,
+        token_type: "Bearer".to_owned(),
+        user_id: user_id.to_owned(),
+    }
+}
+
+/// Creates a transaction with no merchant or counterparty
+fn transaction(id: &str, created: &str, amount: i32, description: &str) -> Transaction {
+    serde_json::from_value(serde_json::json!({
+        "account_balance": null,
+        "amount": amount,
+        "created": created,
+        "currency": "GBP",
+        "description": description,
+        "id": id,
+        "merchant": null,
+        "counterparty": null,
+        "decline_reason": null,
+        "metadata": {},
+    }))
+    .unwrap()
+}
+
+/// Gives a transaction a merchant, in the chain `group_id`
+fn at_merchant(
+    mut transaction: Transaction,
+    id: &str,
+    group_id: &str,
+    name: &str,
+    city: &str,
+) -> Transaction {
+    transaction.merchant = serde_json::from_value(serde_json::json!({
+        "address": {
+            "address": "1 High Street",
+            "city": city,
+            "country": "GBR",
+            "latitude": 0.0,
+            "longitude": 0.0,
+            "postcode": "",
+            "region": "",
+        },
+        "created": "2020-01-01T00:00:00.0Z",
+        "group_id": group_id,
+        "id": id,
+        "logo": "",
+        "emoji": "",
+        "name": name,
+        "category": "groceries",
+    }))
+    .unwrap();
+    transaction
+}
+
+/// Creates an account owned by `owners`
+fn account(id: &str, account_type: &str, closed: bool, owners: &[&str]) -> Account {
+    serde_json::from_value(serde_json::json!({
+        "id": id,
+        "closed": closed,
+        "type": account_type,
+        "created": "2020-01-01T00:00:00.0Z",
+        "description": "",
+        "currency": "GBP",
+        "country_code": "GB",
+        "owners": owners
+            .iter()
+            .map(|name| serde_json::json!({
+                "user_id": format!("user_{}", name),
+                "preferred_name": name,
+                "preferred_first_name": name,
+            }))
+            .collect::<Vec<_>>(),
+        "account_number": "12345678",
+        "sort_code": "040004",
+    }))
+    .unwrap()
+}
+
 pub fn time_parsing() {
     let time_1 = Time::try_parse_str("t+10s");
     let time_2 = Time::try_parse_str("T - 10h");
@@ -19,3 +118,1263 @@ pub fn time_parsing() {
     println!("time_4: {}", display_result(time_4));
     println!("time_5: {}", display_result(time_5));
 }
+
+pub fn recurring_detection() {
+    let transactions = vec![
+        transaction("tx_1", "2022-01-01T09:00:00.0Z", -899, "NETFLIX"),
+        transaction("tx_2", "2022-02-01T09:00:00.0Z", -899, "NETFLIX"),
+        transaction("tx_3", "2022-03-01T09:00:00.0Z", -999, "NETFLIX"),
+        transaction("tx_4", "2022-03-01T10:00:00.0Z", -999, "NETFLIX"),
+        transaction("tx_5", "2022-05-01T09:00:00.0Z", -999, "NETFLIX"),
+        transaction("tx_6", "2022-01-03T12:00:00.0Z", -2314, "TESCO"),
+        transaction("tx_7", "2022-01-10T12:00:00.0Z", -4120, "TESCO"),
+        transaction("tx_8", "2022-01-17T12:00:00.0Z", -1833, "TESCO"),
+        transaction("tx_9", "2022-01-24T12:00:00.0Z", -2950, "TESCO"),
+    ];
+    let now = Time::try_parse_str("2022-05-10T00:00:00.0Z").unwrap();
+    // NETFLIX should be monthly with a price rise, a duplicate and a missed payment in april,
+    // TESCO should not be detected
+    for payment in detect_recurring(&transactions, &now) {
+        println!("{:?}", payment);
+    }
+}
+
+pub fn filter_parsing() {
+    let mut transactions = [
+        transaction("tx_1", "2022-01-01T09:00:00.0Z", -6000, "TESCO STORES"),
+        transaction("tx_2", "2022-01-02T09:00:00.0Z", -450, "PRET A MANGER"),
+        transaction("tx_3", "2022-01-03T09:00:00.0Z", 10000, "SALARY"),
+    ];
+    transactions[1]
+        .metadata
+        .insert("notes".to_owned(), "lunch with Sam".to_owned());
+    for filter in [
+        "amount<-5000",
+        "tesco or has-notes",
+        "!(amount>0) & \"lunch\"",
+        "description~\"a manger\" currency=gbp",
+        "currency=EUR",
+        "",
+        "amount<five",
+        "merchant<tesco",
+        "(declined",
+        "colour=red",
+    ] {
+        let ids = Filter::parse(filter).map(|filter| {
+            transactions
+                .iter()
+                .filter(|transaction| filter.matches(transaction))
+                .map(|transaction| transaction.id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        });
+        println!("{:?}: {}", filter, display_result(ids));
+    }
+}
+
+pub fn table_rendering() {
+    use crate::cli::table::{Align, Table};
+    let red = |s: &str| {
+        crate::cli::AnsiStringBuilder::new()
+            .set_foreground_color(255, 20, 20)
+            .push_str(s)
+            .build()
+    };
+    let mut table = Table::new()
+        .column("TIME", Align::Left)
+        .column("PAYEE", Align::Left)
+        .column("AMOUNT", Align::Right)
+        .column("NOTES", Align::Left);
+    table.push_row(vec![
+        "2022-01-03 12:00".to_owned(),
+        "Tesco Stores 3297 London".to_owned(),
+        red("-1.05"),
+        "milk".to_owned(),
+    ]);
+    table.push_row(vec![
+        "2022-01-04 09:00".to_owned(),
+        "Sam Smith".to_owned(),
+        "1200.00".to_owned(),
+        "rent for January and February".to_owned(),
+    ]);
+    // the amounts are never truncated, and no column is shrunk below 6 characters
+    for max_width in [None, Some(60), Some(40), Some(10)] {
+        println!("{:?}", max_width);
+        println!("{}", table.render_with(max_width, false));
+    }
+    println!("{:?}", table.render_with(Some(40), true));
+
+    // no headers are shown when every header is empty
+    let mut table = Table::new()
+        .column("", Align::Left)
+        .column("", Align::Right);
+    table.push_row(vec!["balance".to_owned(), "12.00".to_owned()]);
+    table.push_row(vec!["pots".to_owned()]);
+    println!("{}", table.render_with(None, false));
+
+    let coloured = format!("{} {}", red("café"), red("crème"));
+    println!(
+        "{:?} {} {:?}",
+        crate::cli::strip_ansi(&coloured),
+        crate::cli::visible_width(&coloured),
+        crate::cli::truncate_ansi(&coloured, 6)
+    );
+    println!("{:?}", crate::cli::truncate_ansi("plain text", 5));
+}
+
+pub fn tui_cells() {
+    let red = crate::cli::AnsiStringBuilder::new()
+        .set_foreground_color(255, 20, 20)
+        .push_str("-£12.50")
+        .build();
+    for (s, width) in [
+        ("TESCO", 8),
+        ("TESCO STORES 3297", 8),
+        ("café", 4),
+        ("", 3),
+        ("TESCO", 0),
+        (red.as_str(), 10),
+        (red.as_str(), 4),
+    ] {
+        println!("{:?} {} -> {:?}", s, width, fit(s, width));
+    }
+}
+
+pub fn colour_parsing() {
+    for s in [
+        "#14ff14", "#14FF14", "14ff14", "#14ff1", "#14ff14f", "#14ffgg", "#+f+f+f", "#a€bc", "",
+    ] {
+        println!("{:?} -> {:?}", s, Colour::try_from(s.to_owned()));
+    }
+    // the closest colour when the terminal doesn't support 24-bit colour
+    for colour in [
+        (255, 20, 20),
+        (20, 255, 20),
+        (150, 150, 150),
+        (0, 0, 0),
+        (255, 255, 255),
+    ] {
+        for color_support in [
+            ColorSupport::TrueColor,
+            ColorSupport::Ansi256,
+            ColorSupport::Ansi16,
+            ColorSupport::None,
+        ] {
+            println!(
+                "{:?} {:?} {:?}",
+                colour,
+                color_support,
+                foreground_color_codes(colour, color_support)
+            );
+        }
+    }
+}
+
+pub fn template_rendering() {
+    for template in [
+        "{created:%d %b} {amount:money} {merchant.name|counterparty.name|description}",
+        "{{{description}}}",
+        "{description:8}|{amount:>6}|",
+        "{created:%H:%M:%S}",
+        "{description",
+        "description}",
+        "{merchant.|description}",
+        "{amount:wide}",
+        "{created:%Q}",
+    ] {
+        println!(
+            "{:?}: {:?}",
+            template,
+            crate::cli::template::Template::parse(template).map_err(|e| e.0)
+        );
+    }
+
+    let tesco = at_merchant(
+        transaction("tx_1", "2022-01-03T12:00:00.0Z", -105, "TESCO STORES 3297"),
+        "merch_1",
+        "grp_1",
+        "Tesco",
+        "London",
+    );
+    let mut transfer = transaction("tx_2", "2022-01-04T09:00:00.0Z", 1200, "MONZO-ABCD");
+    transfer.counterparty = Some(Counterparty {
+        account_number: None,
+        name: Some("Sam Smith".to_owned()),
+        sort_code: None,
+        user_id: None,
+    });
+    let plain = transaction("tx_3", "2022-01-05T18:30:00.0Z", -50, "CAFE");
+    for template in [
+        "{created:%d %b} {amount:money} {merchant.name|counterparty.name|description}",
+        "{{{description}}} {notes}",
+        "[{description:8}] [{amount:money:>8}]",
+        "{created:%H:%M} {amount:money:colour:bold}",
+    ] {
+        let template = crate::cli::template::Template::parse(template).unwrap();
+        for transaction in [&tesco, &transfer, &plain] {
+            println!("{:?}", template.render(transaction));
+        }
+    }
+}
+
+pub fn template_money() {
+    let mut transactions = vec![
+        transaction("tx_1", "2022-01-03T12:00:00.0Z", -105, "TESCO"),
+        transaction("tx_2", "2022-01-03T18:00:00.0Z", 1200, "REFUND"),
+        transaction("tx_3", "2022-01-04T09:00:00.0Z", -1054, "CAFE"),
+    ];
+    transactions[2].local_amount = -1234;
+    transactions[2].local_currency = "EUR".to_owned();
+    let template =
+        crate::cli::template::Template::parse("{amount:money} ({local_amount:money})").unwrap();
+    for transaction in &transactions {
+        println!("{}", template.render(transaction));
+    }
+}
+
+pub fn statement_export() {
+    let mut transactions = vec![
+        transaction("tx_1", "2022-01-03T12:00:00.0Z", -3001, "CAFÉ <&> CRÈME"),
+        transaction("tx_2", "2022-01-03T18:00:00.0Z", -5000, "HOTEL"),
+        transaction("tx_3", "2022-01-04T09:00:00.0Z", -2500, "SHOES"),
+    ];
+    transactions[0].account_balance = Some(96999);
+    transactions[0]
+        .metadata
+        .insert("notes".to_owned(), "coffee\nwith Sam".to_owned());
+    transactions[1].updated = Some(transactions[1].created.clone());
+    transactions[2].decline_reason = Some("INSUFFICIENT_FUNDS".to_owned());
+    let account = account("acc_1", "uk_retail", false, &["Alex"]);
+
+    println!(
+        "{}",
+        display_result(crate::commands::transactions_to_csv(&transactions))
+    );
+
+    // the statement time changes every run, so only the header and transactions are shown
+    let ofx = crate::export::to_ofx(&account, &transactions, false);
+    for line in ofx.lines().filter(|line| {
+        line.starts_with("ENCODING")
+            || line.starts_with("CHARSET")
+            || line.starts_with("<FITID>")
+            || line.starts_with("<NAME>")
+            || line.starts_with("<MEMO>")
+            || line.starts_with("<BALAMT>")
+    }) {
+        println!("{}", line);
+    }
+    // pending transactions are only included when asked for
+    println!(
+        "ofx with pending: {} transactions",
+        crate::export::to_ofx(&account, &transactions, true)
+            .matches("<STMTTRN>")
+            .count()
+    );
+    print!("{}", crate::export::to_qif(&account, &transactions, false));
+    print!("{}", crate::export::to_qif(&account, &transactions, true));
+}
+
+pub fn ledger_export() {
+    let mut transactions = vec![
+        transaction("tx_1", "2022-01-03T12:00:00.0Z", -3001, "TESCO"),
+        transaction("tx_2", "2022-01-03T18:00:00.0Z", -5000, "HOTEL"),
+        transaction("tx_3", "2022-01-04T09:00:00.0Z", -2500, "SHOES"),
+        transaction("tx_4", "2022-01-05T09:00:00.0Z", 10000, "SALARY"),
+    ];
+    transactions[0].account_balance = Some(96999);
+    transactions[1].account_balance = Some(91999);
+    // pending, so it isn't cleared
+    transactions[1].updated = Some(transactions[1].created.clone());
+    transactions[2].decline_reason = Some("INSUFFICIENT_FUNDS".to_owned());
+    transactions[3].account_balance = Some(101999);
+    let account = account("acc_1", "uk_retail", false, &["Alex"]);
+    let config = LedgerConfig::default();
+
+    // the balance before the first transaction is the opening balance
+    print!(
+        "{}",
+        crate::export::to_ledger(&account, &transactions, &config, true)
+    );
+    print!(
+        "{}",
+        crate::export::to_beancount(&account, &transactions, &config, true)
+    );
+    // filtered transactions don't add up to the balances, so nothing is asserted
+    print!(
+        "{}",
+        crate::export::to_ledger(&account, &transactions[1..2], &config, false)
+    );
+}
+
+pub fn balance_history_reconstruction() {
+    let mut transactions = vec![
+        transaction("tx_1", "2022-01-02T09:00:00.0Z", -1000, "TESCO"),
+        transaction("tx_2", "2022-01-02T18:00:00.0Z", -500, "PRET A MANGER"),
+        transaction("tx_3", "2022-01-04T09:00:00.0Z", 20000, "SALARY"),
+        transaction("tx_4", "2022-01-05T09:00:00.0Z", -9999, "DECLINED"),
+    ];
+    transactions[3].decline_reason = Some("INSUFFICIENT_FUNDS".to_owned());
+    let date = |s| chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+    // a closing balance of 200.00 means 15.00 before the first transaction, with no warnings
+    let history = balance_history(
+        &transactions,
+        Some(20000),
+        "GBP",
+        date("2022-01-01"),
+        date("2022-01-05"),
+    );
+    for day in &history.days {
+        println!("{} {}", day.date, day.balance);
+    }
+    println!("warnings: {:?}", history.warnings);
+    println!(
+        "{}",
+        chart::sparkline(
+            &history
+                .days
+                .iter()
+                .map(|day| day.balance as i64)
+                .collect::<Vec<_>>(),
+            false
+        )
+    );
+
+    // a transaction balance that disagrees with the current balance should be warned about
+    transactions[1].account_balance = Some(105);
+    let history = balance_history(
+        &transactions,
+        Some(20000),
+        "GBP",
+        date("2022-01-01"),
+        date("2022-01-05"),
+    );
+    println!("warnings: {:?}", history.warnings);
+}
+
+pub fn account_merging() {
+    let mut accounts = vec![
+        account("acc_1", "uk_retail", false, &["Alex"]),
+        account("acc_2", "uk_retail_joint", false, &["Alex"]),
+        account("acc_3", "uk_prepaid", false, &["Alex"]),
+    ];
+    let fetched = vec![
+        account("acc_4", "uk_retail_joint", false, &["Alex", "Sam"]),
+        account("acc_2", "uk_retail_joint", false, &["Alex", "Sam"]),
+        account("acc_1", "uk_retail", true, &["Alex"]),
+    ];
+    // acc_1 closed, acc_2 gained an owner, acc_3 vanished and acc_4 opened
+    for change in merge_accounts(&mut accounts, fetched) {
+        println!("{}", change);
+    }
+    // the stored order is kept, with new accounts at the end
+    for account in &accounts {
+        println!("{} closed={}", account.id, account.closed);
+    }
+}
+
+pub fn account_selection() {
+    let mut accounts = vec![
+        account("acc_1", "uk_retail", false, &["Alex"]),
+        account("acc_2", "uk_retail_joint", false, &["Alex", "Sam"]),
+        account("acc_3", "uk_retail_joint", true, &["Alex", "Kim"]),
+    ];
+    accounts[1].account_number = "35847686".to_owned();
+    let mut aliases = std::collections::BTreeMap::new();
+    aliases.insert("bills".to_owned(), "****7686".to_owned());
+    for selector in [
+        "0", "2", "acc_3", "****7686", "joint", "retail", "bills", "3", "savings",
+    ] {
+        println!(
+            "{:?}: {}",
+            selector,
+            display_result(crate::util::resolve_account(&accounts, &aliases, selector))
+        );
+    }
+
+    for (account_type, selector) in [
+        ("uk_retail_joint", "joint"),
+        ("uk_retail_joint", "Retail_Joint"),
+        ("uk_retail_joint", "retail"),
+        ("uk_retail", "retail"),
+        ("uk_retail", "uk_retail"),
+        ("uk_retail", "tail"),
+    ] {
+        println!(
+            "{} {}: {}",
+            account_type,
+            selector,
+            crate::util::account_type_matches(account_type, selector)
+        );
+    }
+
+    // without `account=`, the first open retail account rather than whatever is first
+    let mut accounts = vec![
+        account("import_barclays", "import", false, &[]),
+        account("acc_1", "uk_retail", true, &["Alex"]),
+        account("acc_2", "uk_retail_joint", false, &["Alex", "Sam"]),
+        account("acc_3", "uk_retail", false, &["Alex"]),
+    ];
+    println!("default: {:?}", crate::util::default_account(&accounts));
+    accounts.remove(3);
+    println!(
+        "no open retail account: {:?}",
+        crate::util::default_account(&accounts)
+    );
+    println!("no accounts: {:?}", crate::util::default_account(&[]));
+}
+
+pub fn daemon_events() {
+    let cached = [transaction(
+        "tx_1",
+        "2022-01-01T09:00:00.0Z",
+        -450,
+        "PRET A MANGER",
+    )];
+    let mut fetched = vec![
+        cached[0].clone(),
+        transaction("tx_2", "2022-01-02T09:00:00.0Z", -120000, "RENT"),
+        transaction("tx_3", "2022-01-02T10:00:00.0Z", -305, "COFFEE"),
+        transaction("tx_4", "2022-01-02T11:00:00.0Z", -5000, "SHOES"),
+        transaction("tx_5", "2022-01-02T12:00:00.0Z", -1205, "CAFE"),
+    ];
+    fetched[3].decline_reason = Some("INSUFFICIENT_FUNDS".to_owned());
+    fetched[4].currency = "EUR".to_owned();
+    let config = crate::types::config::DaemonConfig {
+        large_debit: Some(10000),
+        low_balance: Some(5000),
+        ..Default::default()
+    };
+    // tx_1 is already cached, so it shouldn't be an event
+    for event in transaction_events(&cached.iter().collect::<Vec<_>>(), &fetched, &config) {
+        println!("{}: {}", event.kind(), event.message());
+    }
+
+    let balance: crate::types::Balance =
+        serde_json::from_value(serde_json::json!({ "balance": 4005, "currency": "GBP" })).unwrap();
+    // only the sync where the balance becomes low has an event
+    for previous in [None, Some(6000), Some(4500)] {
+        println!(
+            "{:?}: {:?}",
+            previous,
+            low_balance_event("acc_1", previous, &balance, &config).map(|event| event.message())
+        );
+    }
+}
+
+pub fn metrics_rendering() {
+    let account = account("acc_1", "uk_retail", false, &["Alex"]);
+    let balance: crate::types::Balance = serde_json::from_value(serde_json::json!({
+        "balance": 81240,
+        "total_balance": 131240,
+        "currency": "GBP",
+        "spend_today": -1220,
+    }))
+    .unwrap();
+    let pots: Vec<crate::types::Pot> = serde_json::from_value(serde_json::json!([
+        { "id": "pot_1", "name": "Holiday \"2022\"", "balance": 50000, "currency": "GBP" },
+        { "id": "pot_2", "name": "Old", "balance": 0, "currency": "GBP", "deleted": true },
+    ]))
+    .unwrap();
+    let transactions = [
+        transaction("tx_1", "2022-01-01T09:00:00.0Z", -450, "PRET A MANGER"),
+        transaction("tx_2", "2022-01-02T09:00:00.0Z", -550, "PRET A MANGER"),
+        transaction("tx_3", "2022-01-03T09:00:00.0Z", 10000, "SALARY"),
+    ];
+    print!(
+        "{}",
+        render_metrics(&[AccountMetrics {
+            account: &account,
+            balance: &balance,
+            pots: &pots,
+            transactions: transactions.iter().collect(),
+        }])
+    );
+}
+
+pub fn balance_all_rendering() {
+    let balance = |balance: i32, currency: &str| -> Balance {
+        serde_json::from_value(serde_json::json!({
+            "balance": balance,
+            "total_balance": balance,
+            "currency": currency,
+            "spend_today": -1220,
+        }))
+        .unwrap()
+    };
+    let pots: Vec<Pot> = serde_json::from_value(serde_json::json!([
+        { "id": "pot_1", "name": "Holiday", "balance": 50000, "currency": "GBP" },
+        { "id": "pot_4", "name": "Bills", "balance": 1205, "currency": "GBP" },
+        { "id": "pot_2", "name": "Old", "balance": 1000, "currency": "GBP", "deleted": true },
+        { "id": "pot_3", "name": "Euros", "balance": 2550, "currency": "EUR" },
+    ]))
+    .unwrap();
+    let accounts = vec![
+        (0, account("acc_1", "uk_retail", false, &["Alex"])),
+        (
+            2,
+            account("acc_2", "uk_retail_joint", false, &["Alex", "Sam"]),
+        ),
+    ];
+    let balances = vec![
+        (balance(81240, "GBP"), pots),
+        (balance(-2000, "GBP"), vec![]),
+    ];
+    println!(
+        "{}",
+        balance_all_table(&accounts, &balances).render_with(None, false)
+    );
+}
+
+pub fn serve_api() {
+    let mut user = User::new(
+        access_token_response("user"),
+        vec![account("acc_1", "uk_retail", false, &["Alex"])],
+    );
+    let mut declined = transaction("tx_4", "2022-01-04T09:00:00.0Z", -2000, "HOTEL");
+    declined.decline_reason = Some("INSUFFICIENT_FUNDS".to_owned());
+    let mut rent = transaction("tx_5", "2022-01-05T09:00:00.0Z", -80000, "LANDLORD");
+    rent.custom_category = Some("rent".to_owned());
+    let mut transactions = vec![
+        at_merchant(
+            transaction("tx_1", "2022-01-01T09:00:00.0Z", -450, "PRET A MANGER"),
+            "merch_1",
+            "grp_1",
+            "Pret A Manger",
+            "London",
+        ),
+        transaction("tx_2", "2022-01-02T09:00:00.0Z", -550, "TFL"),
+        transaction("tx_3", "2022-01-03T09:00:00.0Z", 10000, "SALARY"),
+        declined,
+        rent,
+    ];
+    for transaction in &mut transactions {
+        transaction.account_id = "acc_1".to_owned();
+    }
+    user.cache_transactions(&transactions);
+    let state = ServeState {
+        user,
+        client: reqwest::Client::new(),
+        config: Default::default(),
+        synced: Default::default(),
+        metrics: None,
+    };
+
+    let queries = [
+        serde_json::json!({}),
+        serde_json::json!({ "since": "2022-01-02T00:00:00.0Z", "before": "2022-01-05T00:00:00.0Z" }),
+        serde_json::json!({ "q": "amount<0" }),
+        serde_json::json!({ "since": "yesterday-ish" }),
+    ];
+    for query in queries {
+        let query: Query = serde_json::from_value(query).unwrap();
+        match query.transactions(&state, 0) {
+            Ok(transactions) => {
+                let ids: Vec<&str> = transactions.iter().map(|t| t.id.as_str()).collect();
+                println!("{:?} {:?}", query, ids);
+                println!("{:?}", summarise(&state.user.accounts[0], &transactions));
+            }
+            Err(e) => println!("{:?} {:?}", query, e),
+        }
+    }
+
+    let filter = authorized("secret".to_owned());
+    let requests = [
+        warp::test::request().header("authorization", "Bearer secret"),
+        warp::test::request().path("/summary?token=secret"),
+        warp::test::request().header("authorization", "Bearer wrong"),
+        warp::test::request().path("/summary?token=wrong"),
+        warp::test::request().header("authorization", "secret"),
+        warp::test::request(),
+    ];
+    for request in requests {
+        println!("{}", pollster::block_on(request.filter(&filter)).is_ok());
+    }
+}
+
+pub fn attachment_dirs() {
+    let mut transaction = transaction(
+        "tx_1",
+        "2022-01-14T12:30:00.0Z",
+        -450,
+        "AMZN Mktp UK*2R4 / Books.",
+    );
+    transaction.attachments = serde_json::from_value(serde_json::json!([{
+        "id": "attach_1",
+        "external_id": "tx_1",
+        "file_url": "https://example.com/receipt",
+        "file_type": "image/jpeg",
+        "created": "2022-01-14T13:00:00.0Z",
+    }]))
+    .unwrap();
+    // slashes and other characters that aren't allowed in file names are replaced
+    println!(
+        "{}",
+        attachment_dir(std::path::Path::new("receipts"), &transaction).display()
+    );
+}
+
+pub fn expense_report() {
+    let tagged = |id: &str, created: &str, amount: i32, description: &str, notes: &str| {
+        let mut transaction = transaction(id, created, amount, description);
+        transaction
+            .metadata
+            .insert("notes".to_owned(), notes.to_owned());
+        transaction
+    };
+    let mut transactions = vec![
+        tagged(
+            "tx_1",
+            "2022-01-03T12:00:00.0Z",
+            -1250,
+            "TRAINLINE",
+            "to the office #work",
+        ),
+        tagged(
+            "tx_2",
+            "2022-01-04T12:00:00.0Z",
+            -900,
+            "PRET",
+            "#Work lunch | client",
+        ),
+        // not tagged, `#workshop` isn't `#work`
+        tagged(
+            "tx_3",
+            "2022-01-05T12:00:00.0Z",
+            -3000,
+            "HOBBYCRAFT",
+            "#workshop",
+        ),
+        tagged(
+            "tx_4",
+            "2022-01-06T12:00:00.0Z",
+            250,
+            "TRAINLINE",
+            "refund #work",
+        ),
+        tagged("tx_5", "2022-01-07T12:00:00.0Z", -2000, "HOTEL", "#work"),
+        tagged("tx_6", "2022-01-08T12:00:00.0Z", -400, "TAXI", "#work"),
+        // tagged by a rule rather than in the notes
+        tagged("tx_7", "2022-01-09T12:00:00.0Z", -1500, "PREMIER INN", ""),
+    ];
+    transactions[4].currency = "EUR".to_owned();
+    transactions[6].tags = vec!["Work".to_owned()];
+    transactions[5].decline_reason = Some("INSUFFICIENT_FUNDS".to_owned());
+    let report = ExpenseReport::new(
+        &transactions,
+        "#work",
+        Some(Time::try_parse_str("2022-01-01T00:00:00.0Z").unwrap()),
+        None,
+    );
+    println!("{}", report.to_markdown());
+    println!("{}", report.to_csv().unwrap());
+}
+
+pub fn split_ledger() {
+    let transfer = |id: &str, created: &str, amount: i32, name: &str, sort_code: &str| {
+        let mut transaction = transaction(id, created, amount, name);
+        transaction.counterparty = Some(Counterparty {
+            account_number: Some("12345678".to_owned()),
+            name: Some(name.to_owned()),
+            sort_code: Some(sort_code.to_owned()),
+            user_id: None,
+        });
+        transaction
+    };
+    let groceries = transaction("tx_1", "2022-01-03T12:00:00.0Z", -3001, "TESCO");
+    let takeaway = transaction("tx_2", "2022-01-04T12:00:00.0Z", -2500, "DELIVEROO");
+    let mut ledger = SplitLedger::default();
+    // split evenly three ways, you pay the extra penny
+    let split = Split::new(
+        &groceries,
+        &[("sam".to_owned(), None), ("alex".to_owned(), None)],
+        true,
+    )
+    .unwrap();
+    ledger.splits.insert(split.transaction_id.clone(), split);
+    // alex owes a fixed amount and sam pays the rest
+    let split = Split::new(
+        &takeaway,
+        &[("alex".to_owned(), Some(700)), ("sam".to_owned(), None)],
+        false,
+    )
+    .unwrap();
+    ledger.splits.insert(split.transaction_id.clone(), split);
+    // the shares don't add up to the transaction
+    if let Err(e) = Split::new(&takeaway, &[("alex".to_owned(), Some(700))], false) {
+        println!("{}", e);
+    }
+    // the fixed shares are more than the transaction, or owe the wrong way
+    for people in [
+        vec![("sam".to_owned(), Some(3000)), ("alex".to_owned(), None)],
+        vec![("sam".to_owned(), Some(-300)), ("alex".to_owned(), None)],
+    ] {
+        for include_me in [true, false] {
+            if let Err(e) = Split::new(&takeaway, &people, include_me) {
+                println!("{}", e);
+            }
+        }
+    }
+
+    let mut people = std::collections::BTreeMap::new();
+    people.insert(
+        "alex".to_owned(),
+        Person {
+            sort_code: Some("04-00-04".to_owned()),
+            ..Default::default()
+        },
+    );
+    let transactions = vec![
+        // from before anything was split, so it doesn't settle anything
+        transfer(
+            "tx_6",
+            "2021-12-01T12:00:00.0Z",
+            1000,
+            "Sam Smith",
+            "11-11-11",
+        ),
+        // sam pays back more than they owe, only what is owed is settled
+        transfer(
+            "tx_3",
+            "2022-01-05T12:00:00.0Z",
+            5000,
+            "Sam Smith",
+            "11-11-11",
+        ),
+        // the name doesn't match but the configured sort code does
+        transfer("tx_4", "2022-01-06T12:00:00.0Z", 500, "A Jones", "040004"),
+        // someone else
+        transfer("tx_5", "2022-01-06T12:00:00.0Z", 500, "Kim", "22-22-22"),
+    ];
+    for settlement in ledger.settle(&transactions, &people) {
+        println!(
+            "{} settled {} with {:?}",
+            settlement.person, settlement.amount, settlement.transaction_id
+        );
+    }
+    // settling again doesn't count the same transfers twice
+    println!("{}", ledger.settle(&transactions, &people).len());
+    for balance in ledger.balances() {
+        println!(
+            "{} shared={} settled={} outstanding={}",
+            balance.person, balance.shared, balance.settled, balance.outstanding
+        );
+    }
+}
+
+pub fn rule_categorisation() {
+    let rules = vec![
+        Rule {
+            sort_code: Some("20-00-00".to_owned()),
+            max_amount: Some(-50000),
+            category: Some("rent".to_owned()),
+            tags: vec!["bills".to_owned()],
+            ..Default::default()
+        },
+        Rule {
+            description: Some("^(tfl|trainline)".to_owned()),
+            category: Some("commute".to_owned()),
+            ..Default::default()
+        },
+        // adds a tag without changing the category
+        Rule {
+            description: Some("trainline".to_owned()),
+            tags: vec!["work".to_owned()],
+            ..Default::default()
+        },
+    ];
+    let rules = Rules::new(&rules).unwrap();
+    let mut rent = transaction("tx_1", "2022-01-01T09:00:00.0Z", -95000, "LANDLORD");
+    rent.counterparty = Some(Counterparty {
+        account_number: Some("87654321".to_owned()),
+        name: Some("Landlord Ltd".to_owned()),
+        sort_code: Some("200000".to_owned()),
+        user_id: None,
+    });
+    let mut transactions = vec![
+        rent,
+        transaction("tx_2", "2022-01-02T09:00:00.0Z", -2350, "TRAINLINE.COM"),
+        transaction("tx_3", "2022-01-03T09:00:00.0Z", -280, "TfL Travel Charge"),
+        transaction("tx_4", "2022-01-04T09:00:00.0Z", -1000, "AMAZON"),
+    ];
+    for transaction in &mut transactions {
+        let matched = rules.apply(transaction);
+        println!(
+            "{} matched={} category={:?} tags={:?}",
+            transaction.id,
+            matched,
+            transaction.category(),
+            transaction.tags
+        );
+    }
+    // the categories and tags are used by filters and exports
+    let filter = Filter::parse("category=commute or tag=bills").unwrap();
+    let ledger = LedgerConfig::default();
+    for transaction in &transactions {
+        println!(
+            "{} filtered={} account={}",
+            transaction.id,
+            filter.matches(transaction),
+            ledger.account_for(transaction)
+        );
+    }
+    println!(
+        "{}",
+        display_result(
+            Rules::new(&[Rule {
+                merchant: Some("(unclosed".to_owned()),
+                ..Default::default()
+            }])
+            .map(|_| "")
+        )
+    );
+}
+
+pub fn merchant_directory_grouping() {
+    let mut declined = transaction("tx_4", "2022-01-04T09:00:00.0Z", -9000, "TESCO");
+    declined.decline_reason = Some("INSUFFICIENT_FUNDS".to_owned());
+    let mut refund = transaction("tx_6", "2022-01-06T09:00:00.0Z", 500, "FRIEND");
+    refund.counterparty = Some(Counterparty {
+        account_number: Some("12345678".to_owned()),
+        name: Some("Sam Smith".to_owned()),
+        sort_code: Some("040004".to_owned()),
+        user_id: None,
+    });
+    let transactions = vec![
+        at_merchant(
+            transaction("tx_1", "2022-01-01T09:00:00.0Z", -1200, "TESCO"),
+            "merch_1",
+            "grp_tesco",
+            "Tesco",
+            "London",
+        ),
+        at_merchant(
+            transaction("tx_2", "2022-01-02T09:00:00.0Z", -800, "TESCO"),
+            "merch_2",
+            "grp_tesco",
+            "Tesco",
+            "Leeds",
+        ),
+        at_merchant(
+            transaction("tx_3", "2022-01-03T09:00:00.0Z", -350, "PRET"),
+            "merch_3",
+            "grp_pret",
+            "Pret A Manger",
+            "London",
+        ),
+        at_merchant(declined, "merch_1", "grp_tesco", "Tesco", "London"),
+        transaction("tx_5", "2022-01-05T09:00:00.0Z", -10000, "POT TRANSFER"),
+        refund,
+    ];
+    // chains are collapsed into one entry, declined transactions aren't visits
+    for collapse_chains in &[true, false] {
+        for entry in merchant_directory(&transactions, *collapse_chains) {
+            println!(
+                "{} [{}] visits={} spent={:?} received={:?} transactions={} address={:?}",
+                entry.name,
+                entry.key,
+                entry.visits,
+                entry.spent,
+                entry.received,
+                entry.transactions.len(),
+                entry.address
+            );
+        }
+    }
+}
+
+pub fn balance_caching() {
+    let mut user = User::new(access_token_response("user"), vec![]);
+    let balance = |balance: i32| Balance {
+        balance,
+        total_balance: balance,
+        balance_including_flexible_savings: balance,
+        currency: "GBP".to_owned(),
+        spend_today: 0,
+    };
+    let pot = Pot {
+        id: "pot_1".to_owned(),
+        name: "Savings".to_owned(),
+        balance: 5000,
+        currency: "GBP".to_owned(),
+        deleted: false,
+    };
+    user.cache_balance("acc_1", &balance(1000), Some(&[pot]));
+    // pots aren't fetched with a single balance, so the cached pots are kept
+    user.cache_balance("acc_1", &balance(750), None);
+    user.cache_balance("acc_2", &balance(-20), None);
+    for (account_id, cached) in &user.balances {
+        println!(
+            "{} balance={} pots={:?}",
+            account_id,
+            cached.balance.balance,
+            cached
+                .pots
+                .iter()
+                .map(|pot| (&pot.name, pot.balance))
+                .collect::<Vec<_>>()
+        );
+    }
+}
+
+pub fn statement_import() {
+    for amount in &[
+        "12.34", "-1,234.5", "£0.99", "(12.00)", "+3", "1.234", "abc", "",
+    ] {
+        println!("{:?} -> {:?}", amount, parse_amount(amount, "GBP"));
+    }
+
+    let source = ImportSource {
+        debit: Some("Paid out".to_owned()),
+        credit: Some("Paid in".to_owned()),
+        balance: Some("Balance".to_owned()),
+        ..Default::default()
+    };
+    // newest first, with two identical coffees and a footer
+    let csv = "\u{feff}Date,Description,Paid out,Paid in,Balance\n\
+        03/01/2022,COFFEE,2.50,,\"1,095.00\"\n\
+        03/01/2022,COFFEE,2.50,,\"1,097.50\"\n\
+        02/01/2022,SALARY,,\"1,000.00\",\"1,100.00\"\n\
+        ,Total,5.00,1000.00,\n";
+    let csv_transactions = parse_statement("barclays", &source, "statement.csv", csv);
+    let ofx = "OFXHEADER:100\n<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS><CURDEF>EUR\n\
+        <BANKTRANLIST>\n\
+        <STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20220105120000.000[0:GMT]<TRNAMT>-20.00\
+        <FITID>abc123<NAME>BAKERY &amp; CAFE<MEMO>CARD 1234</STMTTRN>\n\
+        <STMTTRN><TRNTYPE>CREDIT<DTPOSTED>20220104<TRNAMT>5<NAME>REFUND</STMTTRN>\n\
+        </BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>";
+    let ofx_transactions =
+        parse_statement("revolut", &ImportSource::default(), "statement.ofx", ofx);
+    for transactions in &[csv_transactions, ofx_transactions] {
+        match transactions {
+            Ok(transactions) => {
+                for transaction in transactions {
+                    println!(
+                        "{} {} {} {} {:?} {}",
+                        transaction.created,
+                        transaction.description,
+                        transaction.amount,
+                        transaction.currency,
+                        transaction.account_balance,
+                        transaction.id
+                    );
+                }
+                let transactions: Vec<&Transaction> = transactions.iter().collect();
+                println!(
+                    "balance {}",
+                    statement_balance(&transactions, "GBP").balance
+                );
+            }
+            Err(e) => println!("{}", e),
+        }
+    }
+    if let Err(e) = parse_statement("barclays", &source, "statement.pdf", "") {
+        println!("{}", e);
+    }
+    let missing_column = parse_statement(
+        "barclays",
+        &ImportSource::default(),
+        "statement.csv",
+        "Date,Details,Amount\n",
+    );
+    if let Err(e) = missing_column {
+        println!("{}", e);
+    }
+}
+
+pub fn statement_reconciliation() {
+    let with_balance = |mut transaction: Transaction, balance: i32| {
+        transaction.account_balance = Some(balance);
+        transaction
+    };
+    let coffee = |id: &str, created: &str| {
+        at_merchant(
+            transaction(id, created, -250, "PRET"),
+            "merch_pret",
+            "grp_pret",
+            "Pret A Manger",
+            "London",
+        )
+    };
+    let monzo = vec![
+        with_balance(
+            transaction("tx_1", "2022-01-01T09:00:00.0Z", 100000, "SALARY"),
+            100000,
+        ),
+        with_balance(coffee("tx_2", "2022-01-02T08:00:00.0Z"), 99750),
+        with_balance(coffee("tx_3", "2022-01-02T08:04:00.0Z"), 99500),
+        with_balance(
+            transaction("tx_4", "2022-01-03T12:00:00.0Z", -4000, "TFL"),
+            95500,
+        ),
+        with_balance(
+            transaction("tx_5", "2022-01-04T12:00:00.0Z", -1999, "NETFLIX"),
+            93501,
+        ),
+        with_balance(coffee("tx_6", "2022-01-06T08:00:00.0Z"), 93251),
+    ];
+    // tx_1 is matched by its id, the coffees by amount and date, TFL is missing from the
+    // statement and the statement has a fee that isn't in Monzo
+    let journal = "2022/01/01 * Salary\n    ; monzo_id: tx_1\n    Income:Salary\n    \
+        Assets:Monzo  1000.00 GBP = 1000.00 GBP\n\n\
+        2022-01-02 * \"PRET A MANGER\" \"coffee\"\n  Expenses:Coffee  2.50 GBP\n  \
+        Assets:Monzo  -2.50 GBP\n\n\
+        2022-01-03 open Expenses:Fees\n\n\
+        2022/01/03 PRET A MANGER LONDON\n    Assets:Monzo  -2.50 GBP = 995.00 GBP\n    \
+        Expenses:Coffee\n\n\
+        2022/01/05 NETFLIX.COM\n    Assets:Monzo  £-19.99 = 975.01 GBP\n    \
+        Expenses:Subscriptions\n\n\
+        2022/01/05 Account fee\n    Assets:Monzo  -5.00 GBP\n    Expenses:Fees\n";
+    let entries = match parse_ledger("statement", journal, "Assets:Monzo", "GBP") {
+        Ok(entries) => entries,
+        Err(e) => return println!("{}", e),
+    };
+    for entry in &entries {
+        println!(
+            "{} {} {} {:?}",
+            entry.created, entry.description, entry.amount, entry.account_balance
+        );
+    }
+
+    let reconciliation = match_statement(&entries, &monzo, 3);
+    for pair in &reconciliation.matched {
+        println!(
+            "matched {} with {} ({} days apart)",
+            pair.entry.description, pair.transaction.id, pair.days_apart
+        );
+    }
+    for entry in &reconciliation.unmatched_entries {
+        println!("not in monzo: {}", entry.description);
+    }
+    for transaction in &reconciliation.unmatched_transactions {
+        println!("not on the statement: {}", transaction.id);
+    }
+    for drift in &reconciliation.drifts {
+        println!("drift at {}: {}", drift.transaction.id, drift.difference);
+    }
+    for duplicate in duplicate_charges(&monzo, 10) {
+        println!(
+            "duplicate {} {} {}m apart",
+            duplicate.first.id, duplicate.second.id, duplicate.minutes_apart
+        );
+    }
+}
+
+pub fn pending_transactions() {
+    let now = Time::now();
+    let days_ago = |days: i64| now.add(&chrono::Duration::days(-days)).as_iso_8601_string();
+    let monzo = |id: &str, created: &str, amount: i32, settled: &str| -> Transaction {
+        serde_json::from_value(serde_json::json!({
+            "account_id": "acc_1",
+            "account_balance": null,
+            "amount": amount,
+            "created": created,
+            "currency": "GBP",
+            "local_amount": amount * 117 / 100,
+            "local_currency": "EUR",
+            "amount_is_pending": false,
+            "settled": settled,
+            "updated": created,
+            "description": "HOTEL",
+            "id": id,
+            "merchant": null,
+            "counterparty": null,
+            "decline_reason": null,
+            "metadata": {},
+        }))
+        .unwrap()
+    };
+    let settled = days_ago(9);
+    let mut cached = vec![
+        monzo("tx_1", &days_ago(10), -1250, &settled),
+        monzo("tx_2", &days_ago(8), -20000, ""),
+        monzo("tx_3", &days_ago(1), -300, ""),
+    ];
+    // transactions cached before these fields were stored have neither time, and are settled
+    cached.push(transaction("tx_4", &days_ago(1), -100, "COFFEE"));
+    for transaction in &cached {
+        println!(
+            "{} pending={} foreign={:?}",
+            transaction.id,
+            transaction.is_pending(),
+            transaction.foreign_amount()
+        );
+    }
+
+    let mut user = User::new(access_token_response("user"), vec![]);
+    user.cache_transactions(&cached);
+    // the resync goes back to tx_2, rather than two days before tx_3
+    let since = user.resync_since("acc_1");
+    println!(
+        "resync from tx_2: {}",
+        since.as_iso_8601_string() == cached[1].created.as_iso_8601_string()
+    );
+
+    // tx_2 settles for a different amount, tx_3 is still pending
+    let fetched = vec![
+        monzo("tx_2", &days_ago(8), -18005, &days_ago(6)),
+        cached[2].clone(),
+    ];
+    let config = crate::types::config::DaemonConfig::default();
+    for event in transaction_events(&cached.iter().collect::<Vec<_>>(), &fetched, &config) {
+        println!("{}: {}", event.kind(), event.message());
+    }
+    user.cache_transactions(&fetched);
+    println!(
+        "cached tx_2: amount={} pending={}",
+        user.transactions[1].amount,
+        user.transactions[1].is_pending()
+    );
+}
+
+pub fn fx_report() {
+    let abroad = |id: &str, created: &str, amount: i32, local_amount: i32, currency: &str| {
+        let mut transaction = transaction(id, created, amount, "ABROAD");
+        transaction.local_amount = local_amount;
+        transaction.local_currency = currency.to_owned();
+        transaction
+    };
+    let mut transactions = vec![
+        abroad("tx_1", "2022-06-01T10:00:00.0Z", -1000, -1170, "EUR"),
+        abroad("tx_2", "2022-06-03T10:00:00.0Z", -2000, -2300, "EUR"),
+        abroad("tx_3", "2022-06-02T10:00:00.0Z", -500, -800, "JPY"),
+        abroad("tx_4", "2022-06-20T10:00:00.0Z", -4000, -4800, "EUR"),
+        // a refund, a declined payment and spending at home aren't in any trip
+        abroad("tx_5", "2022-06-03T12:00:00.0Z", 1000, 1170, "EUR"),
+        abroad("tx_6", "2022-06-03T13:00:00.0Z", -1000, -1170, "EUR"),
+        transaction("tx_7", "2022-06-02T10:00:00.0Z", -300, "COFFEE"),
+    ];
+    transactions[5].decline_reason = Some("INSUFFICIENT_FUNDS".to_owned());
+
+    let rates =
+        ReferenceRates::parse("Date,Currency,Rate\n2022-05-31,EUR,1.18\n2022-06-19,eur,1.20\n")
+            .unwrap();
+    let trips = fx_trips(&transactions, Some(&rates), 3);
+    for trip in &trips {
+        println!(
+            "{} {} to {}: {} transactions spent={} cost={} rate={:.4} reference={:?} extra={:?}",
+            trip.local_currency,
+            trip.start,
+            trip.end,
+            trip.transactions.len(),
+            trip.local_spent,
+            trip.spent,
+            trip.average_rate,
+            trip.reference_cost,
+            trip.extra_cost()
+        );
+        for fx in &trip.transactions {
+            println!(
+                "  {} rate={:.4} reference_rate={:?} extra={:?}",
+                fx.id,
+                fx.rate,
+                fx.reference_rate,
+                fx.extra_cost()
+            );
+        }
+    }
+    for spending in fx_currencies(&trips) {
+        println!(
+            "{}: {} trips, spent={} cost={} rate={:.4} reference={:?}",
+            spending.local_currency,
+            spending.trips,
+            spending.local_spent,
+            spending.spent,
+            spending.average_rate,
+            spending.reference_cost
+        );
+    }
+    // a shorter gap splits the first EUR trip
+    println!("gap 1: {} trips", fx_trips(&transactions, None, 1).len());
+    if let Err(e) = ReferenceRates::parse("date,currency,rate\n2022-06-01,EUR,x\n") {
+        println!("{}", e);
+    }
+}
+
+pub fn reauthorization() {
+    let mut user = User::new(
+        access_token_response("user"),
+        vec![
+            account("acc_1", "uk_retail", false, &["Alex"]),
+            account("import_barclays", "import", false, &[]),
+        ],
+    );
+    let groceries = transaction("tx_1", "2022-01-03T12:00:00.0Z", -3000, "TESCO");
+    let split = Split::new(&groceries, &[("sam".to_owned(), None)], true).unwrap();
+    user.splits
+        .splits
+        .insert(split.transaction_id.clone(), split);
+    user.cache_transactions(&[groceries]);
+    user.serve_token = Some("serve".to_owned());
+
+    let print = |user: &User| {
+        println!(
+            "accounts={:?} splits={} transactions={} serve_token={:?}",
+            user.accounts
+                .iter()
+                .map(|account| (&account.id, account.closed))
+                .collect::<Vec<_>>(),
+            user.splits.splits.len(),
+            user.transactions.len(),
+            user.serve_token
+        );
+    };
+    // the same user keeps everything that is only kept locally
+    let mut reauthorized = user.clone();
+    reauthorized.reauthorize(
+        access_token_response("user"),
+        vec![
+            account("acc_1", "uk_retail", false, &["Alex"]),
+            account("acc_2", "uk_retail_joint", false, &["Alex", "Sam"]),
+        ],
+    );
+    print(&reauthorized);
+    // someone else starts again
+    user.reauthorize(
+        access_token_response("someone else"),
+        vec![account("acc_3", "uk_retail", false, &["Kim"])],
+    );
+    print(&user);
+}
+
+pub fn synced_user_merging() {
+    let mut running = User::new(access_token_response("user"), vec![]);
+    running.cache_transactions(&[transaction(
+        "tx_1",
+        "2022-01-01T09:00:00.0Z",
+        -450,
+        "PRET A MANGER",
+    )]);
+    // another command splits a transaction and imports an account while `daemon` is running
+    let mut stored = running.clone();
+    let groceries = transaction("tx_2", "2022-01-02T09:00:00.0Z", -3000, "TESCO");
+    let split = Split::new(&groceries, &[("sam".to_owned(), None)], true).unwrap();
+    stored
+        .splits
+        .splits
+        .insert(split.transaction_id.clone(), split);
+    stored
+        .accounts
+        .push(account("import_barclays", "import", false, &[]));
+    stored.transactions[0]
+        .metadata
+        .insert("notes".to_owned(), "lunch".to_owned());
+
+    // `daemon` refreshes its token and fetches a new transaction
+    running.access_token = crate::types::user::AccessToken::new(access_token_response("user"));
+    running.access_token.token = "refreshed".to_owned();
+    let fetched = [groceries];
+    running.cache_transactions(&fetched);
+    stored.merge_synced(&running, &fetched);
+    println!(
+        "token={} splits={} accounts={} transactions={:?} notes={:?}",
+        stored.access_token.token,
+        stored.splits.splits.len(),
+        stored.accounts.len(),
+        stored
+            .transactions
+            .iter()
+            .map(|transaction| &transaction.id)
+            .collect::<Vec<_>>(),
+        stored.transactions[0].metadata.get("notes")
+    );
+}
@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+use crate::types::*;
+
+/// The user's settings, stored in config.json
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Config {
+    /// Aliases for accounts, each one maps a name to an account selector such as `****7686`
+    #[serde(default)]
+    pub accounts: BTreeMap<String, String>,
+    #[serde(default)]
+    pub daemon: DaemonConfig,
+    /// Statements from other banks, by the name of the account they are imported into
+    #[serde(default)]
+    pub imports: BTreeMap<String, ImportSource>,
+    #[serde(default)]
+    pub ledger: LedgerConfig,
+    /// The people that bills are split with, by the name used in `split`
+    #[serde(default)]
+    pub people: BTreeMap<String, Person>,
+    /// Rules that give transactions custom categories and tags
+    #[serde(default)]
+    pub rules: Vec<rules::Rule>,
+    /// Named templates for displaying transactions
+    #[serde(default)]
+    pub templates: BTreeMap<String, String>,
+    #[serde(default)]
+    pub theme: Theme,
+}
+
+impl Config {
+    /// The ledger settings for `account`, transactions from an imported statement are posted
+    /// to the ledger account of their import instead of `ledger.account`
+    pub fn ledger_for(&self, account: &Account) -> LedgerConfig {
+        let mut ledger = self.ledger.clone();
+        if let Some(name) = account.import_source() {
+            ledger.account = match self.imports.get(name) {
+                Some(ImportSource {
+                    ledger_account: Some(ledger_account),
+                    ..
+                }) => ledger_account.clone(),
+                _ => {
+                    // `barclays` becomes `Assets:Barclays`
+                    let mut account = String::from("Assets:");
+                    let mut chars = name.chars();
+                    if let Some(first) = chars.next() {
+                        account.push(first.to_ascii_uppercase());
+                        account.extend(chars);
+                    }
+                    account
+                }
+            };
+        }
+        ledger
+    }
+}
+
+/// What the `daemon` command watches for, and what it does when it sees something
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct DaemonConfig {
+    /// The number of seconds between syncs
+    pub interval: u64,
+    /// Debits of at least this many minor currency units are large
+    pub large_debit: Option<i32>,
+    /// Balances below this many minor currency units are low
+    pub low_balance: Option<i32>,
+    pub hooks: Vec<Hook>,
+}
+
+impl Default for DaemonConfig {
+    fn default() -> Self {
+        Self {
+            interval: 300,
+            large_debit: None,
+            low_balance: None,
+            hooks: vec![],
+        }
+    }
+}
+
+/// Something to do when the daemon sees an event, every action that is set is done
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Hook {
+    /// The events that trigger this hook, e.g. `declined`, an empty list means every event
+    pub events: Vec<String>,
+    /// A shell command, the event is passed in the `MONZO_EVENT`, `MONZO_MESSAGE` and
+    /// `MONZO_JSON` environment variables
+    pub command: Option<String>,
+    /// Shows a desktop notification with `notify-send`
+    pub notify: bool,
+    /// A url that the event is POSTed to as json
+    pub url: Option<String>,
+}
+
+/// How to recognise transfers from someone that bills are split with, any detail that is set
+/// must match the counterparty of the transfer
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Person {
+    /// The name on their transfers, compared case insensitively
+    pub name: Option<String>,
+    pub sort_code: Option<String>,
+    pub account_number: Option<String>,
+    /// Their Monzo user id, for transfers between Monzo users
+    pub user_id: Option<String>,
+}
+
+impl Person {
+    /// Whether a transfer with `counterparty` is from this person
+    pub fn matches(&self, counterparty: &Counterparty) -> bool {
+        let same = |detail: &Option<String>, other: &Option<String>, ignore_case: bool| {
+            match (detail, other) {
+                (None, _) => true,
+                (Some(detail), Some(other)) if ignore_case => detail.eq_ignore_ascii_case(other),
+                (Some(detail), Some(other)) => {
+                    detail.replace('-', "") == other.replace('-', "")
+                }
+                (Some(_), None) => false,
+            }
+        };
+        (self.name.is_some()
+            || self.sort_code.is_some()
+            || self.account_number.is_some()
+            || self.user_id.is_some())
+            && same(&self.name, &counterparty.name, true)
+            && same(&self.sort_code, &counterparty.sort_code, false)
+            && same(&self.account_number, &counterparty.account_number, false)
+            && same(&self.user_id, &counterparty.user_id, false)
+    }
+
+    /// Whether a transfer with `counterparty` is from someone called `name`, for people
+    /// without details in the config. The first name is enough, so `sam` matches `Sam Smith`
+    pub fn name_matches(name: &str, counterparty: &Counterparty) -> bool {
+        match &counterparty.name {
+            Some(counterparty_name) => {
+                counterparty_name.eq_ignore_ascii_case(name)
+                    || counterparty_name
+                        .split_whitespace()
+                        .next()
+                        .is_some_and(|first_name| first_name.eq_ignore_ascii_case(name))
+            }
+            None => false,
+        }
+    }
+}
+
+/// The format of a statement from another bank
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatementFormat {
+    Csv,
+    Ofx,
+}
+
+/// How to read the statements of an account at another bank. The columns are only used for
+/// csv statements and are the names in the header row
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ImportSource {
+    /// Guessed from the file extension when it isn't set
+    pub format: Option<StatementFormat>,
+    /// The currency of csv statements, ofx statements say which currency they are in
+    pub currency: String,
+    pub sort_code: String,
+    pub account_number: String,
+    /// The account that transactions are posted to for plain-text accounting, defaults to
+    /// `Assets:<Name>`
+    pub ledger_account: Option<String>,
+    pub delimiter: char,
+    pub date: String,
+    /// A `strftime` format such as `%d/%m/%Y`, `%Y-%m-%d` is also always accepted
+    pub date_format: String,
+    pub description: String,
+    /// A signed amount, for statements that don't split money in and out into two columns
+    pub amount: Option<String>,
+    /// Money out, as a positive number
+    pub debit: Option<String>,
+    /// Money in
+    pub credit: Option<String>,
+    /// The balance after each transaction
+    pub balance: Option<String>,
+    /// Flips the sign of `amount`, for statements that show money out as positive
+    pub negate: bool,
+}
+
+impl Default for ImportSource {
+    fn default() -> Self {
+        Self {
+            format: None,
+            currency: "GBP".to_owned(),
+            sort_code: String::new(),
+            account_number: String::new(),
+            ledger_account: None,
+            delimiter: ',',
+            date: "Date".to_owned(),
+            date_format: "%d/%m/%Y".to_owned(),
+            description: "Description".to_owned(),
+            amount: None,
+            debit: None,
+            credit: None,
+            balance: None,
+            negate: false,
+        }
+    }
+}
+
+/// How transactions are mapped to accounts for plain-text accounting
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LedgerConfig {
+    /// The account that monzo transactions are posted to, e.g. `Assets:Monzo`
+    pub account: String,
+    /// Maps a category to an account, categories that aren't in here are posted to
+    /// `Expenses:<Category>`
+    pub categories: BTreeMap<String, String>,
+    /// The account for money going out that has no category (e.g. bank transfers)
+    pub uncategorised_expenses: String,
+    /// The account for money coming in that has no category
+    pub uncategorised_income: String,
+    /// The account that the balance before the first exported transaction comes from
+    pub opening_balances: String,
+}
+
+impl Default for LedgerConfig {
+    fn default() -> Self {
+        Self {
+            account: "Assets:Monzo".to_owned(),
+            categories: BTreeMap::new(),
+            uncategorised_expenses: "Expenses:Uncategorised".to_owned(),
+            uncategorised_income: "Income:Uncategorised".to_owned(),
+            opening_balances: "Equity:Opening-Balances".to_owned(),
+        }
+    }
+}
+
+impl LedgerConfig {
+    /// The account that the other side of `transaction` is posted to
+    pub fn account_for(&self, transaction: &Transaction) -> String {
+        let category = match transaction.category() {
+            Some(category) => category,
+            None if transaction.amount < 0 => return self.uncategorised_expenses.clone(),
+            None => return self.uncategorised_income.clone(),
+        };
+        if let Some(account) = self.categories.get(category) {
+            return account.clone();
+        }
+        // `eating_out` becomes `Expenses:EatingOut`
+        let mut account = String::from("Expenses:");
+        for word in category.split(|c: char| !c.is_ascii_alphanumeric()) {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                account.push(first.to_ascii_uppercase());
+                account.extend(chars);
+            }
+        }
+        account
+    }
+}
+
+/// The styles used for displaying transactions
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Theme {
+    pub credit: Style,
+    pub debit: Style,
+    pub declined: Style,
+    pub pending: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            credit: Style {
+                colour: Some(Colour(20, 255, 20)),
+                ..Default::default()
+            },
+            debit: Style {
+                colour: Some(Colour(255, 20, 20)),
+                ..Default::default()
+            },
+            declined: Style {
+                strikethrough: true,
+                ..Default::default()
+            },
+            pending: Style {
+                colour: Some(Colour(150, 150, 150)),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Style {
+    pub colour: Option<Colour>,
+    pub bold: bool,
+    pub strikethrough: bool,
+}
+
+/// A 24-bit colour, written as `#rrggbb` in the config
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Colour(pub u8, pub u8, pub u8);
+
+impl TryFrom<String> for Colour {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        let err = || format!("`{}` is not a colour, colours look like `#14ff14`", s);
+        let hex = s.strip_prefix('#').ok_or_else(err)?;
+        // checking the digits first also means that slicing can't split a character
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(err());
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| err());
+        Ok(Colour(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl From<Colour> for String {
+    fn from(colour: Colour) -> Self {
+        format!("#{:02x}{:02x}{:02x}", colour.0, colour.1, colour.2)
+    }
+}
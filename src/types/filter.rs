@@ -0,0 +1,370 @@
+use lazy_static::lazy_static;
+
+use super::error::BadArgumentError;
+use super::Transaction;
+
+/// A field of a `Transaction` that can be compared against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    Amount,
+    Category,
+    Counterparty,
+    Currency,
+    Description,
+    Id,
+    Merchant,
+    Notes,
+    Tag,
+}
+
+impl Field {
+    fn from_str(s: &str) -> Result<Self, BadArgumentError> {
+        Ok(match s {
+            "amount" => Self::Amount,
+            "category" => Self::Category,
+            "counterparty" => Self::Counterparty,
+            "currency" => Self::Currency,
+            "description" => Self::Description,
+            "id" => Self::Id,
+            "merchant" => Self::Merchant,
+            "notes" => Self::Notes,
+            "tag" => Self::Tag,
+            _ => {
+                return Err(BadArgumentError(format!(
+                    "`{}` is not a field that can be filtered on",
+                    s
+                )))
+            }
+        })
+    }
+
+    /// The string values of this field for a transaction, a comparison matches if any of
+    /// them match
+    fn values(&self, transaction: &Transaction) -> Vec<String> {
+        match self {
+            Self::Amount => vec![transaction.amount.to_string()],
+            Self::Category => transaction.category().map(str::to_owned).into_iter().collect(),
+            Self::Counterparty => match &transaction.counterparty {
+                Some(counterparty) => [
+                    &counterparty.name,
+                    &counterparty.account_number,
+                    &counterparty.sort_code,
+                    &counterparty.user_id,
+                ]
+                .iter()
+                .filter_map(|value| (*value).clone())
+                .collect(),
+                None => vec![],
+            },
+            Self::Currency => vec![transaction.currency.clone()],
+            Self::Description => vec![transaction.description.clone()],
+            Self::Id => vec![transaction.id.clone()],
+            Self::Merchant => transaction
+                .merchant
+                .iter()
+                .map(|merchant| merchant.name.clone())
+                .collect(),
+            Self::Notes => transaction
+                .metadata
+                .get("notes")
+                .cloned()
+                .into_iter()
+                .collect(),
+            Self::Tag => transaction.tags.clone(),
+        }
+    }
+}
+
+/// How a field is compared against a value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Contains,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn from_str(s: &str) -> Self {
+        match s {
+            "=" => Self::Equal,
+            "!=" => Self::NotEqual,
+            "~" => Self::Contains,
+            "<" => Self::Less,
+            "<=" => Self::LessOrEqual,
+            ">" => Self::Greater,
+            _ => Self::GreaterOrEqual,
+        }
+    }
+
+    fn is_ordering(&self) -> bool {
+        matches!(
+            self,
+            Self::Less | Self::LessOrEqual | Self::Greater | Self::GreaterOrEqual
+        )
+    }
+}
+
+/// A parsed filter expression that can be evaluated over a `Transaction`
+#[derive(Debug, Clone)]
+pub enum Filter {
+    /// Matches every transaction
+    All,
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Compare(Field, Comparison, String),
+    /// Case-insensitive search over the description and notes
+    Text(String),
+    Declined,
+    Pending,
+    HasNotes,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    OpenParen,
+    CloseParen,
+    And,
+    Or,
+    Not,
+    Word(String),
+    Quoted(String),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, BadArgumentError> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' | '\n' => {
+                chars.next();
+            }
+            '(' | ')' | '&' | '|' | '!' => {
+                chars.next();
+                tokens.push(match c {
+                    '(' => Token::OpenParen,
+                    ')' => Token::CloseParen,
+                    '&' => Token::And,
+                    '|' => Token::Or,
+                    _ => Token::Not,
+                });
+                // allow `&&` and `||`
+                if (c == '&' || c == '|') && chars.peek() == Some(&c) {
+                    chars.next();
+                }
+            }
+            _ => {
+                let mut word = String::new();
+                let quoted = c == '"';
+                while let Some(&c) = chars.peek() {
+                    match c {
+                        '"' => {
+                            chars.next();
+                            loop {
+                                match chars.next() {
+                                    Some('"') => break,
+                                    Some(c) => word.push(c),
+                                    None => {
+                                        return Err(BadArgumentError(format!(
+                                            "unclosed quote in filter `{}`",
+                                            s
+                                        )))
+                                    }
+                                }
+                            }
+                        }
+                        ' ' | '\t' | '\n' | '(' | ')' | '&' | '|' => break,
+                        c => {
+                            word.push(c);
+                            chars.next();
+                        }
+                    }
+                }
+                tokens.push(if quoted {
+                    Token::Quoted(word)
+                } else {
+                    match word.to_lowercase().as_str() {
+                        "and" => Token::And,
+                        "or" => Token::Or,
+                        "not" => Token::Not,
+                        _ => Token::Word(word),
+                    }
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn err(&self, reason: &str) -> BadArgumentError {
+        BadArgumentError(format!(
+            "`{}` is not a valid filter -- {}",
+            self.source, reason
+        ))
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// `or := and (("or" | "|") and)*`
+    fn parse_or(&mut self) -> Result<Filter, BadArgumentError> {
+        let mut filter = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            filter = Filter::Or(Box::new(filter), Box::new(self.parse_and()?));
+        }
+        Ok(filter)
+    }
+
+    /// `and := unary (("and" | "&")? unary)*`, terms next to each other are and-ed together
+    fn parse_and(&mut self) -> Result<Filter, BadArgumentError> {
+        let mut filter = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                Some(Token::Or) | Some(Token::CloseParen) | None => break,
+                _ => {}
+            }
+            filter = Filter::And(Box::new(filter), Box::new(self.parse_unary()?));
+        }
+        Ok(filter)
+    }
+
+    /// `unary := ("not" | "!") unary | "(" or ")" | term`
+    fn parse_unary(&mut self) -> Result<Filter, BadArgumentError> {
+        match self.next() {
+            Some(Token::Not) => Ok(Filter::Not(Box::new(self.parse_unary()?))),
+            Some(Token::OpenParen) => {
+                let filter = self.parse_or()?;
+                if self.next() != Some(Token::CloseParen) {
+                    return Err(self.err("missing `)`"));
+                }
+                Ok(filter)
+            }
+            Some(Token::Quoted(text)) => Ok(Filter::Text(text.to_lowercase())),
+            Some(Token::Word(word)) => self.parse_term(&word),
+            Some(_) => Err(self.err("expected a term")),
+            None => Err(self.err("unexpected end of filter")),
+        }
+    }
+
+    fn parse_term(&self, word: &str) -> Result<Filter, BadArgumentError> {
+        lazy_static! {
+            /// splits a comparison into field, operator and value
+            static ref RE: regex::Regex =
+                regex::Regex::new(r"^([a-zA-Z\-_]+)(!=|<=|>=|=|~|<|>)(.*)$").unwrap();
+        }
+        if let Some(captures) = RE.captures(word) {
+            let field = Field::from_str(&captures[1].to_lowercase())?;
+            let comparison = Comparison::from_str(&captures[2]);
+            let value = captures[3].to_owned();
+            if field == Field::Amount {
+                if value.parse::<i64>().is_err() {
+                    return Err(self.err(&format!(
+                        "`{}` is not a valid amount in minor currency units",
+                        value
+                    )));
+                }
+            } else if comparison.is_ordering() {
+                return Err(self.err(&format!(
+                    "`{}` cannot be compared with `<` or `>`",
+                    &captures[1]
+                )));
+            }
+            return Ok(Filter::Compare(field, comparison, value.to_lowercase()));
+        }
+        Ok(match word.to_lowercase().as_str() {
+            "declined" => Filter::Declined,
+            "pending" => Filter::Pending,
+            "has-notes" => Filter::HasNotes,
+            text => Filter::Text(text.to_owned()),
+        })
+    }
+}
+
+impl Filter {
+    /// Parses a filter expression such as `merchant~tesco and (amount<-5000 or declined)`
+    /// An empty expression matches every transaction
+    pub fn parse(s: &str) -> Result<Self, BadArgumentError> {
+        let mut parser = Parser {
+            source: s,
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+        if parser.tokens.is_empty() {
+            return Ok(Self::All);
+        }
+        let filter = parser.parse_or()?;
+        if parser.position < parser.tokens.len() {
+            return Err(parser.err("unexpected `)`"));
+        }
+        Ok(filter)
+    }
+
+    /// Returns true if `transaction` is matched by this filter
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        match self {
+            Self::All => true,
+            Self::And(a, b) => a.matches(transaction) && b.matches(transaction),
+            Self::Or(a, b) => a.matches(transaction) || b.matches(transaction),
+            Self::Not(filter) => !filter.matches(transaction),
+            Self::Compare(field, comparison, value) => {
+                let values = field.values(transaction);
+                if *field == Field::Amount {
+                    let value = value.parse::<i64>().unwrap_or(0);
+                    let amount = transaction.amount as i64;
+                    return match comparison {
+                        Comparison::Equal | Comparison::Contains => amount == value,
+                        Comparison::NotEqual => amount != value,
+                        Comparison::Less => amount < value,
+                        Comparison::LessOrEqual => amount <= value,
+                        Comparison::Greater => amount > value,
+                        Comparison::GreaterOrEqual => amount >= value,
+                    };
+                }
+                let mut values = values.iter().map(|v| v.to_lowercase());
+                match comparison {
+                    Comparison::NotEqual => values.all(|v| &v != value),
+                    Comparison::Contains => values.any(|v| v.contains(value.as_str())),
+                    _ => values.any(|v| &v == value),
+                }
+            }
+            Self::Text(text) => {
+                transaction
+                    .description
+                    .to_lowercase()
+                    .contains(text.as_str())
+                    || transaction
+                        .metadata
+                        .get("notes")
+                        .is_some_and(|notes| notes.to_lowercase().contains(text.as_str()))
+            }
+            Self::Declined => transaction.decline_reason.is_some(),
+            Self::Pending => transaction.is_pending(),
+            Self::HasNotes => transaction
+                .metadata
+                .get("notes")
+                .is_some_and(|notes| !notes.is_empty()),
+        }
+    }
+}
@@ -4,7 +4,11 @@ use crate::types::time::Time;
 use crate::*;
 use crate::util::FmtCurrencyOptions;
 
+pub mod config;
 pub mod error;
+pub mod filter;
+pub mod rules;
+pub mod split;
 pub mod time;
 pub mod user;
 
@@ -52,6 +56,26 @@ impl AccessTokenRequest {
     }
 }
 
+/// Request for a new access token using a refresh token
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RefreshTokenRequest {
+    pub grant_type: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub refresh_token: String,
+}
+
+impl RefreshTokenRequest {
+    pub fn new(refresh_token: String) -> Self {
+        Self {
+            grant_type: "refresh_token".to_owned(),
+            client_id: consts::CLIENT_ID.to_owned(),
+            client_secret: consts::CLIENT_SECRET.to_owned(),
+            refresh_token,
+        }
+    }
+}
+
 /// The authorization code and state token returned after authorizing through
 /// monzo
 #[derive(Deserialize, Default, Clone)]
@@ -76,6 +100,9 @@ pub struct AccessTokenResponse {
 pub struct Account {
     pub id: String,
     pub closed: bool,
+    /// e.g. `uk_retail` or `uk_retail_joint`
+    #[serde(rename = "type", default)]
+    pub account_type: String,
     pub created: Time, // TODO: change to a time type
     pub description: String,
     pub currency: String,
@@ -85,6 +112,17 @@ pub struct Account {
     pub sort_code: String,
 }
 
+impl Account {
+    /// The name of the import in the config, for accounts at other banks whose transactions
+    /// are imported from statements rather than fetched from Monzo
+    pub fn import_source(&self) -> Option<&str> {
+        self.id.strip_prefix(IMPORT_ACCOUNT_PREFIX)
+    }
+}
+
+/// The start of the id of an account whose transactions are imported from statements
+pub const IMPORT_ACCOUNT_PREFIX: &str = "import_";
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Owner {
     pub user_id: String,
@@ -108,16 +146,26 @@ pub struct Balance {
 }
 
 impl Balance {
-    /// Returns a nicely formatted string for this balance (only works for GBP)
+    /// Returns a nicely formatted string for this balance
     pub fn balance_string(&self) -> String {
         self.prettify_minor_currency_units(self.balance)
     }
 
-    /// Returns a nicely formatted string for this total_balance (only works for GBP)
+    /// Returns a nicely formatted string for this total_balance
     pub fn total_balance_string(&self) -> String {
         self.prettify_minor_currency_units(self.total_balance)
     }
 
+    /// Returns a nicely formatted string for this balance_including_flexible_savings
+    pub fn balance_including_flexible_savings_string(&self) -> String {
+        self.prettify_minor_currency_units(self.balance_including_flexible_savings)
+    }
+
+    /// Returns a nicely formatted string for this spend_today
+    pub fn spend_today_string(&self) -> String {
+        self.prettify_minor_currency_units(self.spend_today)
+    }
+
     /// Converts an integer of minor currency units to a string that may contain
     /// some delimeter to separate major and minor units with a currency symbol
     fn prettify_minor_currency_units(&self, amount: i32) -> String {
@@ -125,18 +173,108 @@ impl Balance {
     }
 }
 
+/// A savings pot belonging to an account
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Pot {
+    pub id: String,
+    pub name: String,
+    pub balance: i32,
+    pub currency: String,
+    #[serde(default)]
+    pub deleted: bool,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Transaction {
+    #[serde(default)]
+    pub account_id: String,
     pub account_balance: Option<i32>,
     pub amount: i32,
     pub created: Time,
     pub currency: String,
+    /// The amount in the currency it was spent in, which is `currency` unless it was spent
+    /// abroad
+    #[serde(default)]
+    pub local_amount: i32,
+    #[serde(default)]
+    pub local_currency: String,
+    /// Whether `amount` may still change, e.g. for a hotel or car hire hold
+    #[serde(default)]
+    pub amount_is_pending: bool,
+    /// When the transaction settled, this is `None` while it is pending
+    #[serde(default, deserialize_with = "time::deserialize_optional")]
+    pub settled: Option<Time>,
+    #[serde(default, deserialize_with = "time::deserialize_optional")]
+    pub updated: Option<Time>,
     pub description: String,
     pub id: String,
     pub merchant: Option<Merchant>,
     pub counterparty: Option<Counterparty>,
     pub decline_reason: Option<String>,
     pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// The category given by the first matching rule in the config, see `rules::Rules`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_category: Option<String>,
+    /// The tags given by the rules in the config
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl Transaction {
+    /// The name of whoever this transaction was with, this is the merchant name or the
+    /// counterparty name, falling back to the description
+    pub fn payee_name(&self) -> String {
+        if let Some(merchant) = &self.merchant {
+            merchant.name.clone()
+        } else if let Some(name) = self.counterparty.as_ref().and_then(|c| c.name.clone()) {
+            name
+        } else {
+            self.description.clone()
+        }
+    }
+
+    /// Whether this transaction hasn't settled yet, or its amount may still change.
+    /// Transactions that were cached before `settled` was kept have no `updated` time either,
+    /// and are taken to have settled
+    pub fn is_pending(&self) -> bool {
+        self.decline_reason.is_none()
+            && (self.amount_is_pending || (self.settled.is_none() && self.updated.is_some()))
+    }
+
+    /// The amount and currency that this transaction was spent in, if it isn't the currency
+    /// of the account
+    pub fn foreign_amount(&self) -> Option<(i32, &str)> {
+        if self.local_currency.is_empty() || self.local_currency == self.currency {
+            None
+        } else {
+            Some((self.local_amount, &self.local_currency))
+        }
+    }
+
+    /// The category of this transaction, a category from the rules in the config takes
+    /// precedence over the merchant category
+    pub fn category(&self) -> Option<&str> {
+        match &self.custom_category {
+            Some(category) => Some(category),
+            None => self
+                .merchant
+                .as_ref()
+                .map(|merchant| merchant.category.as_str()),
+        }
+    }
+}
+
+/// A file attached to a transaction, e.g. a photo of a receipt
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Attachment {
+    pub id: String,
+    /// The id of the transaction the file is attached to
+    pub external_id: String,
+    pub file_url: String,
+    pub file_type: String,
+    pub created: Time,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -170,11 +308,27 @@ pub struct Address {
     pub region: String,
 }
 
+impl Address {
+    /// The address on one line, e.g. `1 High Street, London, N1 1AA, GBR`
+    pub fn one_line(&self) -> String {
+        [&self.address, &self.city, &self.postcode, &self.country]
+            .iter()
+            .map(|part| part.replace('\n', ", "))
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum OutputType {
     Json,
     Csv,
     Display,
+    Ofx,
+    Qif,
+    Ledger,
+    Beancount,
 }
 
 impl OutputType {
@@ -183,6 +337,10 @@ impl OutputType {
             "json" => Self::Json,
             "csv" => Self::Csv,
             "display" => Self::Display,
+            "ofx" => Self::Ofx,
+            "qif" => Self::Qif,
+            "ledger" => Self::Ledger,
+            "beancount" => Self::Beancount,
             _ => {
                 return Err(error::InvalidArgumentError(format!(
                     "`{}` is not a valid output format",
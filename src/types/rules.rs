@@ -0,0 +1,133 @@
+use super::error::BadArgumentError;
+use super::Transaction;
+use crate::*;
+
+/// A rule that gives matching transactions a category and tags, every condition that is set
+/// must match
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Rule {
+    /// A regex that the description must match, case insensitively
+    pub description: Option<String>,
+    /// A regex that the merchant name must match, case insensitively
+    pub merchant: Option<String>,
+    pub sort_code: Option<String>,
+    pub account_number: Option<String>,
+    /// The smallest amount that matches, in minor currency units. Debits are negative
+    pub min_amount: Option<i32>,
+    /// The largest amount that matches, in minor currency units
+    pub max_amount: Option<i32>,
+    pub category: Option<String>,
+    pub tags: Vec<String>,
+}
+
+/// A `Rule` with its regexes compiled
+struct CompiledRule<'a> {
+    rule: &'a Rule,
+    description: Option<regex::Regex>,
+    merchant: Option<regex::Regex>,
+}
+
+impl<'a> CompiledRule<'a> {
+    fn new(rule: &'a Rule, index: usize) -> Result<Self, BadArgumentError> {
+        let compile = |pattern: &Option<String>| {
+            pattern
+                .as_ref()
+                .map(|pattern| {
+                    regex::RegexBuilder::new(pattern)
+                        .case_insensitive(true)
+                        .build()
+                        .map_err(|e| {
+                            BadArgumentError(format!(
+                                "rule {} has an invalid regex `{}` -- {}",
+                                index + 1,
+                                pattern,
+                                e
+                            ))
+                        })
+                })
+                .transpose()
+        };
+        Ok(Self {
+            rule,
+            description: compile(&rule.description)?,
+            merchant: compile(&rule.merchant)?,
+        })
+    }
+
+    fn matches(&self, transaction: &Transaction) -> bool {
+        let same_number = |expected: &Option<String>, actual: Option<&String>| match expected {
+            Some(expected) => {
+                actual.is_some_and(|actual| actual.replace('-', "") == expected.replace('-', ""))
+            }
+            None => true,
+        };
+        let counterparty = transaction.counterparty.as_ref();
+        self.description
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(&transaction.description))
+            && self.merchant.as_ref().is_none_or(|regex| {
+                transaction
+                    .merchant
+                    .as_ref()
+                    .is_some_and(|merchant| regex.is_match(&merchant.name))
+            })
+            && same_number(
+                &self.rule.sort_code,
+                counterparty.and_then(|counterparty| counterparty.sort_code.as_ref()),
+            )
+            && same_number(
+                &self.rule.account_number,
+                counterparty.and_then(|counterparty| counterparty.account_number.as_ref()),
+            )
+            && self
+                .rule
+                .min_amount
+                .is_none_or(|min| transaction.amount >= min)
+            && self
+                .rule
+                .max_amount
+                .is_none_or(|max| transaction.amount <= max)
+    }
+}
+
+/// The rules in the config, ready to be applied to transactions
+pub struct Rules<'a> {
+    rules: Vec<CompiledRule<'a>>,
+}
+
+impl<'a> Rules<'a> {
+    pub fn new(rules: &'a [Rule]) -> Result<Self, BadArgumentError> {
+        Ok(Self {
+            rules: rules
+                .iter()
+                .enumerate()
+                .map(|(i, rule)| CompiledRule::new(rule, i))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Sets the custom category and tags of `transaction` from the rules that match it,
+    /// replacing what earlier rules set. The first matching rule with a category decides the
+    /// category and every matching rule adds its tags. Returns whether any rule matched
+    pub fn apply(&self, transaction: &mut Transaction) -> bool {
+        transaction.custom_category = None;
+        transaction.tags.clear();
+        let mut matched = false;
+        for rule in &self.rules {
+            if !rule.matches(transaction) {
+                continue;
+            }
+            matched = true;
+            if transaction.custom_category.is_none() {
+                transaction.custom_category = rule.rule.category.clone();
+            }
+            for tag in &rule.rule.tags {
+                if !transaction.tags.contains(tag) {
+                    transaction.tags.push(tag.clone());
+                }
+            }
+        }
+        matched
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeMap;
+
+use crate::types::config::Person;
+use crate::types::*;
+
+/// A transaction that is shared with other people
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Split {
+    pub transaction_id: String,
+    pub created: Time,
+    pub description: String,
+    pub currency: String,
+    /// The amount paid, in minor currency units. Refunds are negative
+    pub amount: i32,
+    /// person -> the part of `amount` that they owe, in minor currency units
+    pub shares: BTreeMap<String, i32>,
+}
+
+/// Money paid back by someone, towards what they owe
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Settlement {
+    /// The incoming transfer that paid it back, `None` when it was recorded by hand
+    pub transaction_id: Option<String>,
+    pub person: String,
+    pub created: Time,
+    pub currency: String,
+    pub amount: i32,
+}
+
+/// The shared transactions and what has been paid back, stored in user.json
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SplitLedger {
+    /// transaction id -> how it is split
+    pub splits: BTreeMap<String, Split>,
+    pub settlements: Vec<Settlement>,
+}
+
+/// What someone owes in one currency
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SplitBalance {
+    pub person: String,
+    pub currency: String,
+    /// The total of their shares
+    pub shared: i32,
+    /// The total they have paid back
+    pub settled: i32,
+    /// What they still owe, negative when you owe them
+    pub outstanding: i32,
+}
+
+impl Split {
+    /// Splits `transaction` between `people` and yourself. People that are given an amount
+    /// owe that much, the rest of the transaction is divided evenly between everyone else,
+    /// with you taking any remainder so that nobody is charged more than their share. If
+    /// `include_me` is false you aren't one of those people
+    pub fn new(
+        transaction: &Transaction,
+        people: &[(String, Option<i32>)],
+        include_me: bool,
+    ) -> Result<Self, error::BadArgumentError> {
+        if people.is_empty() {
+            return Err(error::BadArgumentError(
+                "give at least one person to split with".to_owned(),
+            ));
+        }
+        let amount = -transaction.amount;
+        let fixed: i32 = people.iter().filter_map(|(_, share)| *share).sum();
+        let even = people.iter().filter(|(_, share)| share.is_none()).count() as i32;
+        let remaining = amount - fixed;
+        let divisor = even + include_me as i32;
+        if remaining != 0 && remaining.signum() != amount.signum() {
+            return Err(error::BadArgumentError(format!(
+                "the shares add up to {}, which is more than the transaction's {}",
+                fixed, amount
+            )));
+        }
+        if divisor == 0 && remaining != 0 {
+            return Err(error::BadArgumentError(format!(
+                "the shares add up to {} but the transaction is {}",
+                fixed, amount
+            )));
+        }
+        let even_share = if divisor == 0 { 0 } else { remaining / divisor };
+
+        let mut shares = BTreeMap::new();
+        for (person, share) in people {
+            if shares
+                .insert(person.clone(), share.unwrap_or(even_share))
+                .is_some()
+            {
+                return Err(error::BadArgumentError(format!(
+                    "`{}` is given more than once",
+                    person
+                )));
+            }
+        }
+        if let Some((person, share)) = shares
+            .iter()
+            .find(|(_, share)| **share != 0 && share.signum() != amount.signum())
+        {
+            return Err(error::BadArgumentError(format!(
+                "`{}` can't have a share of {} of a transaction of {}",
+                person, share, amount
+            )));
+        }
+        // without you, the people that split evenly pick up the remainder a unit each
+        if !include_me {
+            let mut remainder = remaining - even_share * divisor;
+            for (person, share) in people {
+                if remainder == 0 {
+                    break;
+                }
+                if share.is_none() {
+                    *shares.get_mut(person).unwrap() += remainder.signum();
+                    remainder -= remainder.signum();
+                }
+            }
+        }
+        Ok(Self {
+            transaction_id: transaction.id.clone(),
+            created: transaction.created.clone(),
+            description: transaction.payee_name(),
+            currency: transaction.currency.clone(),
+            amount,
+            shares,
+        })
+    }
+}
+
+impl SplitLedger {
+    /// What everyone owes, by person and then currency
+    pub fn balances(&self) -> Vec<SplitBalance> {
+        let mut balances = BTreeMap::<(String, String), SplitBalance>::new();
+        let mut add = |person: &str, currency: &str, shared: i32, settled: i32| {
+            let balance = balances
+                .entry((person.to_owned(), currency.to_owned()))
+                .or_insert_with(|| SplitBalance {
+                    person: person.to_owned(),
+                    currency: currency.to_owned(),
+                    ..Default::default()
+                });
+            balance.shared += shared;
+            balance.settled += settled;
+            balance.outstanding += shared - settled;
+        };
+        for split in self.splits.values() {
+            for (person, share) in &split.shares {
+                add(person, &split.currency, *share, 0);
+            }
+        }
+        for settlement in &self.settlements {
+            add(
+                &settlement.person,
+                &settlement.currency,
+                0,
+                settlement.amount,
+            );
+        }
+        balances.into_values().collect()
+    }
+
+    /// When the oldest split that `person` hasn't paid back in `currency` was made, taking what
+    /// they have paid back as paying for the oldest splits first
+    fn earliest_unpaid(&self, person: &str, currency: &str) -> Option<&Time> {
+        let mut settled: i32 = self
+            .settlements
+            .iter()
+            .filter(|settlement| settlement.person == person && settlement.currency == currency)
+            .map(|settlement| settlement.amount)
+            .sum();
+        let mut shares: Vec<(&Time, i32)> = self
+            .splits
+            .values()
+            .filter(|split| split.currency == currency)
+            .filter_map(|split| {
+                split
+                    .shares
+                    .get(person)
+                    .map(|share| (&split.created, *share))
+            })
+            .collect();
+        shares.sort_by(|a, b| a.0.partial_cmp(b.0).unwrap());
+        for (created, share) in shares {
+            // refunds have negative shares, which count as paid back
+            if settled < share {
+                return Some(created);
+            }
+            settled -= share;
+        }
+        None
+    }
+
+    /// Records incoming transfers from people who owe money as settlements, up to what they
+    /// owe. `people` has the bank details of each person, anyone that isn't in it is matched
+    /// by the name on the transfer. Transfers from before the oldest split that someone hasn't
+    /// paid back can't settle it. Returns the new settlements
+    pub fn settle(
+        &mut self,
+        transactions: &[Transaction],
+        people: &BTreeMap<String, Person>,
+    ) -> Vec<Settlement> {
+        let mut outstanding: BTreeMap<(String, String), i32> = self
+            .balances()
+            .into_iter()
+            .map(|balance| ((balance.person, balance.currency), balance.outstanding))
+            .collect();
+        let mut transactions: Vec<&Transaction> = transactions.iter().collect();
+        transactions.sort_by(|a, b| a.created.partial_cmp(&b.created).unwrap());
+
+        let mut new = vec![];
+        for transaction in transactions {
+            let counterparty = match &transaction.counterparty {
+                Some(counterparty) => counterparty,
+                None => continue,
+            };
+            if transaction.amount <= 0
+                || transaction.decline_reason.is_some()
+                || self.splits.contains_key(&transaction.id)
+                || self.settlements.iter().any(|settlement| {
+                    settlement.transaction_id.as_deref() == Some(transaction.id.as_str())
+                })
+            {
+                continue;
+            }
+            let owed = outstanding.iter_mut().find(|((person, currency), owed)| {
+                **owed > 0
+                    && currency == &transaction.currency
+                    && match people.get(person) {
+                        Some(details) => details.matches(counterparty),
+                        None => Person::name_matches(person, counterparty),
+                    }
+                    && self
+                        .earliest_unpaid(person, currency)
+                        .is_some_and(|created| &transaction.created >= created)
+            });
+            if let Some(((person, currency), owed)) = owed {
+                let amount = transaction.amount.min(*owed);
+                *owed -= amount;
+                let settlement = Settlement {
+                    transaction_id: Some(transaction.id.clone()),
+                    person: person.clone(),
+                    created: transaction.created.clone(),
+                    currency: currency.clone(),
+                    amount,
+                };
+                self.settlements.push(settlement.clone());
+                new.push(settlement);
+            }
+        }
+        new
+    }
+}
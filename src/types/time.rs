@@ -155,6 +155,12 @@ impl Time {
     }
 }
 
+impl From<chrono::NaiveDateTime> for Time {
+    fn from(date_time: chrono::NaiveDateTime) -> Self {
+        Time(date_time)
+    }
+}
+
 impl serde::Serialize for Time {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -173,6 +179,18 @@ impl<'de> serde::Deserialize<'de> for Time {
     }
 }
 
+/// Deserializes a time that may be missing, `null` or an empty string, e.g. the `settled` time
+/// of a transaction that hasn't settled yet
+pub fn deserialize_optional<'de, D>(deserializer: D) -> Result<Option<Time>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match <Option<String> as serde::Deserialize>::deserialize(deserializer)? {
+        Some(s) if !s.is_empty() => TimeVisitor.visit_str(&s).map(Some),
+        _ => Ok(None),
+    }
+}
+
 struct TimeVisitor;
 
 impl<'de> Visitor<'de> for TimeVisitor {
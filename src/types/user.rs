@@ -1,3 +1,5 @@
+use std::collections::{BTreeMap, HashMap};
+
 use crate::types::*;
 
 /// A list of acccounts associated with a given user
@@ -7,22 +9,27 @@ pub struct User {
     pub access_token: AccessToken,
     pub user_id: String,
     pub transactions: Vec<Transaction>,
+    /// The token that clients of `serve` must use, generated the first time it's run
+    #[serde(default)]
+    pub serve_token: Option<String>,
+    /// Transactions shared with other people, see `split`
+    #[serde(default)]
+    pub splits: split::SplitLedger,
+    /// The last known balance of each account, by account id, for when Monzo can't be reached
+    #[serde(default)]
+    pub balances: BTreeMap<String, CachedBalance>,
 }
 
 impl User {
     pub fn new(access_token_response: AccessTokenResponse, accounts: Vec<Account>) -> Self {
-        let now = Time::now();
         Self {
             accounts,
-            user_id: access_token_response.user_id,
-            access_token: AccessToken {
-                token: access_token_response.access_token,
-                expires: now.add(&chrono::Duration::seconds(
-                    access_token_response.expires_in as _,
-                )),
-                created: now,
-            },
+            user_id: access_token_response.user_id.clone(),
+            access_token: AccessToken::new(access_token_response),
             transactions: Vec::new(),
+            serve_token: None,
+            splits: split::SplitLedger::default(),
+            balances: BTreeMap::new(),
         }
     }
 
@@ -35,10 +42,140 @@ impl User {
         Ok(Self::new(access_token_response, accounts))
     }
 
+    /// Replaces the access token and accounts after authorizing again. Everything that is only
+    /// kept in the user file (the cache, splits, imported accounts and the `serve` token) is
+    /// kept, unless a different Monzo user has authorized
+    pub fn reauthorize(
+        &mut self,
+        access_token_response: AccessTokenResponse,
+        accounts: Vec<Account>,
+    ) {
+        if self.user_id != access_token_response.user_id {
+            *self = Self::new(access_token_response, accounts);
+            return;
+        }
+        self.access_token = AccessToken::new(access_token_response);
+        commands::merge_accounts(&mut self.accounts, accounts);
+    }
+
+    /// Adds what a long running command has fetched into `synced` to this user, which has been
+    /// read from the user file since, keeping the newest access token and cached balances
+    pub fn merge_synced(&mut self, synced: &User, transactions: &[Transaction]) {
+        if synced.access_token.created > self.access_token.created {
+            self.access_token = synced.access_token.clone();
+        }
+        self.cache_transactions(transactions);
+        for (account_id, cached) in &synced.balances {
+            if self
+                .balances
+                .get(account_id)
+                .is_none_or(|stored| stored.fetched < cached.fetched)
+            {
+                self.balances.insert(account_id.clone(), cached.clone());
+            }
+        }
+    }
+
     /// Creates an authorized `Client` from this `User` object
     pub fn create_authorized_client(&self) -> reqwest::Client {
         client::new_client_with_authorization_header(&self.access_token.token)
     }
+
+    /// Adds `transactions` to the locally cached transactions, replacing any cached
+    /// transactions with the same id
+    pub fn cache_transactions(&mut self, transactions: &[Transaction]) {
+        let mut cached: HashMap<String, usize> = HashMap::new();
+        for (i, transaction) in self.transactions.iter().enumerate() {
+            cached.insert(transaction.id.clone(), i);
+        }
+        for transaction in transactions {
+            match cached.get(&transaction.id) {
+                Some(&i) => self.transactions[i] = transaction.clone(),
+                None => {
+                    cached.insert(transaction.id.clone(), self.transactions.len());
+                    self.transactions.push(transaction.clone());
+                }
+            }
+        }
+        self.transactions
+            .sort_by(|a, b| a.created.partial_cmp(&b.created).unwrap());
+    }
+
+    /// The time to fetch transactions from to bring the cache for an account up to date, this
+    /// goes back a couple of days before the latest cached transaction to catch transactions
+    /// that have changed since, and to the earliest cached transaction that is still pending so
+    /// that it is updated once it settles
+    pub fn resync_since(&self, account_id: &str) -> Time {
+        let earliest_since =
+            Time::now().add(&(chrono::Duration::days(-90) + chrono::Duration::seconds(100)));
+        let cached = self.cached_transactions(account_id);
+        match cached.last() {
+            Some(last) => {
+                let mut since = last.created.add(&chrono::Duration::days(-2));
+                if let Some(pending) = cached.iter().find(|transaction| transaction.is_pending()) {
+                    if pending.created < since {
+                        since = pending.created.clone();
+                    }
+                }
+                if since < earliest_since {
+                    earliest_since
+                } else {
+                    since
+                }
+            }
+            None => earliest_since,
+        }
+    }
+
+    /// The locally cached transactions for an account, oldest first
+    pub fn cached_transactions(&self, account_id: &str) -> Vec<&Transaction> {
+        self.transactions
+            .iter()
+            .filter(|transaction| transaction.account_id == account_id)
+            .collect()
+    }
+
+    /// The locally cached transactions for an account from `since` until `before`, oldest
+    /// first
+    pub fn cached_transactions_between(
+        &self,
+        account_id: &str,
+        since: Option<&Time>,
+        before: Option<&Time>,
+    ) -> Vec<&Transaction> {
+        self.cached_transactions(account_id)
+            .into_iter()
+            .filter(|transaction| {
+                since.is_none_or(|since| &transaction.created >= since)
+                    && before.is_none_or(|before| &transaction.created < before)
+            })
+            .collect()
+    }
+
+    /// Remembers the balance of an account, and its pots if they were fetched with it
+    pub fn cache_balance(&mut self, account_id: &str, balance: &Balance, pots: Option<&[Pot]>) {
+        let cached = self
+            .balances
+            .entry(account_id.to_owned())
+            .or_insert_with(|| CachedBalance {
+                balance: balance.clone(),
+                pots: vec![],
+                fetched: Time::now(),
+            });
+        cached.balance = balance.clone();
+        cached.fetched = Time::now();
+        if let Some(pots) = pots {
+            cached.pots = pots.to_vec();
+        }
+    }
+}
+
+/// A balance as it was when it was last fetched
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CachedBalance {
+    pub balance: Balance,
+    pub pots: Vec<Pot>,
+    pub fetched: Time,
 }
 
 /// Contains an access token as well as the timestamp at which the access token expires
@@ -47,4 +184,22 @@ pub struct AccessToken {
     pub token: String,
     pub expires: Time,
     pub created: Time,
+    /// Used to get a new access token without authorizing again, only confidential clients
+    /// are given one
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+}
+
+impl AccessToken {
+    pub fn new(access_token_response: AccessTokenResponse) -> Self {
+        let now = Time::now();
+        Self {
+            token: access_token_response.access_token,
+            expires: now.add(&chrono::Duration::seconds(
+                access_token_response.expires_in as _,
+            )),
+            created: now,
+            refresh_token: access_token_response.refresh_token,
+        }
+    }
 }
@@ -18,6 +18,23 @@ pub fn update_user_file(user: &types::user::User) -> std::io::Result<()> {
     Ok(())
 }
 
+/// Writes what `daemon` or `serve` has fetched into `user` (the access token, `transactions`
+/// and cached balances) to the user.json file. The file is read again first so that anything
+/// other commands have written since `user` was loaded is kept, and `user` is updated with it
+pub fn merge_into_user_file(
+    user: &mut types::user::User,
+    transactions: &[types::Transaction],
+) -> std::io::Result<()> {
+    match load_user_file() {
+        Ok(mut stored) => {
+            stored.merge_synced(user, transactions);
+            *user = stored;
+        }
+        Err(_) => user.cache_transactions(transactions),
+    }
+    update_user_file(user)
+}
+
 /// Gets the existing data in the user.json file
 pub fn load_user_file() -> std::io::Result<types::user::User> {
     let mut buf = Vec::new();
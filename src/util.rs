@@ -1,23 +1,205 @@
+use std::collections::BTreeMap;
+
 use crate::types::*;
 use crate::*;
 
+/// Describes an account by every selector that refers to it, for error messages
+fn describe_account(account_index: usize, account: &Account, aliases: &[&String]) -> String {
+    let mut description = format!(
+        "{}, ****{}, {}, {}",
+        account_index,
+        account
+            .account_number
+            .get(account.account_number.len().saturating_sub(4)..)
+            .unwrap_or(""),
+        account.account_type,
+        account.id
+    );
+    for alias in aliases {
+        description.push_str(&format!(", {}", alias));
+    }
+    if account.closed {
+        description.push_str(" (closed)");
+    }
+    description
+}
+
+/// Whether an account of type `account_type` is selected by `selector`, which is the type with
+/// or without `uk_`, or the end of it, e.g. `uk_retail_joint`, `retail_joint` or `joint`
+pub fn account_type_matches(account_type: &str, selector: &str) -> bool {
+    let account_type = account_type.to_lowercase();
+    let selector = selector.to_lowercase();
+    account_type == selector
+        || account_type.strip_prefix("uk_") == Some(selector.as_str())
+        || account_type.ends_with(&format!("_{}", selector))
+}
+
+/// Finds the account that `selector` refers to, a selector is one of
+/// - an index into `accounts`, e.g. `0`
+/// - an account id, e.g. `acc_00009...`
+/// - the account number, or the end of it, e.g. `35847686` or `****7686`
+/// - the account type, with or without `uk_`, e.g. `uk_retail_joint`, `retail_joint` or `joint`
+/// - a name in `aliases`, which maps to one of the above
+///
+/// When more than one account matches, open accounts are preferred
+pub fn resolve_account(
+    accounts: &[Account],
+    aliases: &BTreeMap<String, String>,
+    selector: &str,
+) -> Result<usize, error::BadArgumentError> {
+    let alias = selector;
+    let selector = aliases.get(selector).map_or(selector, String::as_str);
+    let matches = |account: &Account| -> bool {
+        if account.id == selector
+            || account.account_number == selector
+            || account.import_source() == Some(selector)
+        {
+            return true;
+        }
+        if let Some(end) = selector.strip_prefix('*') {
+            let end = end.trim_start_matches('*');
+            return !end.is_empty() && account.account_number.ends_with(end);
+        }
+        account_type_matches(&account.account_type, selector)
+    };
+
+    let mut found: Vec<usize> = match selector.parse::<usize>() {
+        Ok(i) if i < accounts.len() => vec![i],
+        _ => accounts
+            .iter()
+            .enumerate()
+            .filter(|(_, account)| matches(account))
+            .map(|(i, _)| i)
+            .collect(),
+    };
+    if found.len() > 1 {
+        found.retain(|i| !accounts[*i].closed);
+    }
+    if found.len() == 1 {
+        return Ok(found[0]);
+    }
+
+    let choices: Vec<String> = accounts
+        .iter()
+        .enumerate()
+        .map(|(i, account)| {
+            let account_aliases: Vec<&String> = aliases
+                .iter()
+                .filter(|(_, selector)| {
+                    resolve_account(accounts, &BTreeMap::new(), selector).ok() == Some(i)
+                })
+                .map(|(alias, _)| alias)
+                .collect();
+            format!("\n  {}", describe_account(i, account, &account_aliases))
+        })
+        .collect();
+    Err(error::BadArgumentError(format!(
+        "`{}` {}, use one of{}",
+        alias,
+        if found.is_empty() {
+            "doesn't match any account"
+        } else {
+            "matches more than one account"
+        },
+        choices.concat()
+    )))
+}
+
+/// The account used when none is selected, the first open Monzo retail account, or else the
+/// first open Monzo account. `None` if there are no accounts
+pub fn default_account(accounts: &[Account]) -> Option<usize> {
+    let open = |account: &&Account| !account.closed && account.import_source().is_none();
+    accounts
+        .iter()
+        .position(|account| open(&account) && account.account_type == "uk_retail")
+        .or_else(|| accounts.iter().position(|account| open(&account)))
+        .or_else(|| accounts.iter().position(|account| !account.closed))
+        .or_else(|| (!accounts.is_empty()).then_some(0))
+}
+
+/// Gets the account selected by the `account` kwarg (see `resolve_account`), defaults to the
+/// first open retail account (see `default_account`)
 pub fn get_account_index(
     user: &user::User,
     command: &cli::Command,
 ) -> Result<usize, Box<dyn std::error::Error>> {
-    Ok(if let Some(n) = command.uint_kwarg::<u64>("account") {
-        let i = n? as usize;
-        if user.accounts.len() >= i - 1 {
-            Ok(i)
-        } else {
-            Err(error::BadArgumentError(format!(
-                "the account index [{}] doest not exist for this user",
-                i
-            )))
-        }?
+    let selector = match command.kwargs.get("account") {
+        Some(selector) => selector,
+        None => {
+            return default_account(&user.accounts).ok_or_else(|| {
+                error::BadArgumentError(
+                    "there are no accounts, run `accounts refresh` and try again".to_owned(),
+                )
+                .into()
+            })
+        }
+    };
+    let config = config_file::load_config_file()?;
+    Ok(resolve_account(&user.accounts, &config.accounts, selector)?)
+}
+
+/// Gets the `since` and `before` kwargs as times, `since` defaults to the earliest time that
+/// the api allows us to query without having recently authorized
+pub fn get_time_range(
+    user: &user::User,
+    command: &cli::Command,
+) -> Result<(Option<time::Time>, Option<time::Time>), Box<dyn std::error::Error>> {
+    let now = time::Time::now();
+    let before = if let Some(time_str) = command.kwargs.get("before") {
+        Some(time::Time::try_parse_str(time_str)?)
     } else {
-        0
-    })
+        None
+    };
+    let earliest_since = now.add(&(chrono::Duration::days(-90) + chrono::Duration::seconds(100)));
+    let since = if let Some(time_str) = command.kwargs.get("since") {
+        let since = time::Time::try_parse_str(time_str)?;
+        // the cache isn't limited to 90 days
+        if since < earliest_since
+            && !commands::is_offline(command)
+            && user.access_token.created < now.add(&chrono::Duration::seconds(-60 * 4 - 30))
+        {
+            return Err(error::AuthorizationError::Custom(
+                "cannot access more than 90d of transactions, unles the user has been authorized \
+                in the last 5 minutes\nrun `auth` and try again"
+                    .to_owned(),
+            )
+            .into());
+        }
+        Some(since)
+    } else {
+        Some(earliest_since)
+    };
+    Ok((since, before))
+}
+
+/// Gets the `format` kwarg, defaults to `OutputType::Display`
+pub fn get_output_type(command: &cli::Command) -> Result<OutputType, error::InvalidArgumentError> {
+    match command.kwargs.get("format") {
+        Some(output_type_str) => OutputType::from_str(output_type_str),
+        None => Ok(OutputType::Display),
+    }
+}
+
+/// Gets the `filter` kwarg, if there is no filter then every transaction is matched
+pub fn get_filter(command: &cli::Command) -> Result<filter::Filter, error::BadArgumentError> {
+    match command.kwargs.get("filter") {
+        Some(filter_str) => filter::Filter::parse(filter_str),
+        None => Ok(filter::Filter::All),
+    }
+}
+
+/// Gets the `template` kwarg, which is either the name of a template in the config or a
+/// template itself
+pub fn get_template(
+    command: &cli::Command,
+) -> Result<Option<cli::template::Template>, Box<dyn std::error::Error>> {
+    let template_str = match command.kwargs.get("template") {
+        Some(template_str) => template_str,
+        None => return Ok(None),
+    };
+    let config = config_file::load_config_file()?;
+    let template_str = config.templates.get(template_str).unwrap_or(template_str);
+    Ok(Some(cli::template::Template::parse(template_str)?))
 }
 
 pub struct FmtCurrencyOptions {
@@ -34,42 +216,32 @@ impl Default for FmtCurrencyOptions {
     }
 }
 
+/// Formats an amount of minor currency units with its currency, e.g. `-1205` GBP is `-£12.05`
+/// and `2550` EUR is `25.50 EUR`
 pub fn fmt_currency(amount: i32, currency: &str, options: &FmtCurrencyOptions) -> String {
     let amount_is_negative = amount.is_negative();
-    let amount = amount.abs();
-    let fmt_currency = |symbol: &str, delimeter: &str, minor_per_major: i32| {
-        let major_units = amount / minor_per_major;
-        let minor_units = amount - major_units * minor_per_major;
-        format!(
-            "{}{}{}{}{}",
-            if amount_is_negative {
-                "-"
-            } else {
-                if options.include_positive_sign {
-                    "+"
-                } else {
-                    ""
-                }
-            },
-            symbol,
-            major_units,
-            delimeter,
-            minor_units,
-        )
+    let sign = if amount_is_negative {
+        "-"
+    } else if options.include_positive_sign {
+        "+"
+    } else {
+        ""
     };
+    let decimal = fmt_decimal(amount, currency);
+    let decimal = decimal.trim_start_matches('-');
     let string = match currency {
-        "GBP" => fmt_currency("£", ".", 100),
-        _ => format!("{} {}", amount.to_string(), currency),
+        "GBP" => format!("{}£{}", sign, decimal),
+        _ => format!("{}{} {}", sign, decimal, currency),
     };
     if options.colored {
         if amount_is_negative {
             cli::AnsiStringBuilder::new()
-                .set_foreground_color(255, 20, 20)
+                .set_style(&cli::theme().debit)
                 .push_str(&string)
                 .build()
         } else {
             cli::AnsiStringBuilder::new()
-                .set_foreground_color(20, 255, 20)
+                .set_style(&cli::theme().credit)
                 .push_str(&string)
                 .build()
         }
@@ -78,6 +250,35 @@ pub fn fmt_currency(amount: i32, currency: &str, options: &FmtCurrencyOptions) -
     }
 }
 
+/// The number of digits after the decimal point for an ISO 4217 currency
+pub fn currency_exponent(currency: &str) -> u32 {
+    match currency {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+/// Formats an amount of minor currency units as a signed decimal number of major units with
+/// no currency symbol, e.g. `-1234` GBP is `-12.34`
+pub fn fmt_decimal(amount: i32, currency: &str) -> String {
+    let exponent = currency_exponent(currency);
+    let sign = if amount.is_negative() { "-" } else { "" };
+    let amount = (amount as i64).abs();
+    if exponent == 0 {
+        return format!("{}{}", sign, amount);
+    }
+    let minor_per_major = 10i64.pow(exponent);
+    format!(
+        "{}{}.{:0width$}",
+        sign,
+        amount / minor_per_major,
+        amount % minor_per_major,
+        width = exponent as usize
+    )
+}
+
 pub fn unwrap_to_string<'a, T: std::fmt::Display>(option: &'a Option<T>, or: &'a str) -> String {
     match option {
         Some(t) => t.to_string(),
@@ -105,4 +306,21 @@ pub mod serde_csv {
             }
         })
     }
+
+    /// Serializes every record, with a single header line
+    pub fn records_to_string<T: serde::Serialize>(
+        records: impl IntoIterator<Item = T>,
+    ) -> Result<String, io::Error> {
+        let mut output = String::new();
+        for (i, record) in records.into_iter().enumerate() {
+            let record = to_string(record)?;
+            // only keep the header for the first record
+            output.push_str(if i == 0 {
+                &record
+            } else {
+                record.split_once('\n').map(|(_, r)| r).unwrap_or("")
+            });
+        }
+        Ok(output)
+    }
 }